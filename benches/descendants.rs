@@ -0,0 +1,43 @@
+//! Collects the descendants of a wide tree, growing the vector as it goes
+//! or presizing it from a count of the subtree taken first.
+//!
+//! Run with `cargo bench --bench descendants`.
+
+use std::time::{Duration, Instant};
+
+use generational_indextree::{Arena, NodeId};
+
+const CHILDREN: usize = 1_000_000;
+const RUNS: usize = 10;
+
+/// Returns the fastest of `RUNS` runs of `f`.
+fn fastest<F: FnMut() -> usize>(mut f: F) -> Duration {
+    (0..RUNS)
+        .map(|_| {
+            let start = Instant::now();
+            assert_eq!(f(), CHILDREN + 1);
+            start.elapsed()
+        })
+        .min()
+        .unwrap()
+}
+
+fn main() {
+    let arena = &mut Arena::with_capacity(CHILDREN + 1);
+    let root = arena.new_node(0);
+    for data in 1..=CHILDREN {
+        let child = arena.new_node(data);
+        root.append(child, arena);
+    }
+
+    let growing = fastest(|| root.descendants(arena).collect::<Vec<NodeId>>().len());
+    let presized = fastest(|| {
+        let iter = root.descendants(arena);
+        let mut ids = Vec::with_capacity(iter.count_exact());
+        ids.extend(iter);
+        ids.len()
+    });
+    println!("descendants of a root with {} children:", CHILDREN);
+    println!("  collect:                  {:?}", growing);
+    println!("  count_exact, then extend: {:?}", presized);
+}
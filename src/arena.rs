@@ -1,31 +1,168 @@
 //! Arena.
 
+#[cfg(not(feature = "std"))]
+use alloc::{
+    collections::{BTreeMap, BTreeSet},
+    format,
+    string::String,
+    vec,
+    vec::Vec,
+};
 #[cfg(not(feature = "std"))]
 use core::{
+    cmp::Ordering,
+    iter::FromIterator,
+    mem,
     num::NonZeroUsize,
     ops::{Index, IndexMut},
 };
+#[cfg(all(not(feature = "std"), debug_assertions, target_has_atomic = "ptr"))]
+use core::sync::atomic::{AtomicUsize, Ordering as AtomicOrdering};
 #[cfg(feature = "std")]
 use std::{
+    cmp::Ordering,
+    collections::{BTreeMap, BTreeSet, HashMap},
+    hash::Hash,
+    iter::FromIterator,
+    mem,
     ops::{Index, IndexMut},
 };
+#[cfg(all(feature = "std", debug_assertions, target_has_atomic = "ptr"))]
+use std::sync::atomic::{AtomicUsize, Ordering as AtomicOrdering};
 
 use generational_arena::Arena as GenerationalArena;
 #[cfg(feature = "deser")]
 use serde::{Deserialize, Serialize};
+#[cfg(all(feature = "std", feature = "deser"))]
+use serde::{de::Error as _, Deserializer, Serializer};
+#[cfg(feature = "json")]
+use serde::de::DeserializeOwned;
+#[cfg(feature = "json")]
+use std::io;
 
 #[cfg(feature = "par_iter")]
 use rayon::prelude::*;
 
-use crate::{Node, NodeId};
+#[cfg(feature = "std")]
+use crate::id::node_hash;
+use crate::{
+    ChildBuilder, Descendants, Node, NodeEdge, NodeError, NodeId, NodeMut, NodeRef, Tree,
+};
+#[cfg(feature = "deser")]
+use crate::nested::SerializeForest;
 
 #[derive(Clone, Debug)]
 #[cfg_attr(feature = "deser", derive(Deserialize, Serialize))]
+#[cfg_attr(feature = "deser", serde(from = "SerdeArena<T>"))]
 /// An `Arena` structure containing certain [`Node`]s.
 ///
+/// Nodes refer to each other through `NodeId`s instead of owning pointers and
+/// are stored in a flat buffer, so dropping an arena never recurses, no matter
+/// how deep the trees in it are.
+///
 /// [`Node`]: struct.Node.html
 pub struct Arena<T> {
     pub(crate) nodes: GenerationalArena<Node<T>>,
+    /// The stable ID given to the next created node.
+    next_stable_id: u64,
+    /// Maximum number of live nodes, if any.
+    #[cfg_attr(feature = "deser", serde(default))]
+    capacity_limit: Option<usize>,
+    /// Unique stamp of this arena, copied into the `NodeId`s it creates.
+    #[cfg(debug_assertions)]
+    #[cfg_attr(feature = "deser", serde(skip))]
+    pub(crate) arena_id: usize,
+}
+
+/// The serialized form of an `Arena`, which may come from a version of the
+/// crate without stable IDs or without the IDs of nodes.
+#[cfg(feature = "deser")]
+#[derive(Deserialize)]
+struct SerdeArena<T> {
+    nodes: GenerationalArena<Node<T>>,
+    #[serde(default)]
+    next_stable_id: u64,
+    #[serde(default)]
+    capacity_limit: Option<usize>,
+}
+
+#[cfg(feature = "deser")]
+impl<T> From<SerdeArena<T>> for Arena<T> {
+    fn from(arena: SerdeArena<T>) -> Arena<T> {
+        let SerdeArena {
+            mut nodes,
+            next_stable_id,
+            capacity_limit,
+        } = arena;
+        // Nodes written without their own ID read a placeholder.
+        for (index, node) in nodes.iter_mut() {
+            node.id = NodeId::from_raw_index(index, 0);
+        }
+        // Nodes written without stable IDs all read as `0`, so give the nodes
+        // new stable IDs if they are not unique.
+        let mut stable_ids = BTreeSet::new();
+        if !nodes.iter().all(|(_, node)| stable_ids.insert(node.stable_id)) {
+            for (stable_id, (_, node)) in (0..).zip(nodes.iter_mut()) {
+                node.stable_id = stable_id;
+            }
+        }
+        // The counter may be missing or behind the nodes, so never hand out a
+        // stable ID which is already taken.
+        let next_stable_id = nodes
+            .iter()
+            .map(|(_, node)| node.stable_id.saturating_add(1))
+            .fold(next_stable_id, u64::max);
+        Arena {
+            nodes,
+            next_stable_id,
+            capacity_limit,
+            // Like the IDs written with them, deserialized arenas are not
+            // stamped.
+            #[cfg(debug_assertions)]
+            arena_id: 0,
+        }
+    }
+}
+
+/// A node as written by [`Arena::serialize_with_stable_ids`].
+///
+/// [`Arena::serialize_with_stable_ids`]: struct.Arena.html#method.serialize_with_stable_ids
+#[cfg(all(feature = "std", feature = "deser"))]
+#[derive(Deserialize, Serialize)]
+struct StableNode<U> {
+    id: u64,
+    parent: Option<u64>,
+    data: U,
+}
+
+/// Source of the arena stamps. `0` is reserved for "unknown".
+#[cfg(all(debug_assertions, target_has_atomic = "ptr"))]
+static NEXT_ARENA_ID: AtomicUsize = AtomicUsize::new(1);
+
+/// Returns a fresh arena stamp.
+#[cfg(all(debug_assertions, target_has_atomic = "ptr"))]
+fn next_arena_id() -> usize {
+    NEXT_ARENA_ID.fetch_add(1, AtomicOrdering::Relaxed)
+}
+
+/// Returns the "unknown" stamp on targets without atomics, which turns the
+/// foreign ID checks off.
+#[cfg(all(debug_assertions, not(target_has_atomic = "ptr")))]
+fn next_arena_id() -> usize {
+    0
+}
+
+/// Ensures the given node ID was created by the given arena.
+///
+/// This assert is only enabled in debug build.
+macro_rules! debug_assert_owned_by {
+    ($arena:expr, $id:expr $(,)?) => {{
+        debug_assert!(
+            $id.belongs_to($arena),
+            "The node ID {} was not created by this arena",
+            $id
+        );
+    }};
 }
 
 impl<T> Arena<T> {
@@ -35,13 +172,148 @@ impl<T> Arena<T> {
     }
 
     /// Create a new empty `Arena` with pre-allocated memory for `n` items.
-    pub fn with_capacity(n: usize) -> Arena<T> { Self { nodes: GenerationalArena::with_capacity(n) } }
+    ///
+    /// Slots of removed nodes are reused, so to size an arena for the trees
+    /// it will hold, pass the largest number of nodes alive at the same time,
+    /// across all trees of the arena. The arena still grows when the estimate
+    /// is exceeded.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use generational_indextree::Arena;
+    /// let mut arena = Arena::with_capacity(2);
+    /// let foo = arena.try_new_node("foo").unwrap();
+    /// let bar = arena.try_new_node("bar").unwrap();
+    /// foo.append(bar, &mut arena);
+    /// ```
+    pub fn with_capacity(n: usize) -> Arena<T> {
+        Self::from_nodes(GenerationalArena::with_capacity(n))
+    }
+
+    /// Create a new empty `Arena` which can hold at most `n` nodes at a time.
+    ///
+    /// The memory for all `n` nodes is allocated upfront. When the arena is
+    /// full, [`try_new_node`], [`new_node_or_evict`] and the deserializing
+    /// methods fail instead of growing it, while [`new_node`] and the other
+    /// methods creating nodes panic. Removing nodes makes room again.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use generational_indextree::Arena;
+    /// let mut arena = Arena::with_capacity_limit(1);
+    /// let foo = arena.try_new_node("foo").unwrap();
+    /// assert_eq!(arena.try_new_node("bar"), Err("bar"));
+    /// assert_eq!(arena.capacity_limit(), Some(1));
+    ///
+    /// foo.remove(&mut arena);
+    /// assert!(arena.try_new_node("bar").is_ok());
+    /// ```
+    ///
+    /// [`new_node`]: struct.Arena.html#method.new_node
+    /// [`try_new_node`]: struct.Arena.html#method.try_new_node
+    /// [`new_node_or_evict`]: struct.Arena.html#method.new_node_or_evict
+    pub fn with_capacity_limit(n: usize) -> Arena<T> {
+        let mut arena = Self::with_capacity(n);
+        arena.capacity_limit = Some(n);
+        arena
+    }
+
+    /// Creates an arena holding the given trees, and returns it with the IDs
+    /// of their roots in order.
+    ///
+    /// The nodes of each tree are created in preorder. Deep trees are built
+    /// without recursion.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use generational_indextree::{Arena, Tree};
+    /// let (arena, roots) = Arena::from_nested(vec![
+    ///     Tree::new("1", vec![Tree::leaf("1_1"), Tree::new("1_2", vec![Tree::leaf("1_2_1")])]),
+    ///     Tree::leaf("2"),
+    /// ]);
+    /// // arena
+    /// // |-- 1
+    /// // |   |-- 1_1
+    /// // |   `-- 1_2
+    /// // |       `-- 1_2_1
+    /// // `-- 2
+    ///
+    /// assert_eq!(roots.len(), 2);
+    /// let data = roots[0].descendants(&arena).map(|id| *arena[id].get()).collect::<Vec<_>>();
+    /// assert_eq!(data, ["1", "1_1", "1_2", "1_2_1"]);
+    /// assert_eq!(*arena[roots[1]].get(), "2");
+    /// ```
+    pub fn from_nested<I: IntoIterator<Item = Tree<T>>>(spec: I) -> (Arena<T>, Vec<NodeId>) {
+        let mut arena = Arena::new();
+        let mut roots = Vec::new();
+        for tree in spec {
+            let root = arena.new_node(tree.data);
+            roots.push(root);
+            // The children still to be created, for each node of the path.
+            let mut stack = vec![(root, tree.children.into_iter())];
+            while let Some((parent, children)) = stack.last_mut() {
+                let parent = *parent;
+                match children.next() {
+                    Some(child) => {
+                        let id = arena.new_node(child.data);
+                        parent.append(id, &mut arena);
+                        stack.push((id, child.children.into_iter()));
+                    }
+                    None => {
+                        stack.pop();
+                    }
+                }
+            }
+        }
+        (arena, roots)
+    }
+
+    /// Returns the maximum number of nodes the arena can hold, if it was
+    /// created with [`with_capacity_limit`].
+    ///
+    /// [`with_capacity_limit`]: struct.Arena.html#method.with_capacity_limit
+    pub fn capacity_limit(&self) -> Option<usize> {
+        self.capacity_limit
+    }
+
+    /// Returns `true` if the arena has a capacity limit and has reached it.
+    pub(crate) fn is_at_limit(&self) -> bool {
+        matches!(self.capacity_limit, Some(limit) if self.nodes.len() >= limit)
+    }
+
+    /// Returns the stamp copied into the `NodeId`s of this arena, `0` in
+    /// release builds.
+    pub(crate) fn stamp(&self) -> usize {
+        #[cfg(debug_assertions)]
+        let stamp = self.arena_id;
+        #[cfg(not(debug_assertions))]
+        let stamp = 0;
+        stamp
+    }
+
+    /// Wraps the given node storage, stamping the new arena in debug builds.
+    fn from_nodes(nodes: GenerationalArena<Node<T>>) -> Arena<T> {
+        Self {
+            nodes,
+            next_stable_id: 0,
+            capacity_limit: None,
+            #[cfg(debug_assertions)]
+            arena_id: next_arena_id(),
+        }
+    }
 
     /// Creates a new node from its associated data.
     ///
+    /// The node starts out as a root of its own, so this is also how a new
+    /// top-level tree is added to a forest.
+    ///
     /// # Panics
     ///
-    /// Panics if the arena already has `usize::max_value()` nodes.
+    /// Panics if the arena already has `usize::max_value()` nodes, or has
+    /// reached its [capacity limit].
     ///
     /// # Examples
     ///
@@ -52,8 +324,128 @@ impl<T> Arena<T> {
     ///
     /// assert_eq!(*arena[foo].get(), "foo");
     /// ```
+    ///
+    /// [capacity limit]: struct.Arena.html#method.with_capacity_limit
     pub fn new_node(&mut self, data: T) -> NodeId {
-        NodeId::from_index(self.nodes.insert(Node::new(data)))
+        assert!(!self.is_at_limit(), "The arena has reached its capacity limit");
+        let (stable_id, stamp) = (self.next_stable_id, self.stamp());
+        let index = self
+            .nodes
+            .insert_with(|index| Node::new(data, stable_id, NodeId::from_raw_index(index, stamp)));
+        self.next_stable_id += 1;
+        NodeId::from_index(index, self)
+    }
+
+    /// Creates a new node from its associated data, lets `build` add children
+    /// to it, and returns its ID.
+    ///
+    /// The closure gets a [`ChildBuilder`] borrowing the arena, so nested
+    /// trees can be written as nested closures.
+    ///
+    /// # Panics
+    ///
+    /// Panics under the same conditions as [`new_node`], for any of the
+    /// created nodes.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use generational_indextree::Arena;
+    /// let mut arena = Arena::new();
+    /// let list = arena.new_node_with("list", |list| {
+    ///     list.child("item 1");
+    ///     list.child("item 2");
+    /// });
+    ///
+    /// let data = list.children(&arena).map(|id| *arena[id].get()).collect::<Vec<_>>();
+    /// assert_eq!(data, vec!["item 1", "item 2"]);
+    /// ```
+    ///
+    /// [`ChildBuilder`]: struct.ChildBuilder.html
+    /// [`new_node`]: struct.Arena.html#method.new_node
+    pub fn new_node_with<F: FnOnce(&mut ChildBuilder<'_, T>)>(
+        &mut self,
+        data: T,
+        build: F,
+    ) -> NodeId {
+        let id = self.new_node(data);
+        build(&mut ChildBuilder::new(self, id));
+        id
+    }
+
+    /// Attempts to create a new node from its associated data, handing the
+    /// data back as the error if there is no room for it.
+    ///
+    /// An arena with a [capacity limit] has room until it holds that many
+    /// nodes, and allocates if needed up to then. Any other arena only has
+    /// room in the slots it already has: unlike [`new_node`], this never
+    /// grows it.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use generational_indextree::Arena;
+    /// let mut arena = Arena::with_capacity(1);
+    /// let foo = arena.try_new_node("foo");
+    /// assert!(foo.is_ok());
+    ///
+    /// assert_eq!(arena.try_new_node("bar"), Err("bar"));
+    ///
+    /// foo.unwrap().remove(&mut arena);
+    /// assert!(arena.try_new_node("bar").is_ok());
+    /// ```
+    ///
+    /// [`new_node`]: struct.Arena.html#method.new_node
+    /// [capacity limit]: struct.Arena.html#method.with_capacity_limit
+    pub fn try_new_node(&mut self, data: T) -> Result<NodeId, T> {
+        let full = match self.capacity_limit {
+            Some(_) => self.is_at_limit(),
+            // The node storage only grows when it has no free slot left.
+            None => self.nodes.len() == self.nodes.capacity(),
+        };
+        if full {
+            return Err(data);
+        }
+        Ok(self.new_node(data))
+    }
+
+    /// Creates a new node from its associated data, calling `on_full` to
+    /// make room if the arena has reached its [capacity limit].
+    ///
+    /// `on_full` is called at most once, and is expected to remove nodes,
+    /// e.g. the least recently used ones. If the arena is still at its limit
+    /// afterwards, the data is handed back as the error. An arena without a
+    /// limit is never full, and grows like with [`new_node`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use generational_indextree::Arena;
+    /// let mut arena = Arena::with_capacity_limit(2);
+    /// let foo = arena.new_node("foo");
+    /// let _bar = arena.new_node("bar");
+    ///
+    /// let baz = arena.new_node_or_evict("baz", |arena| foo.remove(arena));
+    /// assert!(baz.is_ok());
+    /// assert!(arena.get(foo).is_none());
+    ///
+    /// assert_eq!(arena.new_node_or_evict("qux", |_| {}), Err("qux"));
+    /// ```
+    ///
+    /// [`new_node`]: struct.Arena.html#method.new_node
+    /// [capacity limit]: struct.Arena.html#method.with_capacity_limit
+    pub fn new_node_or_evict<F: FnOnce(&mut Arena<T>)>(
+        &mut self,
+        data: T,
+        on_full: F,
+    ) -> Result<NodeId, T> {
+        if self.is_at_limit() {
+            on_full(self);
+            if self.is_at_limit() {
+                return Err(data);
+            }
+        }
+        Ok(self.new_node(data))
     }
 
     /// Counts the number of nodes in arena and returns it.
@@ -93,6 +485,59 @@ impl<T> Arena<T> {
         self.count() == 0
     }
 
+    /// Returns the number of allocated slots, holding a node or free.
+    ///
+    /// Unlike [`count`], this includes the slots of removed nodes, which are
+    /// reused by the next created nodes. The difference between both tells
+    /// how much of the storage is currently unused.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use generational_indextree::Arena;
+    /// let mut arena = Arena::with_capacity(4);
+    /// let foo = arena.new_node("foo");
+    /// assert_eq!((arena.count(), arena.slot_count()), (1, 4));
+    ///
+    /// foo.remove(&mut arena);
+    /// assert_eq!((arena.count(), arena.slot_count()), (0, 4));
+    /// ```
+    ///
+    /// [`count`]: struct.Arena.html#method.count
+    pub fn slot_count(&self) -> usize {
+        self.nodes.capacity()
+    }
+
+    /// Returns a rough estimate, in bytes, of the memory used by the arena.
+    ///
+    /// This counts the arena itself and every allocated slot, whether it holds
+    /// a node or not, each slot being a [`Node`] plus the generation used to
+    /// tell reused slots apart. The estimate is only approximate: it ignores
+    /// the padding and tags the underlying storage may add, and anything `T`
+    /// allocates on the heap, such as the buffer of a `String`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use generational_indextree::{Arena, Node};
+    /// use std::mem::size_of;
+    ///
+    /// let mut arena = Arena::with_capacity(16);
+    /// let empty = arena.memory_usage();
+    /// assert!(empty >= 16 * size_of::<Node<u32>>());
+    ///
+    /// // Slots are allocated upfront, so filling them does not change the
+    /// // estimate.
+    /// arena.new_node(1u32);
+    /// assert_eq!(arena.memory_usage(), empty);
+    /// ```
+    ///
+    /// [`Node`]: struct.Node.html
+    pub fn memory_usage(&self) -> usize {
+        let slot = mem::size_of::<Node<T>>() + mem::size_of::<u64>();
+        mem::size_of::<Self>() + self.nodes.capacity() * slot
+    }
+
     /// Returns a reference to the node with the given id if in the arena.
     ///
     /// Returns `None` if not available.
@@ -106,23 +551,32 @@ impl<T> Arena<T> {
     /// assert_eq!(arena.get(foo).map(|node| *node.get()), Some("foo"));
     /// ```
     ///
-    /// Note that this does not check whether the given node ID is created by
-    /// the arena.
+    /// Note that only debug builds check whether the given node ID is created
+    /// by the arena, and panic if it is not. Release builds skip the check,
+    /// so an ID from another arena silently refers to whatever node sits in
+    /// the same slot.
+    pub fn get(&self, id: NodeId) -> Option<&Node<T>> {
+        debug_assert_owned_by!(self, id);
+        self.nodes.get(id.get_index())
+    }
+
+    /// Returns a cursor on the node with the given id if in the arena.
+    ///
+    /// Returns `None` if not available.
+    ///
+    /// # Examples
     ///
     /// ```
     /// # use generational_indextree::Arena;
     /// let mut arena = Arena::new();
     /// let foo = arena.new_node("foo");
-    /// let bar = arena.new_node("bar");
-    /// assert_eq!(arena.get(foo).map(|node| *node.get()), Some("foo"));
+    /// assert_eq!(arena.node_ref(foo).map(|node| *node.data()), Some("foo"));
     ///
-    /// let mut another_arena = Arena::new();
-    /// let _ = another_arena.new_node("Another arena");
-    /// assert_eq!(another_arena.get(foo).map(|node| *node.get()), Some("Another arena"));
-    /// assert!(another_arena.get(bar).is_none());
+    /// foo.remove(&mut arena);
+    /// assert!(arena.node_ref(foo).is_none());
     /// ```
-    pub fn get(&self, id: NodeId) -> Option<&Node<T>> {
-        self.nodes.get(id.get_index())
+    pub fn node_ref(&self, id: NodeId) -> Option<NodeRef<'_, T>> {
+        self.get(id).map(|_| NodeRef::new(self, id))
     }
 
     /// Returns a mutable reference to the node with the given id if in the
@@ -142,9 +596,29 @@ impl<T> Arena<T> {
     /// assert_eq!(arena.get(foo).map(|node| *node.get()), Some("FOO!"));
     /// ```
     pub fn get_mut(&mut self, id: NodeId) -> Option<&mut Node<T>> {
+        debug_assert_owned_by!(self, id);
         self.nodes.get_mut(id.get_index())
     }
 
+    /// Returns a mutable cursor on the node with the given id if in the arena.
+    ///
+    /// Returns `None` if not available.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use generational_indextree::Arena;
+    /// let mut arena = Arena::new();
+    /// let foo = arena.new_node("foo");
+    /// let bar = arena.node_mut(foo).unwrap().append_new_child("bar").id();
+    ///
+    /// assert_eq!(arena[bar].parent(), Some(foo));
+    /// ```
+    pub fn node_mut(&mut self, id: NodeId) -> Option<NodeMut<'_, T>> {
+        self.get(id)?;
+        Some(NodeMut::new(self, id))
+    }
+
     /// Get a pair of exclusive references to the elements at index `i1` and `i2` if it is in the
     /// arena.
     ///
@@ -175,76 +649,1800 @@ impl<T> Arena<T> {
     /// assert_eq!(arena[idx2].get(), &"saw");
     /// ```
     pub fn get2_mut(&mut self, i1: NodeId, i2: NodeId) -> (Option<&mut Node<T>>, Option<&mut Node<T>>) {
+        debug_assert_owned_by!(self, i1);
+        debug_assert_owned_by!(self, i2);
         self.nodes.get2_mut(i1.get_index(), i2.get_index())
     }
 
-    /// Returns an iterator of all nodes in the arena in storage-order.
+    /// Returns exclusive references to all the given nodes at once, in the
+    /// order of `ids`.
+    ///
+    /// This generalizes [`get2_mut`] to any number of nodes. All IDs are
+    /// checked before any reference is handed out. Up to two nodes are looked
+    /// up directly. The storage only hands out two exclusive references at a
+    /// time, so more nodes are found with a single walk over the storage,
+    /// which stops at the last of them.
+    ///
+    /// # Failures
+    ///
+    /// * Returns [`NodeError::Removed`] error if one of the nodes is not in
+    ///   the arena.
+    /// * Returns [`NodeError::DuplicateNode`] error if a node is given twice.
     ///
     /// # Examples
     ///
     /// ```
     /// # use generational_indextree::Arena;
     /// let mut arena = Arena::new();
-    /// let _foo = arena.new_node("foo");
-    /// let _bar = arena.new_node("bar");
+    /// let ids = (0..4).map(|data| arena.new_node(data)).collect::<Vec<_>>();
     ///
-    /// let mut iter = arena.iter();
-    /// assert_eq!(iter.next().map(|node| *node.get()), Some("foo"));
-    /// assert_eq!(iter.next().map(|node| *node.get()), Some("bar"));
-    /// assert_eq!(iter.next().map(|node| *node.get()), None);
+    /// for node in arena.get_disjoint_mut(&[ids[3], ids[1]]).unwrap() {
+    ///     *node.get_mut() *= 10;
+    /// }
+    /// let data = arena.iter().map(|node| *node.get()).collect::<Vec<_>>();
+    /// assert_eq!(data, vec![0, 10, 2, 30]);
+    ///
+    /// assert!(arena.get_disjoint_mut(&[ids[0], ids[0]]).is_err());
+    /// ids[2].remove(&mut arena);
+    /// assert!(arena.get_disjoint_mut(&[ids[0], ids[2]]).is_err());
     /// ```
     ///
+    /// [`get2_mut`]: struct.Arena.html#method.get2_mut
+    /// [`NodeError::Removed`]: enum.NodeError.html#variant.Removed
+    /// [`NodeError::DuplicateNode`]: enum.NodeError.html#variant.DuplicateNode
+    pub fn get_disjoint_mut(&mut self, ids: &[NodeId]) -> Result<Vec<&mut Node<T>>, NodeError> {
+        let mut positions = BTreeMap::new();
+        for (position, &id) in ids.iter().enumerate() {
+            debug_assert_owned_by!(self, id);
+            if !self.nodes.contains(id.get_index()) {
+                return Err(NodeError::Removed);
+            }
+            if positions.insert(id.get_index(), position).is_some() {
+                return Err(NodeError::DuplicateNode);
+            }
+        }
+
+        let nodes = match *ids {
+            [] => Vec::new(),
+            [a] => vec![self.nodes.get_mut(a.get_index())],
+            [a, b] => {
+                let (a, b) = self.nodes.get2_mut(a.get_index(), b.get_index());
+                vec![a, b]
+            }
+            _ => {
+                let mut nodes = ids.iter().map(|_| None).collect::<Vec<_>>();
+                let mut left = ids.len();
+                for (index, node) in self.nodes.iter_mut() {
+                    if let Some(&position) = positions.get(&index) {
+                        nodes[position] = Some(node);
+                        left -= 1;
+                        if left == 0 {
+                            break;
+                        }
+                    }
+                }
+                nodes
+            }
+        };
+        Ok(nodes
+            .into_iter()
+            .map(|node| node.expect("Should never fail: all nodes are in the arena"))
+            .collect())
+    }
+
+    /// Swaps the data of two nodes, leaving their relations untouched.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `a` and `b` are pointing to the same node, like [`get2_mut`],
+    /// or if either of them is not in the arena.
+    ///
+    /// # Examples
+    ///
     /// ```
     /// # use generational_indextree::Arena;
     /// let mut arena = Arena::new();
-    /// let _foo = arena.new_node("foo");
+    /// let foo = arena.new_node("foo");
     /// let bar = arena.new_node("bar");
-    /// bar.remove(&mut arena);
+    /// foo.append(bar, &mut arena);
     ///
-    /// let mut iter = arena.iter();
-    /// assert_eq!(iter.next().map(|node| *node.get()), Some("foo"));
-    /// assert_eq!(iter.next().map(|node| *node.get()), None);
+    /// arena.swap_data(foo, bar);
+    /// assert_eq!(*arena[foo].get(), "bar");
+    /// assert_eq!(*arena[bar].get(), "foo");
+    /// assert_eq!(arena[bar].parent(), Some(foo));
     /// ```
-    pub fn iter(&self) -> impl Iterator<Item=&Node<T>> {
-        self.nodes.iter().map(|pair| pair.1)
+    ///
+    /// [`get2_mut`]: struct.Arena.html#method.get2_mut
+    pub fn swap_data(&mut self, a: NodeId, b: NodeId) {
+        match self.get2_mut(a, b) {
+            (Some(a), Some(b)) => mem::swap(&mut a.data, &mut b.data),
+            _ => panic!("Both nodes must be in the arena"),
+        }
     }
 
-    /// Returns an iterator of all pairs (NodeId, &Node<T>) in the arena in storage-order.
+    /// Removes the subtrees of all the given nodes and returns the number of
+    /// nodes freed.
+    ///
+    /// IDs that are not in the arena, including those already removed as
+    /// part of an earlier subtree in the same batch, are skipped.
+    ///
+    /// # Examples
     ///
     /// ```
     /// # use generational_indextree::Arena;
-    /// let mut arena = Arena::new();
-    /// let _foo = arena.new_node("foo");
-    /// let _bar = arena.new_node("bar");
+    /// # let mut arena = Arena::new();
+    /// # let n1 = arena.new_node("1");
+    /// # let n1_1 = arena.new_node("1_1");
+    /// # n1.append(n1_1, &mut arena);
+    /// # let n1_1_1 = arena.new_node("1_1_1");
+    /// # n1_1.append(n1_1_1, &mut arena);
+    /// # let n1_2 = arena.new_node("1_2");
+    /// # n1.append(n1_2, &mut arena);
+    /// #
+    /// // arena
+    /// // `-- 1
+    /// //     |-- 1_1
+    /// //     |   `-- 1_1_1
+    /// //     `-- 1_2
     ///
-    /// let mut iter = arena.iter_pairs();
-    /// assert_eq!(iter.next().map(|node| (node.0, *node.1.get())), Some((_foo, "foo")));
-    /// assert_eq!(iter.next().map(|node| (node.0, *node.1.get())), Some((_bar, "bar")));
-    /// assert_eq!(iter.next().map(|node| (node.0, *node.1.get())), None);
+    /// assert_eq!(arena.remove_many(vec![n1_1, n1_1_1, n1_2]), 3);
+    /// assert_eq!(arena.count(), 1);
+    /// assert!(arena[n1].first_child().is_none());
     /// ```
-    pub fn iter_pairs(&self) -> impl Iterator<Item=(NodeId, &Node<T>)> {
-        self.nodes.iter().map(|pair| (NodeId::from_index(pair.0), pair.1))
+    pub fn remove_many<I: IntoIterator<Item = NodeId>>(&mut self, ids: I) -> usize {
+        let count = self.count();
+        for id in ids {
+            if self.nodes.contains(id.get_index()) {
+                id.remove_subtree(self);
+            }
+        }
+        count - self.count()
     }
-}
 
-impl<T> Default for Arena<T> {
-    fn default() -> Self {
-        Self { nodes: GenerationalArena::new() }
+    /// Removes every node which is not in one of the subtrees rooted at the
+    /// given nodes, and returns the number of removed nodes.
+    ///
+    /// This is a mark and sweep pass: nodes left over from earlier work, e.g.
+    /// detached trees nobody refers to anymore, are freed in one go. The kept
+    /// subtrees are unchanged, except that a kept root is detached from any
+    /// parent or sibling which is removed, becoming a root itself. The
+    /// ancestors of the given nodes are not kept. IDs that are not in the
+    /// arena are skipped.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use generational_indextree::Arena;
+    /// # let mut arena = Arena::new();
+    /// # let n1 = arena.new_node("1");
+    /// # let n1_1 = arena.new_node("1_1");
+    /// # n1.append(n1_1, &mut arena);
+    /// # let n1_1_1 = arena.new_node("1_1_1");
+    /// # n1_1.append(n1_1_1, &mut arena);
+    /// # let n1_2 = arena.new_node("1_2");
+    /// # n1.append(n1_2, &mut arena);
+    /// # let n2 = arena.new_node("2");
+    /// #
+    /// // arena
+    /// // |-- 1
+    /// // |   |-- 1_1
+    /// // |   |   `-- 1_1_1
+    /// // |   `-- 1_2
+    /// // `-- 2
+    ///
+    /// assert_eq!(arena.retain_reachable_from(vec![n1_1, n2]), 2);
+    /// // arena
+    /// // |-- 1_1
+    /// // |   `-- 1_1_1
+    /// // `-- 2
+    ///
+    /// assert!(arena.get(n1).is_none());
+    /// assert!(arena.get(n1_2).is_none());
+    /// assert!(arena[n1_1].parent().is_none());
+    /// assert_eq!(n1_1.children(&arena).collect::<Vec<_>>(), vec![n1_1_1]);
+    /// assert_eq!(arena.count(), 3);
+    /// ```
+    pub fn retain_reachable_from<I: IntoIterator<Item = NodeId>>(
+        &mut self,
+        keep_roots: I,
+    ) -> usize {
+        let mut keep = BTreeSet::new();
+        for root in keep_roots {
+            if self.nodes.contains(root.get_index()) && !keep.contains(&root) {
+                keep.extend(root.descendants(self));
+            }
+        }
+        // Unlink the kept nodes from their removed neighbors, which can only
+        // be the ones of the kept roots.
+        let dangling = keep
+            .iter()
+            .copied()
+            .filter(|&id| {
+                let node = &self[id];
+                [node.parent, node.previous_sibling, node.next_sibling]
+                    .iter()
+                    .flatten()
+                    .any(|neighbor| !keep.contains(neighbor))
+            })
+            .collect::<Vec<_>>();
+        for id in dangling {
+            id.detach(self);
+        }
+        let count = self.count();
+        self.nodes.retain(|index, _| keep.contains(&NodeId::from_raw_index(index, 0)));
+        self.debug_assert_valid();
+        count - self.count()
     }
-}
 
-impl<T> Index<NodeId> for Arena<T> {
-    type Output = Node<T>;
-
-    fn index(&self, node: NodeId) -> &Node<T> {
-        &self.nodes[node.get_index()]
+    /// Removes the tree below `old_root`, and creates a new root node with the
+    /// given data in its place.
+    ///
+    /// This is [`remove_subtree`] followed by [`new_node`]: the IDs of the
+    /// replaced nodes become stale, and the new node is a root even if
+    /// `old_root` had a parent. The new node may reuse the slot of a removed
+    /// one, but the generations tell them apart.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `old_root` was already removed, or under the same conditions
+    /// as [`new_node`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use generational_indextree::Arena;
+    /// let mut arena = Arena::new();
+    /// let old = arena.new_node("old");
+    /// let child = arena.new_node("child");
+    /// old.append(child, &mut arena);
+    ///
+    /// let new = arena.replace_tree(old, "new");
+    /// assert!(arena.get(old).is_none());
+    /// assert!(arena.get(child).is_none());
+    /// assert_eq!(*arena[new].get(), "new");
+    /// assert_eq!(arena.count(), 1);
+    /// ```
+    ///
+    /// [`remove_subtree`]: struct.NodeId.html#method.remove_subtree
+    /// [`new_node`]: struct.Arena.html#method.new_node
+    pub fn replace_tree(&mut self, old_root: NodeId, new_root_data: T) -> NodeId {
+        old_root.remove_subtree(self);
+        self.new_node(new_root_data)
     }
-}
 
-impl<T> IndexMut<NodeId> for Arena<T> {
-    fn index_mut(&mut self, node: NodeId) -> &mut Node<T> {
-        &mut self.nodes[node.get_index()]
-    }
+    /// Links the nodes without a parent into trees, by matching the parent
+    /// key of every such node to the key of another one, and returns the IDs
+    /// of the nodes left as roots.
+    ///
+    /// This builds trees from flat records referring to their parents, e.g.
+    /// database rows, once a node has been created for every record. Nodes
+    /// with a parent are left in place, and the subtrees of all nodes are kept.
+    /// Children are appended in storage-order, which is also the order of
+    /// the returned roots.
+    ///
+    /// # Failures
+    ///
+    /// Nothing is linked if an error is returned.
+    ///
+    /// * Returns [`NodeError::DuplicateKey`] error if two of the nodes have
+    ///   the same key.
+    /// * Returns [`NodeError::DanglingParentKey`] error if the parent key of
+    ///   a node is not the key of any of the nodes.
+    /// * Returns [`NodeError::ParentKeyCycle`] error if following the parent
+    ///   keys from a node leads back to it.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use generational_indextree::Arena;
+    /// let mut arena = Arena::new();
+    /// let rows = vec![(2, Some(1)), (1, None), (3, Some(1)), (4, Some(2))];
+    /// let ids = rows.into_iter().map(|row| arena.new_node(row)).collect::<Vec<_>>();
+    ///
+    /// let roots = arena.link_by_keys(|&(key, _)| key, |&(_, parent)| parent).unwrap();
+    /// // arena
+    /// // `-- 1
+    /// //     |-- 2
+    /// //     |   `-- 4
+    /// //     `-- 3
+    ///
+    /// assert_eq!(roots, vec![ids[1]]);
+    /// assert_eq!(ids[1].children(&arena).collect::<Vec<_>>(), vec![ids[0], ids[2]]);
+    /// assert_eq!(arena[ids[3]].parent(), Some(ids[0]));
+    /// ```
+    ///
+    /// [`NodeError::DuplicateKey`]: enum.NodeError.html#variant.DuplicateKey
+    /// [`NodeError::DanglingParentKey`]: enum.NodeError.html#variant.DanglingParentKey
+    /// [`NodeError::ParentKeyCycle`]: enum.NodeError.html#variant.ParentKeyCycle
+    #[cfg(feature = "std")]
+    pub fn link_by_keys<K, FK, FP>(
+        &mut self,
+        key: FK,
+        parent_key: FP,
+    ) -> Result<Vec<NodeId>, NodeError>
+    where
+        K: Hash + Eq,
+        FK: Fn(&T) -> K,
+        FP: Fn(&T) -> Option<K>,
+    {
+        let orphans = self.roots().collect::<Vec<_>>();
+        let mut by_key = HashMap::with_capacity(orphans.len());
+        for &id in &orphans {
+            if by_key.insert(key(&self[id].data), id).is_some() {
+                return Err(NodeError::DuplicateKey);
+            }
+        }
+        let mut parents = HashMap::with_capacity(orphans.len());
+        for &id in &orphans {
+            if let Some(parent) = parent_key(&self[id].data) {
+                let parent = *by_key.get(&parent).ok_or(NodeError::DanglingParentKey)?;
+                parents.insert(id, parent);
+            }
+        }
+
+        // Walk up from every node, stopping at nodes already known to lead to a
+        // root, so that every node is walked over once.
+        let mut acyclic = BTreeSet::new();
+        for &id in &orphans {
+            let mut path = BTreeSet::new();
+            let mut current = id;
+            while !acyclic.contains(&current) {
+                if !path.insert(current) {
+                    return Err(NodeError::ParentKeyCycle);
+                }
+                match parents.get(&current) {
+                    Some(&parent) => current = parent,
+                    None => break,
+                }
+            }
+            acyclic.extend(path);
+        }
+
+        let mut roots = Vec::new();
+        for id in orphans {
+            match parents.get(&id) {
+                Some(&parent) => parent.append(id, self),
+                None => roots.push(id),
+            }
+        }
+        Ok(roots)
+    }
+
+    /// Returns the lowest common ancestor of all the given nodes, i.e. the
+    /// deepest node which has all of them among its descendants.
+    ///
+    /// A node counts as its own ancestor, so the result may be one of the
+    /// given nodes. Returns `None` if no nodes are given or if they are not
+    /// all in the same tree.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use generational_indextree::Arena;
+    /// # let mut arena = Arena::new();
+    /// # let n1 = arena.new_node("1");
+    /// # let n1_1 = arena.new_node("1_1");
+    /// # n1.append(n1_1, &mut arena);
+    /// # let n1_1_1 = arena.new_node("1_1_1");
+    /// # n1_1.append(n1_1_1, &mut arena);
+    /// # let n1_1_2 = arena.new_node("1_1_2");
+    /// # n1_1.append(n1_1_2, &mut arena);
+    /// # let n1_2 = arena.new_node("1_2");
+    /// # n1.append(n1_2, &mut arena);
+    /// # let n2 = arena.new_node("2");
+    /// #
+    /// // arena
+    /// // |-- 1
+    /// // |   |-- 1_1
+    /// // |   |   |-- 1_1_1
+    /// // |   |   `-- 1_1_2
+    /// // |   `-- 1_2
+    /// // `-- 2
+    ///
+    /// assert_eq!(arena.lowest_common_ancestor_many(vec![n1_1_1, n1_1_2]), Some(n1_1));
+    /// assert_eq!(arena.lowest_common_ancestor_many(vec![n1_1_1, n1_1_2, n1_2]), Some(n1));
+    /// assert_eq!(arena.lowest_common_ancestor_many(vec![n1_1, n1_1_2]), Some(n1_1));
+    /// assert_eq!(arena.lowest_common_ancestor_many(vec![n1_1_1, n2]), None);
+    /// assert_eq!(arena.lowest_common_ancestor_many(vec![]), None);
+    /// ```
+    pub fn lowest_common_ancestor_many<I: IntoIterator<Item = NodeId>>(
+        &self,
+        ids: I,
+    ) -> Option<NodeId> {
+        let mut nodes = ids
+            .into_iter()
+            .map(|id| (id, id.ancestors(self).count()))
+            .collect::<Vec<_>>();
+        let min_depth = nodes.iter().map(|&(_, depth)| depth).min()?;
+
+        // Lift all nodes to the same depth, then walk up together.
+        for (node, depth) in nodes.iter_mut() {
+            *node = node
+                .ancestors(self)
+                .nth(*depth - min_depth)
+                .expect("Should never fail: the node has that many ancestors");
+        }
+        loop {
+            let first = nodes[0].0;
+            if nodes.iter().all(|&(node, _)| node == first) {
+                return Some(first);
+            }
+            for (node, _) in nodes.iter_mut() {
+                *node = self[*node].parent?;
+            }
+        }
+    }
+
+    /// Returns the path between two nodes of the same tree, from `a` up to
+    /// their lowest common ancestor and down to `b`, both included.
+    ///
+    /// This is meant for paths between leaves, e.g. for routing in a tree
+    /// shaped network, but works for any two nodes. Returns `None` if the
+    /// nodes are not in the same tree.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use generational_indextree::Arena;
+    /// # let mut arena = Arena::new();
+    /// # let n1 = arena.new_node("1");
+    /// # let n1_1 = arena.new_node("1_1");
+    /// # n1.append(n1_1, &mut arena);
+    /// # let n1_1_1 = arena.new_node("1_1_1");
+    /// # n1_1.append(n1_1_1, &mut arena);
+    /// # let n1_2 = arena.new_node("1_2");
+    /// # n1.append(n1_2, &mut arena);
+    /// # let n2 = arena.new_node("2");
+    /// #
+    /// // arena
+    /// // |-- 1
+    /// // |   |-- 1_1
+    /// // |   |   `-- 1_1_1
+    /// // |   `-- 1_2
+    /// // `-- 2
+    ///
+    /// assert_eq!(arena.leaf_path(n1_1_1, n1_2), Some(vec![n1_1_1, n1_1, n1, n1_2]));
+    /// assert_eq!(arena.leaf_path(n1_2, n1_2), Some(vec![n1_2]));
+    /// assert_eq!(arena.leaf_path(n1_1_1, n2), None);
+    /// ```
+    pub fn leaf_path(&self, a: NodeId, b: NodeId) -> Option<Vec<NodeId>> {
+        let lca = self.lowest_common_ancestor_many([a, b])?;
+        let mut path = a.ancestors(self).take_while(|&id| id != lca).collect::<Vec<_>>();
+        path.push(lca);
+        let down = b.ancestors(self).take_while(|&id| id != lca).collect::<Vec<_>>();
+        path.extend(down.into_iter().rev());
+        Some(path)
+    }
+
+    /// Returns an iterator of the IDs of the given nodes and their
+    /// descendants, walking each subtree in pre-order and skipping nodes
+    /// already visited.
+    ///
+    /// When one of the given nodes is inside the subtree of another, its
+    /// nodes are only yielded once.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use generational_indextree::Arena;
+    /// # let mut arena = Arena::new();
+    /// # let n1 = arena.new_node("1");
+    /// # let n1_1 = arena.new_node("1_1");
+    /// # n1.append(n1_1, &mut arena);
+    /// # let n1_1_1 = arena.new_node("1_1_1");
+    /// # n1_1.append(n1_1_1, &mut arena);
+    /// # let n1_2 = arena.new_node("1_2");
+    /// # n1.append(n1_2, &mut arena);
+    /// # let n2 = arena.new_node("2");
+    /// #
+    /// // arena
+    /// // |-- 1
+    /// // |   |-- 1_1
+    /// // |   |   `-- 1_1_1
+    /// // |   `-- 1_2
+    /// // `-- 2
+    ///
+    /// let iter = arena.descendants_of(vec![n1_1, n2, n1]);
+    /// assert_eq!(iter.collect::<Vec<_>>(), vec![n1_1, n1_1_1, n2, n1, n1_2]);
+    /// ```
+    pub fn descendants_of<'a, I>(&'a self, roots: I) -> impl Iterator<Item = NodeId> + 'a
+    where
+        I: IntoIterator<Item = NodeId>,
+        I::IntoIter: 'a,
+    {
+        let mut visited = BTreeSet::new();
+        roots
+            .into_iter()
+            .flat_map(move |root| root.descendants(self))
+            .filter(move |&id| visited.insert(id))
+    }
+
+    /// Returns an iterator of the nodes from `start` to `end`, both included,
+    /// in preorder.
+    ///
+    /// The iterator is empty if the nodes are in different trees or `end`
+    /// precedes `start`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use generational_indextree::Arena;
+    /// # let mut arena = Arena::new();
+    /// # let n1 = arena.new_node("1");
+    /// # let n1_1 = arena.new_node("1_1");
+    /// # n1.append(n1_1, &mut arena);
+    /// # let n1_1_1 = arena.new_node("1_1_1");
+    /// # n1_1.append(n1_1_1, &mut arena);
+    /// # let n1_2 = arena.new_node("1_2");
+    /// # n1.append(n1_2, &mut arena);
+    /// # let n1_2_1 = arena.new_node("1_2_1");
+    /// # n1_2.append(n1_2_1, &mut arena);
+    /// # let n2 = arena.new_node("2");
+    /// #
+    /// // arena
+    /// // |-- 1
+    /// // |   |-- 1_1
+    /// // |   |   `-- 1_1_1
+    /// // |   `-- 1_2
+    /// // |       `-- 1_2_1
+    /// // `-- 2
+    ///
+    /// let iter = arena.range(n1_1_1, n1_2_1);
+    /// assert_eq!(iter.collect::<Vec<_>>(), vec![n1_1_1, n1_2, n1_2_1]);
+    ///
+    /// assert_eq!(arena.range(n1_2, n1_1).count(), 0);
+    /// assert_eq!(arena.range(n1, n2).count(), 0);
+    /// ```
+    pub fn range(&self, start: NodeId, end: NodeId) -> impl Iterator<Item = NodeId> + '_ {
+        let len = match self.lowest_common_ancestor_many([start, end]) {
+            Some(_) => (end.preorder_index(self) + 1).saturating_sub(start.preorder_index(self)),
+            None => 0,
+        };
+        core::iter::successors(Some(start), move |&node| node.next_preorder(self)).take(len)
+    }
+
+    /// Compares two nodes by their position in document order, i.e. preorder.
+    ///
+    /// An ancestor comes before its descendants. Nodes in different trees are
+    /// ordered like their roots, by the storage order of the root IDs, so the
+    /// result is a total order over the nodes of the arena.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use generational_indextree::Arena;
+    /// # use std::cmp::Ordering;
+    /// # let mut arena = Arena::new();
+    /// # let n1 = arena.new_node("1");
+    /// # let n1_1 = arena.new_node("1_1");
+    /// # n1.append(n1_1, &mut arena);
+    /// # let n1_1_1 = arena.new_node("1_1_1");
+    /// # n1_1.append(n1_1_1, &mut arena);
+    /// # let n1_2 = arena.new_node("1_2");
+    /// # n1.append(n1_2, &mut arena);
+    /// #
+    /// // arena
+    /// // `-- 1
+    /// //     |-- 1_1
+    /// //     |   `-- 1_1_1
+    /// //     `-- 1_2
+    ///
+    /// assert_eq!(arena.cmp_document_order(n1, n1_1_1), Ordering::Less);
+    /// assert_eq!(arena.cmp_document_order(n1_2, n1_1_1), Ordering::Greater);
+    ///
+    /// let mut selection = vec![n1_2, n1, n1_1_1, n1_1];
+    /// selection.sort_by(|&a, &b| arena.cmp_document_order(a, b));
+    /// assert_eq!(selection, vec![n1, n1_1, n1_1_1, n1_2]);
+    /// ```
+    pub fn cmp_document_order(&self, a: NodeId, b: NodeId) -> Ordering {
+        if a == b {
+            return Ordering::Equal;
+        }
+        // Paths from the roots down to the nodes.
+        let mut a_path = a.ancestors(self).collect::<Vec<_>>();
+        let mut b_path = b.ancestors(self).collect::<Vec<_>>();
+        a_path.reverse();
+        b_path.reverse();
+        if a_path[0] != b_path[0] {
+            return a_path[0].cmp(&b_path[0]);
+        }
+        match a_path.iter().zip(&b_path).find(|(a, b)| a != b) {
+            Some((&a, &b)) => {
+                // Siblings below the lowest common ancestor.
+                if a.following_siblings(self).any(|sibling| sibling == b) {
+                    Ordering::Less
+                } else {
+                    Ordering::Greater
+                }
+            }
+            // One node is an ancestor of the other.
+            None => a_path.len().cmp(&b_path.len()),
+        }
+    }
+
+    /// Checks the consistency of the links between all nodes of the arena.
+    ///
+    /// Verifies that parent, child and sibling links of every node point to
+    /// nodes of the arena and agree with each other, and that there are no
+    /// parent or sibling cycles. Returns a description of every violation
+    /// found.
+    ///
+    /// Trees built through the public API are always consistent, so this is
+    /// mostly useful to debug code mutating the tree. With the `paranoid`
+    /// feature, debug builds run this check after every structural mutation,
+    /// such as appending, inserting, detaching or removing a node, and panic
+    /// at the first inconsistency.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use generational_indextree::Arena;
+    /// let mut arena = Arena::new();
+    /// let n1 = arena.new_node("1");
+    /// let n1_1 = arena.new_node("1_1");
+    /// n1.append(n1_1, &mut arena);
+    /// n1_1.insert_after(arena.new_node("1_2"), &mut arena);
+    /// n1_1.remove(&mut arena);
+    ///
+    /// assert_eq!(arena.validate(), Ok(()));
+    /// ```
+    pub fn validate(&self) -> Result<(), Vec<String>> {
+        let mut errors = Vec::new();
+        for (id, node) in self.iter_pairs() {
+            if node.first_child.is_some() != node.last_child.is_some() {
+                errors.push(format!("{} has only one of a first and a last child", id));
+            }
+            if let Some(parent) = self.linked(&mut errors, id, "parent", node.parent) {
+                if node.previous_sibling.is_none() && parent.first_child != Some(id) {
+                    errors.push(format!(
+                        "{} has no previous sibling but is not the first child of its parent",
+                        id
+                    ));
+                }
+                if node.next_sibling.is_none() && parent.last_child != Some(id) {
+                    errors.push(format!(
+                        "{} has no next sibling but is not the last child of its parent",
+                        id
+                    ));
+                }
+            }
+            if let Some(previous) = self.linked(&mut errors, id, "previous sibling", node.previous_sibling) {
+                if previous.next_sibling != Some(id) {
+                    errors.push(format!("the previous sibling of {} does not link back to it", id));
+                }
+                if previous.parent != node.parent {
+                    errors.push(format!("{} and its previous sibling have different parents", id));
+                }
+            }
+            if let Some(next) = self.linked(&mut errors, id, "next sibling", node.next_sibling) {
+                if next.previous_sibling != Some(id) {
+                    errors.push(format!("the next sibling of {} does not link back to it", id));
+                }
+            }
+            if let Some(first) = self.linked(&mut errors, id, "first child", node.first_child) {
+                if first.parent != Some(id) {
+                    errors.push(format!("the first child of {} has another parent", id));
+                }
+                if first.previous_sibling.is_some() {
+                    errors.push(format!("the first child of {} has a previous sibling", id));
+                }
+            }
+            if let Some(last) = self.linked(&mut errors, id, "last child", node.last_child) {
+                if last.parent != Some(id) {
+                    errors.push(format!("the last child of {} has another parent", id));
+                }
+                if last.next_sibling.is_some() {
+                    errors.push(format!("the last child of {} has a next sibling", id));
+                }
+            }
+        }
+
+        // Walk up from every node, stopping at nodes already walked over, so
+        // that each cycle is only reported once.
+        let mut visited = BTreeSet::new();
+        for id in self.iter_pairs().map(|(id, _)| id) {
+            let mut path = BTreeSet::new();
+            let mut current = Some(id);
+            while let Some(node) = current {
+                if visited.contains(&node) {
+                    break;
+                }
+                if !path.insert(node) {
+                    errors.push(format!("{} is part of a parent cycle", node));
+                    break;
+                }
+                current = self.get(node).and_then(|node| node.parent);
+            }
+            visited.extend(path);
+        }
+
+        // Every sibling chain starts at a node without a previous sibling, so
+        // nodes not reached from one of those are part of a sibling ring.
+        let mut visited = BTreeSet::new();
+        let starts = self
+            .iter_pairs()
+            .filter(|(_, node)| node.previous_sibling.is_none())
+            .map(|(id, _)| id);
+        for start in starts {
+            let mut current = Some(start);
+            while let Some(node) = current {
+                if !visited.insert(node) {
+                    break;
+                }
+                current = self.get(node).and_then(|node| node.next_sibling);
+            }
+        }
+        for id in self.iter_pairs().map(|(id, _)| id) {
+            if visited.contains(&id) {
+                continue;
+            }
+            errors.push(format!("{} is part of a sibling cycle", id));
+            let mut current = Some(id);
+            while let Some(node) = current {
+                if !visited.insert(node) {
+                    break;
+                }
+                current = self.get(node).and_then(|node| node.next_sibling);
+            }
+        }
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+
+    /// Returns the node linked to `id` by the given relation, recording an
+    /// error if the link points to a removed node.
+    fn linked(
+        &self,
+        errors: &mut Vec<String>,
+        id: NodeId,
+        relation: &str,
+        link: Option<NodeId>,
+    ) -> Option<&Node<T>> {
+        let node = self.get(link?);
+        if node.is_none() {
+            errors.push(format!("the {} of {} has been removed", relation, id));
+        }
+        node
+    }
+
+    /// Panics if the links of the arena are inconsistent, as reported by
+    /// [`validate`].
+    ///
+    /// Structural mutations call this once they are done. The check walks
+    /// the whole arena, so it only runs in debug builds with the `paranoid`
+    /// feature enabled, and does nothing otherwise.
+    ///
+    /// [`validate`]: struct.Arena.html#method.validate
+    #[inline]
+    pub(crate) fn debug_assert_valid(&self) {
+        #[cfg(all(debug_assertions, feature = "paranoid"))]
+        {
+            if let Err(errors) = self.validate() {
+                panic!("Should never fail: inconsistent links: {}", errors.join("; "));
+            }
+        }
+    }
+
+    /// Returns an iterator of all nodes in the arena in storage-order.
+    ///
+    /// Storage-order depends on the history of insertions and removals, as
+    /// removed slots are reused. Use [`iter_document_order`] to visit the
+    /// nodes in an order based on the tree structure.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use generational_indextree::Arena;
+    /// let mut arena = Arena::new();
+    /// let _foo = arena.new_node("foo");
+    /// let _bar = arena.new_node("bar");
+    ///
+    /// let mut iter = arena.iter();
+    /// assert_eq!(iter.next().map(|node| *node.get()), Some("foo"));
+    /// assert_eq!(iter.next().map(|node| *node.get()), Some("bar"));
+    /// assert_eq!(iter.next().map(|node| *node.get()), None);
+    /// ```
+    ///
+    /// ```
+    /// # use generational_indextree::Arena;
+    /// let mut arena = Arena::new();
+    /// let _foo = arena.new_node("foo");
+    /// let bar = arena.new_node("bar");
+    /// bar.remove(&mut arena);
+    ///
+    /// let mut iter = arena.iter();
+    /// assert_eq!(iter.next().map(|node| *node.get()), Some("foo"));
+    /// assert_eq!(iter.next().map(|node| *node.get()), None);
+    /// ```
+    ///
+    /// [`iter_document_order`]: struct.Arena.html#method.iter_document_order
+    pub fn iter(&self) -> impl Iterator<Item=&Node<T>> {
+        self.nodes.iter().map(|pair| pair.1)
+    }
+
+    /// Returns an iterator of all pairs (NodeId, &Node<T>) in the arena in storage-order.
+    ///
+    /// ```
+    /// # use generational_indextree::Arena;
+    /// let mut arena = Arena::new();
+    /// let _foo = arena.new_node("foo");
+    /// let _bar = arena.new_node("bar");
+    ///
+    /// let mut iter = arena.iter_pairs();
+    /// assert_eq!(iter.next().map(|node| (node.0, *node.1.get())), Some((_foo, "foo")));
+    /// assert_eq!(iter.next().map(|node| (node.0, *node.1.get())), Some((_bar, "bar")));
+    /// assert_eq!(iter.next().map(|node| (node.0, *node.1.get())), None);
+    /// ```
+    pub fn iter_pairs(&self) -> impl Iterator<Item=(NodeId, &Node<T>)> {
+        self.nodes.iter().map(move |pair| (NodeId::from_index(pair.0, self), pair.1))
+    }
+
+    /// Returns an iterator of the data of all nodes in the arena in
+    /// storage-order.
+    ///
+    /// ```
+    /// # use generational_indextree::Arena;
+    /// let mut arena = Arena::new();
+    /// let _foo = arena.new_node(1);
+    /// let bar = arena.new_node(2);
+    /// let _baz = arena.new_node(3);
+    /// bar.remove(&mut arena);
+    ///
+    /// assert_eq!(arena.values().sum::<i32>(), 4);
+    /// ```
+    pub fn values(&self) -> impl Iterator<Item = &T> {
+        self.nodes.iter().map(|(_, node)| &node.data)
+    }
+
+    /// Returns an iterator of mutable references to the data of all nodes in
+    /// the arena in storage-order.
+    ///
+    /// ```
+    /// # use generational_indextree::Arena;
+    /// let mut arena = Arena::new();
+    /// let foo = arena.new_node(1);
+    /// let bar = arena.new_node(2);
+    /// foo.append(bar, &mut arena);
+    ///
+    /// arena.values_mut().for_each(|data| *data *= 10);
+    /// assert_eq!(arena.values().copied().collect::<Vec<_>>(), vec![10, 20]);
+    /// ```
+    pub fn values_mut(&mut self) -> impl Iterator<Item = &mut T> {
+        self.nodes.iter_mut().map(|(_, node)| &mut node.data)
+    }
+
+    /// Returns an iterator of the IDs of all nodes in the arena in
+    /// storage-order.
+    ///
+    /// ```
+    /// # use generational_indextree::Arena;
+    /// let mut arena = Arena::new();
+    /// let foo = arena.new_node("foo");
+    /// let bar = arena.new_node("bar");
+    /// let baz = arena.new_node("baz");
+    /// bar.remove(&mut arena);
+    ///
+    /// assert_eq!(arena.node_ids().collect::<Vec<_>>(), vec![foo, baz]);
+    /// ```
+    pub fn node_ids(&self) -> impl Iterator<Item = NodeId> + '_ {
+        self.nodes.iter().map(move |pair| NodeId::from_index(pair.0, self))
+    }
+
+    /// Returns an iterator of the IDs of all nodes without children, in
+    /// storage-order.
+    ///
+    /// ```
+    /// # use generational_indextree::Arena;
+    /// let mut arena = Arena::new();
+    /// let foo = arena.new_node("foo");
+    /// let bar = arena.new_node("bar");
+    /// let baz = arena.new_node("baz");
+    /// foo.append(bar, &mut arena);
+    ///
+    /// assert_eq!(arena.leaves().collect::<Vec<_>>(), vec![bar, baz]);
+    /// ```
+    pub fn leaves(&self) -> impl Iterator<Item = NodeId> + '_ {
+        self.iter_pairs()
+            .filter(|(_, node)| node.first_child.is_none())
+            .map(|(id, _)| id)
+    }
+
+    /// Returns an iterator of the IDs of all nodes with at least one child, in
+    /// storage-order.
+    ///
+    /// ```
+    /// # use generational_indextree::Arena;
+    /// let mut arena = Arena::new();
+    /// let foo = arena.new_node("foo");
+    /// let bar = arena.new_node("bar");
+    /// let baz = arena.new_node("baz");
+    /// foo.append(bar, &mut arena);
+    /// bar.append(baz, &mut arena);
+    ///
+    /// assert_eq!(arena.internal_nodes().collect::<Vec<_>>(), vec![foo, bar]);
+    /// ```
+    pub fn internal_nodes(&self) -> impl Iterator<Item = NodeId> + '_ {
+        self.iter_pairs()
+            .filter(|(_, node)| node.first_child.is_some())
+            .map(|(id, _)| id)
+    }
+
+    /// Returns an iterator of the IDs of all nodes without a parent, in
+    /// storage-order.
+    ///
+    /// ```
+    /// # use generational_indextree::Arena;
+    /// let mut arena = Arena::new();
+    /// let foo = arena.new_node("foo");
+    /// let bar = arena.new_node("bar");
+    /// let baz = arena.new_node("baz");
+    /// foo.append(bar, &mut arena);
+    ///
+    /// assert_eq!(arena.roots().collect::<Vec<_>>(), vec![foo, baz]);
+    /// ```
+    pub fn roots(&self) -> impl Iterator<Item = NodeId> + '_ {
+        self.iter_pairs()
+            .filter(|(_, node)| node.parent.is_none())
+            .map(|(id, _)| id)
+    }
+
+    /// Returns an iterator of all nodes without a parent along with their
+    /// data, in the order of [`roots`].
+    ///
+    /// ```
+    /// # use generational_indextree::Arena;
+    /// let mut arena = Arena::new();
+    /// let foo = arena.new_node("foo");
+    /// let bar = arena.new_node("bar");
+    /// let baz = arena.new_node("baz");
+    /// foo.append(bar, &mut arena);
+    ///
+    /// assert_eq!(arena.roots_data().collect::<Vec<_>>(), vec![(foo, &"foo"), (baz, &"baz")]);
+    /// ```
+    ///
+    /// [`roots`]: struct.Arena.html#method.roots
+    pub fn roots_data(&self) -> impl Iterator<Item = (NodeId, &T)> + '_ {
+        self.iter_pairs()
+            .filter(|(_, node)| node.parent.is_none())
+            .map(|(id, node)| (id, &node.data))
+    }
+
+    /// Returns an iterator of the trees of the arena, yielding each root along
+    /// with an iterator of its subtree in preorder.
+    ///
+    /// The roots come in the order of [`roots`], and each subtree is only
+    /// walked when its iterator is advanced.
+    ///
+    /// ```
+    /// # use generational_indextree::Arena;
+    /// let mut arena = Arena::new();
+    /// let foo = arena.new_node("foo");
+    /// let bar = arena.new_node("bar");
+    /// let baz = arena.new_node("baz");
+    /// foo.append(bar, &mut arena);
+    ///
+    /// let sizes = arena
+    ///     .subtrees()
+    ///     .map(|(root, subtree)| (*arena[root].get(), subtree.count()))
+    ///     .collect::<Vec<_>>();
+    /// assert_eq!(sizes, vec![("foo", 2), ("baz", 1)]);
+    /// ```
+    ///
+    /// [`roots`]: struct.Arena.html#method.roots
+    pub fn subtrees(&self) -> impl Iterator<Item = (NodeId, Descendants<'_, T>)> + '_ {
+        self.roots().map(move |root| (root, root.descendants(self)))
+    }
+
+    /// Returns `true` if both arenas hold the same trees: the same number of
+    /// roots and, root by root in the order of [`roots`], subtrees of the
+    /// same shape with equal data.
+    ///
+    /// Unlike `==`, which compares the arenas slot by slot, this ignores where
+    /// the nodes are stored: their IDs, the free slots left by removed nodes,
+    /// and the capacity. A node without a parent is a tree of its own, and is
+    /// compared as such.
+    ///
+    /// The roots are paired up in storage order, though, since that is the
+    /// order [`roots`] yields them in: two arenas with the same trees stored
+    /// in a different order are not equal.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use generational_indextree::Arena;
+    /// let mut arena = Arena::new();
+    /// let garbage = arena.new_node("garbage");
+    /// let foo = arena.new_node("foo");
+    /// foo.append(arena.new_node("bar"), &mut arena);
+    /// garbage.remove(&mut arena);
+    ///
+    /// let mut other = Arena::new();
+    /// let foo = other.new_node("foo");
+    /// foo.append(other.new_node("bar"), &mut other);
+    ///
+    /// assert!(arena != other);
+    /// assert!(arena.forest_eq(&other));
+    ///
+    /// other.new_node("baz");
+    /// assert!(!arena.forest_eq(&other));
+    ///
+    /// let mut reversed = Arena::new();
+    /// reversed.new_node("baz");
+    /// let foo = reversed.new_node("foo");
+    /// foo.append(reversed.new_node("bar"), &mut reversed);
+    /// assert!(!reversed.forest_eq(&other));
+    /// ```
+    ///
+    /// [`roots`]: struct.Arena.html#method.roots
+    pub fn forest_eq(&self, other: &Self) -> bool
+    where
+        T: PartialEq,
+    {
+        // The edges of the traversals give the shape, the data of the starts
+        // the contents.
+        fn contents<T>(arena: &Arena<T>) -> impl Iterator<Item = Option<&T>> {
+            arena
+                .roots()
+                .flat_map(move |root| root.traverse(arena))
+                .map(move |edge| match edge {
+                    NodeEdge::Start(id) => Some(&arena[id].data),
+                    NodeEdge::End(_) => None,
+                })
+        }
+        contents(self).eq(contents(other))
+    }
+
+    /// Returns an iterator of all pairs (NodeId, &Node<T>) in the arena in
+    /// document order.
+    ///
+    /// Each tree is visited in preorder, and the trees are visited in the
+    /// storage-order of their roots. Unlike [`iter`], the order within a tree
+    /// only depends on its structure.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use generational_indextree::Arena;
+    /// let mut arena = Arena::new();
+    /// let foo = arena.new_node("foo");
+    /// let bar = arena.new_node("bar");
+    /// let baz = arena.new_node("baz");
+    /// foo.append(baz, &mut arena);
+    /// baz.insert_before(bar, &mut arena);
+    /// // arena
+    /// // `-- foo
+    /// //     |-- bar
+    /// //     `-- baz
+    ///
+    /// let iter = arena.iter_document_order().map(|(_, node)| *node.get());
+    /// assert_eq!(iter.collect::<Vec<_>>(), vec!["foo", "bar", "baz"]);
+    /// ```
+    ///
+    /// [`iter`]: struct.Arena.html#method.iter
+    pub fn iter_document_order(&self) -> impl Iterator<Item = (NodeId, &Node<T>)> {
+        self.subtrees()
+            .flat_map(|(_, subtree)| subtree)
+            .map(move |id| (id, &self[id]))
+    }
+
+    /// Returns the stable IDs of all nodes in the arena.
+    ///
+    /// Stable IDs come from a counter stored in the arena and are assigned on
+    /// creation, so unlike `NodeId`s they are never reused when a removed
+    /// node's slot is given to a new node. They are part of the serialized
+    /// arena, which makes them suitable for persisting references between
+    /// nodes; after loading, rebuild the mapping with
+    /// [`nodes_by_stable_id`]. Anything that moves nodes to new slots keeps
+    /// their stable IDs, as they are stored in the nodes themselves.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use generational_indextree::Arena;
+    /// let mut arena = Arena::new();
+    /// let foo = arena.new_node("foo");
+    /// let bar = arena.new_node("bar");
+    /// bar.remove(&mut arena);
+    /// let baz = arena.new_node("baz");
+    ///
+    /// let stable_ids = arena.stable_ids();
+    /// assert_eq!(stable_ids[&foo], 0);
+    /// assert_eq!(stable_ids[&baz], 2);
+    /// assert_eq!(arena[baz].stable_id(), 2);
+    /// ```
+    ///
+    /// [`nodes_by_stable_id`]: struct.Arena.html#method.nodes_by_stable_id
+    #[cfg(feature = "std")]
+    pub fn stable_ids(&self) -> HashMap<NodeId, u64> {
+        self.iter_pairs()
+            .map(|(id, node)| (id, node.stable_id))
+            .collect()
+    }
+
+    /// Returns the `NodeId`s of all nodes in the arena, by stable ID.
+    ///
+    /// This is the inverse of [`stable_ids`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use generational_indextree::Arena;
+    /// let mut arena = Arena::new();
+    /// let foo = arena.new_node("foo");
+    /// let stable_id = arena[foo].stable_id();
+    ///
+    /// assert_eq!(arena.nodes_by_stable_id()[&stable_id], foo);
+    /// ```
+    ///
+    /// [`stable_ids`]: struct.Arena.html#method.stable_ids
+    #[cfg(feature = "std")]
+    pub fn nodes_by_stable_id(&self) -> HashMap<u64, NodeId> {
+        self.iter_pairs()
+            .map(|(id, node)| (node.stable_id, id))
+            .collect()
+    }
+
+    /// Packs the live nodes at the start of the storage, dropping the free
+    /// slots, and returns the mapping from the old IDs to the new ones.
+    ///
+    /// `fixup` is called on the data of every node with the mapping, after
+    /// all nodes have been moved, so that IDs stored in `T` can be rewritten.
+    /// The storage order and the stable IDs of the nodes are kept.
+    ///
+    /// All IDs taken before the compaction must be remapped: the generations
+    /// start over, so an old ID may now refer to an unrelated node.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use generational_indextree::{Arena, NodeId};
+    /// let mut arena = Arena::<Option<NodeId>>::new();
+    /// let removed = arena.new_node(None);
+    /// let target = arena.new_node(None);
+    /// let link = arena.new_node(Some(target));
+    /// removed.remove(&mut arena);
+    ///
+    /// let remap = arena.compact_with(|data, remap| {
+    ///     if let Some(id) = data {
+    ///         *id = remap[id];
+    ///     }
+    /// });
+    /// assert_eq!(remap.len(), 2);
+    /// assert_eq!(*arena[remap[&link]].get(), Some(remap[&target]));
+    /// assert_eq!(arena.iter_pairs().next().unwrap().0, remap[&target]);
+    /// ```
+    #[cfg(feature = "std")]
+    pub fn compact_with<F: FnMut(&mut T, &HashMap<NodeId, NodeId>)>(
+        &mut self,
+        fixup: F,
+    ) -> HashMap<NodeId, NodeId> {
+        self.rebuild(self.count(), fixup)
+    }
+
+    /// Reduces the number of allocated slots toward `min_capacity`, and
+    /// returns the mapping from the old IDs of the nodes to their new ones.
+    ///
+    /// The target is clamped up to [`count`], so no node is ever dropped,
+    /// and nothing happens if the arena does not have more slots than that:
+    /// the mapping is then the identity.
+    ///
+    /// The underlying storage cannot release slots in place, so shrinking
+    /// moves the nodes into a new, smaller storage, just like
+    /// [`compact_with`] does. The storage order and the stable IDs of the
+    /// nodes are kept, but all IDs taken before the shrink must be remapped.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use generational_indextree::Arena;
+    /// let mut arena = Arena::with_capacity(64);
+    /// let foo = arena.new_node("foo");
+    /// let bar = arena.new_node("bar");
+    /// foo.append(bar, &mut arena);
+    ///
+    /// let remap = arena.shrink_to(8);
+    /// assert_eq!(arena.slot_count(), 8);
+    /// assert_eq!(arena[remap[&bar]].parent(), Some(remap[&foo]));
+    ///
+    /// // Never below the number of nodes.
+    /// arena.shrink_to(0);
+    /// assert_eq!(arena.slot_count(), 2);
+    /// ```
+    ///
+    /// [`count`]: struct.Arena.html#method.count
+    /// [`compact_with`]: struct.Arena.html#method.compact_with
+    #[cfg(feature = "std")]
+    pub fn shrink_to(&mut self, min_capacity: usize) -> HashMap<NodeId, NodeId> {
+        let capacity = min_capacity.max(self.count());
+        if self.nodes.capacity() <= capacity {
+            return self.node_ids().map(|id| (id, id)).collect();
+        }
+        self.rebuild(capacity, |_, _| {})
+    }
+
+    /// Moves all nodes, in storage order, into a new storage of `capacity`
+    /// slots, and returns the mapping from their old IDs to their new ones.
+    #[cfg(feature = "std")]
+    fn rebuild<F: FnMut(&mut T, &HashMap<NodeId, NodeId>)>(
+        &mut self,
+        capacity: usize,
+        mut fixup: F,
+    ) -> HashMap<NodeId, NodeId> {
+        let count = self.count();
+        let mut old = mem::replace(&mut self.nodes, GenerationalArena::with_capacity(capacity));
+        let mut remap = HashMap::with_capacity(count);
+        let stamp = self.stamp();
+        for (_, mut node) in old.drain() {
+            let old_id = node.id;
+            let new_index = self.nodes.insert_with(|index| {
+                node.id = NodeId::from_raw_index(index, stamp);
+                node
+            });
+            remap.insert(old_id, NodeId::from_index(new_index, self));
+        }
+
+        let moved = |id: Option<NodeId>| id.map(|id| remap[&id]);
+        for (_, node) in self.nodes.iter_mut() {
+            node.parent = moved(node.parent);
+            node.previous_sibling = moved(node.previous_sibling);
+            node.next_sibling = moved(node.next_sibling);
+            node.first_child = moved(node.first_child);
+            node.last_child = moved(node.last_child);
+            fixup(&mut node.data, &remap);
+        }
+        remap
+    }
+
+    /// Groups the nodes of the arena whose subtrees are identical, i.e. have
+    /// the same shape and the same data.
+    ///
+    /// Every group holds at least two nodes, and nodes without a duplicate
+    /// are left out. Candidates are bucketed by [`subtree_hash`], then
+    /// compared node by node, so hash collisions never merge different
+    /// subtrees. The descendants of duplicates are duplicates too, and are
+    /// reported in groups of their own. Groups and their nodes are ordered
+    /// as the nodes are first met by postorder walks of the trees, in
+    /// storage order of the roots.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use generational_indextree::Arena;
+    /// // (x + y) * (x + y)
+    /// let mut arena = Arena::new();
+    /// let mul = arena.new_node("*");
+    /// let mut sums = Vec::new();
+    /// for _ in 0..2 {
+    ///     let sum = arena.new_node("+");
+    ///     sum.append(arena.new_node("x"), &mut arena);
+    ///     sum.append(arena.new_node("y"), &mut arena);
+    ///     mul.append(sum, &mut arena);
+    ///     sums.push(sum);
+    /// }
+    ///
+    /// let duplicates = arena.find_duplicate_subtrees();
+    /// assert_eq!(duplicates.len(), 3);
+    /// assert!(duplicates.contains(&sums));
+    /// ```
+    ///
+    /// [`subtree_hash`]: struct.NodeId.html#method.subtree_hash
+    #[cfg(feature = "std")]
+    pub fn find_duplicate_subtrees(&self) -> Vec<Vec<NodeId>>
+    where
+        T: Hash + Eq,
+    {
+        // Computes all subtree hashes in one pass, children first.
+        let mut hashes = HashMap::with_capacity(self.count());
+        let mut buckets = HashMap::<u64, Vec<NodeId>>::new();
+        let mut order = Vec::new();
+        let roots = self.iter_pairs().filter(|(_, node)| node.parent.is_none());
+        for (root, _) in roots {
+            for id in root.descendants_postorder(self) {
+                let children = id.children(self).map(|child| hashes[&child]).collect::<Vec<_>>();
+                let hash = node_hash(&self[id].data, &children);
+                hashes.insert(id, hash);
+                let bucket = buckets.entry(hash).or_default();
+                if bucket.is_empty() {
+                    order.push(hash);
+                }
+                bucket.push(id);
+            }
+        }
+
+        let mut groups = Vec::new();
+        for hash in order {
+            let bucket = &buckets[&hash];
+            if bucket.len() < 2 {
+                continue;
+            }
+            let mut classes = Vec::<Vec<NodeId>>::new();
+            for &id in bucket {
+                match classes.iter_mut().find(|class| self.subtrees_eq(class[0], id)) {
+                    Some(class) => class.push(id),
+                    None => classes.push(vec![id]),
+                }
+            }
+            groups.extend(classes.into_iter().filter(|class| class.len() > 1));
+        }
+        groups
+    }
+
+    /// Returns `true` if the subtrees of `a` and `b` have the same shape and
+    /// the same data.
+    #[cfg(feature = "std")]
+    fn subtrees_eq(&self, a: NodeId, b: NodeId) -> bool
+    where
+        T: PartialEq,
+    {
+        let data = |edge| match edge {
+            NodeEdge::Start(id) => Some(&self[id].data),
+            NodeEdge::End(_) => None,
+        };
+        a.traverse(self).map(data).eq(b.traverse(self).map(data))
+    }
+
+    /// Reads a tree nested as `{"data": ..., "children": [...]}` into the
+    /// arena, and returns the ID of its root.
+    ///
+    /// Nodes are created while the input is read, so the memory used on top
+    /// of the arena is proportional to the depth of the tree rather than to
+    /// its size. This makes it suitable for streaming large exports, e.g.
+    /// from a `serde_json::Deserializer::from_reader`. Formats which are not
+    /// self-describing read every node as the sequence of its data and its
+    /// children.
+    ///
+    /// `children` may come before `data`, as in maps sorted by key, in which
+    /// case those children are held as detached trees until the data is read.
+    /// Reading recurses once per level, so trees nested deeper than 128
+    /// levels are rejected; [`NodeId::append_json_tree`] builds deeper trees
+    /// from a JSON value. Trees which do not fit under the [capacity limit]
+    /// of the arena are rejected too. On error,
+    /// the nodes read so far are removed again, so the arena is left as it
+    /// was.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use generational_indextree::Arena;
+    /// let json = r#"{"data": "foo", "children": [{"data": "bar", "children": []}]}"#;
+    /// let mut arena = Arena::<String>::new();
+    /// let foo = arena
+    ///     .deserialize_tree(&mut serde_json::Deserializer::from_str(json))
+    ///     .unwrap();
+    /// assert_eq!(arena[foo].get(), "foo");
+    /// assert_eq!(arena.count(), 2);
+    ///
+    /// let invalid = r#"{"data": "foo", "children": [{"data": "bar", "children": [1]}]}"#;
+    /// assert!(arena
+    ///     .deserialize_tree(&mut serde_json::Deserializer::from_str(invalid))
+    ///     .is_err());
+    /// assert_eq!(arena.count(), 2);
+    /// ```
+    ///
+    /// [`NodeId::append_json_tree`]: struct.NodeId.html#method.append_json_tree
+    /// [capacity limit]: struct.Arena.html#method.with_capacity_limit
+    #[cfg(feature = "deser")]
+    pub fn deserialize_tree<'de, D>(&mut self, deserializer: D) -> Result<NodeId, D::Error>
+    where
+        T: Deserialize<'de>,
+        D: serde::Deserializer<'de>,
+    {
+        crate::nested::deserialize_tree(self, deserializer)
+    }
+
+    /// Reads a tree nested as `{"data": ..., "children": [...]}` from a JSON
+    /// reader into the arena, and returns the ID of its root.
+    ///
+    /// This is [`deserialize_tree`] on a streaming JSON parser: the nodes are
+    /// created while the input is read, so the memory used on top of the
+    /// arena is proportional to the depth of the tree, not to the size of the
+    /// input. The reader is not buffered, wrap it in a `BufReader` if needed.
+    /// Trailing characters other than whitespace are an error. On error, the
+    /// nodes read so far are removed again.
+    ///
+    /// The JSON parser bounds the nesting of its input to 128 levels of
+    /// objects and arrays, i.e. 64 levels of nodes.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use generational_indextree::Arena;
+    /// let json = r#"{"data": 1, "children": [{"data": 2, "children": []}]}"#;
+    /// let mut arena = Arena::<i32>::new();
+    /// let root = arena.from_json_reader(json.as_bytes()).unwrap();
+    ///
+    /// let data = root.descendants(&arena).map(|id| *arena[id].get()).collect::<Vec<_>>();
+    /// assert_eq!(data, vec![1, 2]);
+    ///
+    /// assert!(arena.from_json_reader(&br#"{"data": 3, "children": []} 4"#[..]).is_err());
+    /// assert_eq!(arena.count(), 2);
+    /// ```
+    ///
+    /// [`deserialize_tree`]: struct.Arena.html#method.deserialize_tree
+    #[cfg(feature = "json")]
+    pub fn from_json_reader<R: io::Read>(&mut self, reader: R) -> Result<NodeId, serde_json::Error>
+    where
+        T: DeserializeOwned,
+    {
+        let mut deserializer = serde_json::Deserializer::from_reader(reader);
+        let root = self.deserialize_tree(&mut deserializer)?;
+        if let Err(error) = deserializer.end() {
+            root.remove_subtree(self);
+            return Err(error);
+        }
+        Ok(root)
+    }
+
+    /// Reads a list of trees nested as `{"data": ..., "children": [...]}`
+    /// into the arena, and returns the IDs of their roots in order.
+    ///
+    /// This is the counterpart of [`serialize_forest`], and reads every tree
+    /// like [`deserialize_tree`]: the structure is rebuilt from the nesting
+    /// alone, so the IDs of the new nodes are unrelated to the ones of the
+    /// serialized arena. On error, all the trees read so far are removed
+    /// again.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use generational_indextree::Arena;
+    /// let json = r#"[{"data": 1, "children": [{"data": 2, "children": []}]}]"#;
+    /// let mut arena = Arena::<i32>::new();
+    /// let roots = arena
+    ///     .deserialize_forest(&mut serde_json::Deserializer::from_str(json))
+    ///     .unwrap();
+    ///
+    /// let data = roots[0].descendants(&arena).map(|id| *arena[id].get()).collect::<Vec<_>>();
+    /// assert_eq!(data, vec![1, 2]);
+    /// ```
+    ///
+    /// [`serialize_forest`]: struct.Arena.html#method.serialize_forest
+    /// [`deserialize_tree`]: struct.Arena.html#method.deserialize_tree
+    #[cfg(feature = "deser")]
+    pub fn deserialize_forest<'de, D>(&mut self, deserializer: D) -> Result<Vec<NodeId>, D::Error>
+    where
+        T: Deserialize<'de>,
+        D: serde::Deserializer<'de>,
+    {
+        crate::nested::deserialize_forest(self, deserializer)
+    }
+
+    /// Returns a serializable view of all the trees of the arena, as a list of
+    /// nodes nested as `{"data": ..., "children": [...]}`, one per root in
+    /// storage order.
+    ///
+    /// Unlike the derived `Serialize` of the arena, only the data of the nodes
+    /// is written, without their indices and links, which makes the output
+    /// smaller and independent of how the arena stores its nodes. Top-level
+    /// siblings are written as separate trees. Read it back with
+    /// [`deserialize_forest`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use generational_indextree::Arena;
+    /// let mut arena = Arena::new();
+    /// let foo = arena.new_node("foo");
+    /// let bar = arena.new_node("bar");
+    /// foo.append(bar, &mut arena);
+    ///
+    /// let json = serde_json::to_string(&arena.serialize_forest()).unwrap();
+    /// assert_eq!(json, r#"[{"data":"foo","children":[{"data":"bar","children":[]}]}]"#);
+    /// ```
+    ///
+    /// [`deserialize_forest`]: struct.Arena.html#method.deserialize_forest
+    #[cfg(feature = "deser")]
+    pub fn serialize_forest(&self) -> SerializeForest<'_, T> {
+        SerializeForest::new(self)
+    }
+
+    /// Serializes the arena as a list of nodes referring to each other by
+    /// stable ID, converting the data of every node with `f`.
+    ///
+    /// `f` gets the stable IDs of all nodes, so that node IDs embedded in the
+    /// data can be written as stable IDs. Nodes are written in
+    /// [document order], parents before their children. Read the result back
+    /// with [`deserialize_with_stable_ids`].
+    ///
+    /// [document order]: struct.Arena.html#method.iter_document_order
+    /// [`deserialize_with_stable_ids`]: struct.Arena.html#method.deserialize_with_stable_ids
+    #[cfg(all(feature = "std", feature = "deser"))]
+    pub fn serialize_with_stable_ids<S, U, F>(
+        &self,
+        serializer: S,
+        mut f: F,
+    ) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+        U: Serialize,
+        F: FnMut(&T, &HashMap<NodeId, u64>) -> U,
+    {
+        let stable_ids = self.stable_ids();
+        let records = self
+            .iter_document_order()
+            .map(|(_, node)| StableNode {
+                id: node.stable_id,
+                parent: node.parent.map(|parent| stable_ids[&parent]),
+                data: f(&node.data, &stable_ids),
+            })
+            .collect::<Vec<_>>();
+        serializer.collect_seq(records)
+    }
+
+    /// Deserializes an arena written by [`serialize_with_stable_ids`],
+    /// converting the data of every node with `f`.
+    ///
+    /// `f` gets the `NodeId`s of all nodes by stable ID, so that stable IDs
+    /// embedded in the data can be turned back into node IDs. The nodes keep
+    /// their stable IDs, and new nodes get stable IDs greater than all of
+    /// them.
+    ///
+    /// # Failures
+    ///
+    /// Fails if two nodes have the same stable ID, or a node comes before its
+    /// parent. The IDs of the nodes are worked out before the nodes are
+    /// created, from the order in which the storage hands out its slots; if
+    /// a node does not end up at its expected slot, this fails too rather
+    /// than return an arena with wrong links.
+    ///
+    /// [`serialize_with_stable_ids`]: struct.Arena.html#method.serialize_with_stable_ids
+    #[cfg(all(feature = "std", feature = "deser"))]
+    pub fn deserialize_with_stable_ids<'de, D, U, F>(
+        deserializer: D,
+        mut f: F,
+    ) -> Result<Arena<T>, D::Error>
+    where
+        D: Deserializer<'de>,
+        U: Deserialize<'de>,
+        F: FnMut(U, &HashMap<u64, NodeId>) -> T,
+    {
+        let records = Vec::<StableNode<U>>::deserialize(deserializer)?;
+        let mut arena = Arena::with_capacity(records.len());
+
+        // Fresh arenas of the same capacity hand out the same indices for the
+        // same insertions, so the IDs of all nodes are known before their
+        // data is converted.
+        let mut slots = GenerationalArena::with_capacity(records.len());
+        let mut ids = HashMap::with_capacity(records.len());
+        for record in &records {
+            let id = NodeId::from_index(slots.insert(()), &arena);
+            if ids.insert(record.id, id).is_some() {
+                return Err(D::Error::custom(format_args!("duplicate stable ID {}", record.id)));
+            }
+        }
+        let next_stable_id = records
+            .iter()
+            .map(|record| record.id.saturating_add(1))
+            .max()
+            .unwrap_or(0);
+
+        for record in records {
+            let id = ids[&record.id];
+            let parent = match record.parent {
+                Some(parent) => match ids.get(&parent) {
+                    Some(&parent) if arena.nodes.contains(parent.get_index()) => Some(parent),
+                    _ => {
+                        return Err(D::Error::custom(format_args!(
+                            "parent {} of node {} is missing or comes after it",
+                            parent, record.id
+                        )))
+                    }
+                },
+                None => None,
+            };
+            let index = arena.nodes.insert(Node::new(f(record.data, &ids), record.id, id));
+            // The links of all nodes would be wrong otherwise.
+            if index != id.get_index() {
+                return Err(D::Error::custom(format_args!(
+                    "node {} was not stored at its expected index",
+                    record.id
+                )));
+            }
+            if let Some(parent) = parent {
+                parent.append(id, &mut arena);
+            }
+        }
+        arena.next_stable_id = next_stable_id;
+        Ok(arena)
+    }
+
+    /// Replaces the current state of the arena with the given snapshot.
+    ///
+    /// Every `NodeId` that was valid in the snapshot is valid again after the
+    /// restore, and refers to the same node it did when the snapshot was
+    /// taken. Nodes created after the snapshot are gone.
+    ///
+    /// See [`snapshot`] for taking snapshots.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use generational_indextree::Arena;
+    /// let mut arena = Arena::new();
+    /// let foo = arena.new_node("foo");
+    /// let snapshot = arena.snapshot();
+    ///
+    /// let bar = arena.new_node("bar");
+    /// foo.append(bar, &mut arena);
+    /// *arena[foo].get_mut() = "FOO!";
+    ///
+    /// arena.restore_from(snapshot);
+    /// assert_eq!(*arena[foo].get(), "foo");
+    /// assert!(arena[foo].first_child().is_none());
+    /// assert!(arena.get(bar).is_none());
+    /// ```
+    ///
+    /// [`snapshot`]: struct.Arena.html#method.snapshot
+    pub fn restore_from(&mut self, snapshot: Arena<T>) {
+        *self = snapshot;
+    }
+}
+
+impl<T: Clone> Arena<T> {
+    /// Takes a snapshot of the arena, to be restored later with
+    /// [`restore_from`].
+    ///
+    /// The snapshot is a full clone of the arena, including the generations
+    /// of the free slots, so `NodeId`s keep their meaning across a restore.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use generational_indextree::Arena;
+    /// let mut arena = Arena::new();
+    /// let foo = arena.new_node("foo");
+    /// let snapshot = arena.snapshot();
+    ///
+    /// foo.remove(&mut arena);
+    /// assert!(arena.get(foo).is_none());
+    /// assert_eq!(snapshot.get(foo).map(|node| *node.get()), Some("foo"));
+    /// ```
+    ///
+    /// [`restore_from`]: struct.Arena.html#method.restore_from
+    pub fn snapshot(&self) -> Arena<T> {
+        self.clone()
+    }
+
+    /// Clones the arena and returns the clone along with the mapping from the
+    /// IDs of this arena to the ones of the clone.
+    ///
+    /// A clone keeps every node in the same slot with the same generation, so
+    /// the mapping is the identity: it is provided for code fixing up external
+    /// references, or `NodeId`s stored in `T`, which should not rely on that.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use generational_indextree::Arena;
+    /// let mut arena = Arena::new();
+    /// let foo = arena.new_node("foo");
+    /// let bar = arena.new_node("bar");
+    /// foo.append(bar, &mut arena);
+    ///
+    /// let (clone, remap) = arena.clone_with_remap();
+    /// assert_eq!(remap.len(), 2);
+    /// assert_eq!(*clone[remap[&bar]].get(), "bar");
+    /// assert_eq!(clone[remap[&bar]].parent(), Some(remap[&foo]));
+    /// ```
+    #[cfg(feature = "std")]
+    pub fn clone_with_remap(&self) -> (Arena<T>, HashMap<NodeId, NodeId>) {
+        let remap = self.node_ids().map(|id| (id, id)).collect();
+        (self.clone(), remap)
+    }
+}
+
+#[cfg(feature = "par_iter")]
+impl<T> Arena<T> {
+    /// Returns a parallel iterator of all nodes in the arena in storage-order.
+    ///
+    /// See [`iter`] for the order of the nodes.
+    ///
+    /// [`iter`]: struct.Arena.html#method.iter
+    pub fn par_iter(&self) -> impl IndexedParallelIterator<Item = &Node<T>>
+    where
+        T: Sync,
+    {
+        self.iter().collect::<Vec<_>>().into_par_iter()
+    }
+
+    /// Creates a new root node for every item of a parallel iterator, and
+    /// returns their IDs, in the order of the items.
+    ///
+    /// The arena can only be grown from one thread, so the items are first
+    /// collected in parallel, and the nodes created afterwards. This pays
+    /// off when producing the items is the expensive part.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use generational_indextree::Arena;
+    /// use rayon::prelude::*;
+    ///
+    /// let mut arena = Arena::new();
+    /// let roots = arena.par_extend((0..4).into_par_iter().map(|i| i * i));
+    ///
+    /// let data = roots.iter().map(|&id| *arena[id].get()).collect::<Vec<_>>();
+    /// assert_eq!(data, vec![0, 1, 4, 9]);
+    /// assert!(arena.iter().all(|node| node.parent().is_none()));
+    /// ```
+    pub fn par_extend<I>(&mut self, iter: I) -> Vec<NodeId>
+    where
+        I: IntoParallelIterator<Item = T>,
+        T: Send,
+    {
+        let items = iter.into_par_iter().collect::<Vec<_>>();
+        self.nodes.reserve(items.len());
+        items.into_iter().map(|data| self.new_node(data)).collect()
+    }
+}
+
+impl<T> Default for Arena<T> {
+    fn default() -> Self {
+        Self::from_nodes(GenerationalArena::new())
+    }
+}
+
+impl<T> FromIterator<T> for Arena<T> {
+    /// Creates an arena with a new root node for every item, in order.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use generational_indextree::Arena;
+    /// let arena: Arena<_> = vec!["foo", "bar"].into_iter().collect();
+    ///
+    /// let roots = arena.iter().map(|node| *node.get()).collect::<Vec<_>>();
+    /// assert_eq!(roots, vec!["foo", "bar"]);
+    /// assert!(arena.iter().all(|node| node.parent().is_none()));
+    /// ```
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        let iter = iter.into_iter();
+        let mut arena = Arena::with_capacity(iter.size_hint().0);
+        arena.extend(iter);
+        arena
+    }
+}
+
+impl<T> Extend<T> for Arena<T> {
+    /// Creates a new root node for every item, in order.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use generational_indextree::Arena;
+    /// let mut arena = Arena::new();
+    /// let foo = arena.new_node("foo");
+    /// arena.extend(vec!["bar", "baz"]);
+    ///
+    /// assert_eq!(arena.count(), 3);
+    /// assert!(arena.iter().all(|node| node.parent().is_none()));
+    /// assert_eq!(foo.following_siblings(&arena).count(), 1);
+    /// ```
+    fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+        for data in iter {
+            self.new_node(data);
+        }
+    }
+}
+
+impl<T> Index<NodeId> for Arena<T> {
+    type Output = Node<T>;
+
+    fn index(&self, node: NodeId) -> &Node<T> {
+        debug_assert_owned_by!(self, node);
+        &self.nodes[node.get_index()]
+    }
+}
+
+impl<T> IndexMut<NodeId> for Arena<T> {
+    fn index_mut(&mut self, node: NodeId) -> &mut Node<T> {
+        debug_assert_owned_by!(self, node);
+        &mut self.nodes[node.get_index()]
+    }
 }
 
 impl<T: PartialEq> PartialEq for Arena<T>
@@ -276,8 +2474,37 @@ fn reuse_node() {
     n1_id.remove(&mut arena);
     n2_id.remove(&mut arena);
     n3_id.remove(&mut arena);
-    let n1_id = arena.new_node("1");
-    let n2_id = arena.new_node("2");
-    let n3_id = arena.new_node("3");
+    let _n1_id = arena.new_node("1");
+    let _n2_id = arena.new_node("2");
+    let _n3_id = arena.new_node("3");
     assert_eq!(arena.nodes.len(), 3);
 }
+
+#[test]
+fn validate_broken_links() {
+    let mut arena = Arena::new();
+    let n1 = arena.new_node("1");
+    let n1_1 = arena.new_node("1_1");
+    n1.append(n1_1, &mut arena);
+    let n1_2 = arena.new_node("1_2");
+    n1.append(n1_2, &mut arena);
+    assert_eq!(arena.validate(), Ok(()));
+
+    arena[n1_2].next_sibling = Some(n1_1);
+    arena[n1_1].previous_sibling = Some(n1_2);
+    let errors = arena.validate().unwrap_err();
+    assert!(errors.iter().any(|error| error.contains("sibling cycle")));
+    assert!(errors.iter().any(|error| error.contains("has a previous sibling")));
+
+    arena[n1_1].previous_sibling = None;
+    arena[n1_2].next_sibling = None;
+    arena[n1].parent = Some(n1_1);
+    let errors = arena.validate().unwrap_err();
+    assert!(errors.iter().any(|error| error.contains("parent cycle")));
+
+    arena[n1].parent = None;
+    n1_2.remove(&mut arena);
+    arena[n1_1].next_sibling = Some(n1_2);
+    let errors = arena.validate().unwrap_err();
+    assert!(errors.iter().any(|error| error.contains("has been removed")));
+}
@@ -0,0 +1,76 @@
+//! Scoped tree builder.
+
+use crate::{Arena, NodeId};
+
+/// A node being built, bundled with a mutable reference to its arena.
+///
+/// It is handed to the closures of [`Arena::new_node_with`] and
+/// [`child_with`], which add children to the node without passing the arena
+/// around at each call.
+///
+/// # Examples
+///
+/// ```
+/// # use generational_indextree::Arena;
+/// let mut arena = Arena::new();
+/// let root = arena.new_node_with("root", |root| {
+///     root.child("a");
+///     root.child_with("b", |b| {
+///         b.child("b_1");
+///     });
+/// });
+///
+/// let data = root.descendants(&arena).map(|id| *arena[id].get()).collect::<Vec<_>>();
+/// assert_eq!(data, vec!["root", "a", "b", "b_1"]);
+/// ```
+///
+/// [`Arena::new_node_with`]: struct.Arena.html#method.new_node_with
+/// [`child_with`]: struct.ChildBuilder.html#method.child_with
+pub struct ChildBuilder<'a, T> {
+    arena: &'a mut Arena<T>,
+    id: NodeId,
+}
+
+impl<'a, T> ChildBuilder<'a, T> {
+    /// Creates a builder on a node which is known to exist.
+    pub(crate) fn new(arena: &'a mut Arena<T>, id: NodeId) -> Self {
+        ChildBuilder { arena, id }
+    }
+
+    /// Returns the ID of the node being built.
+    pub fn id(&self) -> NodeId {
+        self.id
+    }
+
+    /// Creates a new node with the given data as the last child of the node
+    /// being built, and returns its ID.
+    ///
+    /// # Panics
+    ///
+    /// Panics under the same conditions as [`Arena::new_node`].
+    ///
+    /// [`Arena::new_node`]: struct.Arena.html#method.new_node
+    pub fn child(&mut self, data: T) -> NodeId {
+        let child = self.arena.new_node(data);
+        self.id.append(child, self.arena);
+        child
+    }
+
+    /// Creates a new node with the given data as the last child of the node
+    /// being built, lets `build` add children to it, and returns its ID.
+    ///
+    /// # Panics
+    ///
+    /// Panics under the same conditions as [`Arena::new_node`].
+    ///
+    /// [`Arena::new_node`]: struct.Arena.html#method.new_node
+    pub fn child_with<F: FnOnce(&mut ChildBuilder<'_, T>)>(
+        &mut self,
+        data: T,
+        build: F,
+    ) -> NodeId {
+        let child = self.child(data);
+        build(&mut ChildBuilder::new(self.arena, child));
+        child
+    }
+}
@@ -0,0 +1,281 @@
+//! Node cursors.
+
+#[cfg(not(feature = "std"))]
+use core::mem;
+#[cfg(feature = "std")]
+use std::mem;
+
+use crate::{Arena, Node, NodeId};
+
+/// A node of an arena, bundled with a shared reference to the arena.
+///
+/// This is a thin wrapper over a `NodeId` and its arena, so that tree walking
+/// code does not have to pass both around: navigating returns further
+/// `NodeRef`s. It is created by [`Arena::node_ref`], which checks that the
+/// node exists.
+///
+/// # Examples
+///
+/// ```
+/// # use generational_indextree::Arena;
+/// let mut arena = Arena::new();
+/// let foo = arena.new_node("foo");
+/// let bar = arena.new_node("bar");
+/// foo.append(bar, &mut arena);
+///
+/// let node = arena.node_ref(bar).unwrap();
+/// assert_eq!(*node.parent().unwrap().data(), "foo");
+/// ```
+///
+/// [`Arena::node_ref`]: struct.Arena.html#method.node_ref
+pub struct NodeRef<'a, T> {
+    arena: &'a Arena<T>,
+    id: NodeId,
+}
+
+impl<'a, T> NodeRef<'a, T> {
+    /// Creates a cursor on a node which is known to exist.
+    pub(crate) fn new(arena: &'a Arena<T>, id: NodeId) -> Self {
+        NodeRef { arena, id }
+    }
+
+    /// Returns the ID of the node.
+    pub fn id(&self) -> NodeId {
+        self.id
+    }
+
+    /// Returns the arena of the node.
+    pub fn arena(&self) -> &'a Arena<T> {
+        self.arena
+    }
+
+    /// Returns the node itself.
+    pub fn node(&self) -> &'a Node<T> {
+        &self.arena[self.id]
+    }
+
+    /// Returns a reference to the data of the node.
+    pub fn data(&self) -> &'a T {
+        &self.node().data
+    }
+
+    /// Returns the parent of the node, if any.
+    pub fn parent(&self) -> Option<Self> {
+        self.node().parent.map(|id| self.at(id))
+    }
+
+    /// Returns the first child of the node, if any.
+    pub fn first_child(&self) -> Option<Self> {
+        self.node().first_child.map(|id| self.at(id))
+    }
+
+    /// Returns the last child of the node, if any.
+    pub fn last_child(&self) -> Option<Self> {
+        self.node().last_child.map(|id| self.at(id))
+    }
+
+    /// Returns the previous sibling of the node, if any.
+    pub fn previous_sibling(&self) -> Option<Self> {
+        self.node().previous_sibling.map(|id| self.at(id))
+    }
+
+    /// Returns the next sibling of the node, if any.
+    pub fn next_sibling(&self) -> Option<Self> {
+        self.node().next_sibling.map(|id| self.at(id))
+    }
+
+    /// Returns an iterator of the children of the node, in order.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use generational_indextree::Arena;
+    /// let mut arena = Arena::new();
+    /// let foo = arena.new_node("foo");
+    /// foo.append(arena.new_node("bar"), &mut arena);
+    /// foo.append(arena.new_node("baz"), &mut arena);
+    ///
+    /// let node = arena.node_ref(foo).unwrap();
+    /// let data = node.children().map(|child| *child.data()).collect::<Vec<_>>();
+    /// assert_eq!(data, vec!["bar", "baz"]);
+    /// ```
+    pub fn children(&self) -> impl Iterator<Item = NodeRef<'a, T>> + 'a {
+        let arena = self.arena;
+        self.id
+            .children(arena)
+            .map(move |id| NodeRef::new(arena, id))
+    }
+
+    /// Returns an iterator of the node and its ancestors, from the node up to
+    /// the root.
+    pub fn ancestors(&self) -> impl Iterator<Item = NodeRef<'a, T>> + 'a {
+        let arena = self.arena;
+        self.id
+            .ancestors(arena)
+            .map(move |id| NodeRef::new(arena, id))
+    }
+
+    /// Returns an iterator of the node and its descendants, in preorder.
+    pub fn descendants(&self) -> impl Iterator<Item = NodeRef<'a, T>> + 'a {
+        let arena = self.arena;
+        self.id
+            .descendants(arena)
+            .map(move |id| NodeRef::new(arena, id))
+    }
+
+    /// Returns a cursor on another node of the same arena.
+    fn at(&self, id: NodeId) -> Self {
+        NodeRef::new(self.arena, id)
+    }
+}
+
+impl<'a, T> Clone for NodeRef<'a, T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<'a, T> Copy for NodeRef<'a, T> {}
+
+/// A node of an arena, bundled with an exclusive reference to the arena.
+///
+/// This is the mutable counterpart of [`NodeRef`], for building and editing
+/// trees without threading the arena and IDs around. The borrow of the arena
+/// is exclusive, so navigating consumes the cursor and returns a new one on
+/// the target node. It is created by [`Arena::node_mut`], which checks that
+/// the node exists.
+///
+/// # Examples
+///
+/// ```
+/// # use generational_indextree::Arena;
+/// let mut arena = Arena::new();
+/// let root = arena.new_node("root");
+///
+/// let mut cursor = arena.node_mut(root).unwrap();
+/// cursor.append_new_child("a").append_new_child("a_1");
+/// cursor.append_new_child("b");
+///
+/// let a_1 = cursor.into_first_child().unwrap().into_first_child().unwrap();
+/// assert_eq!(*a_1.data(), "a_1");
+/// let a = a_1.into_parent().unwrap();
+/// assert_eq!(*a.data(), "a");
+///
+/// let data = root.descendants(&arena).map(|id| *arena[id].get()).collect::<Vec<_>>();
+/// assert_eq!(data, vec!["root", "a", "a_1", "b"]);
+/// ```
+///
+/// [`NodeRef`]: struct.NodeRef.html
+/// [`Arena::node_mut`]: struct.Arena.html#method.node_mut
+pub struct NodeMut<'a, T> {
+    arena: &'a mut Arena<T>,
+    id: NodeId,
+}
+
+impl<'a, T> NodeMut<'a, T> {
+    /// Creates a cursor on a node which is known to exist.
+    pub(crate) fn new(arena: &'a mut Arena<T>, id: NodeId) -> Self {
+        NodeMut { arena, id }
+    }
+
+    /// Returns the ID of the node.
+    pub fn id(&self) -> NodeId {
+        self.id
+    }
+
+    /// Returns a read-only cursor on the node, borrowing this one.
+    pub fn as_ref(&self) -> NodeRef<'_, T> {
+        NodeRef::new(self.arena, self.id)
+    }
+
+    /// Turns the cursor into a read-only one.
+    pub fn into_ref(self) -> NodeRef<'a, T> {
+        NodeRef::new(self.arena, self.id)
+    }
+
+    /// Returns a reference to the data of the node.
+    pub fn data(&self) -> &T {
+        &self.arena[self.id].data
+    }
+
+    /// Returns a mutable reference to the data of the node.
+    pub fn data_mut(&mut self) -> &mut T {
+        &mut self.arena[self.id].data
+    }
+
+    /// Replaces the data of the node, and returns the previous data.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use generational_indextree::Arena;
+    /// let mut arena = Arena::new();
+    /// let foo = arena.new_node("foo");
+    ///
+    /// let mut cursor = arena.node_mut(foo).unwrap();
+    /// assert_eq!(cursor.set_data("bar"), "foo");
+    /// assert_eq!(*arena[foo].get(), "bar");
+    /// ```
+    pub fn set_data(&mut self, data: T) -> T {
+        mem::replace(self.data_mut(), data)
+    }
+
+    /// Creates a new node with the given data as the last child of this
+    /// node, and returns a cursor on it, borrowing this one.
+    ///
+    /// # Panics
+    ///
+    /// Panics under the same conditions as [`Arena::new_node`].
+    ///
+    /// [`Arena::new_node`]: struct.Arena.html#method.new_node
+    pub fn append_new_child(&mut self, data: T) -> NodeMut<'_, T> {
+        let child = self.arena.new_node(data);
+        self.id.append(child, self.arena);
+        NodeMut::new(self.arena, child)
+    }
+
+    /// Creates a new node with the given data as the first child of this
+    /// node, and returns a cursor on it, borrowing this one.
+    ///
+    /// # Panics
+    ///
+    /// Panics under the same conditions as [`Arena::new_node`].
+    ///
+    /// [`Arena::new_node`]: struct.Arena.html#method.new_node
+    pub fn prepend_new_child(&mut self, data: T) -> NodeMut<'_, T> {
+        let child = self.arena.new_node(data);
+        self.id.prepend(child, self.arena);
+        NodeMut::new(self.arena, child)
+    }
+
+    /// Moves the cursor to the parent of the node, if any.
+    pub fn into_parent(self) -> Option<Self> {
+        self.into_link(|node| node.parent)
+    }
+
+    /// Moves the cursor to the first child of the node, if any.
+    pub fn into_first_child(self) -> Option<Self> {
+        self.into_link(|node| node.first_child)
+    }
+
+    /// Moves the cursor to the last child of the node, if any.
+    pub fn into_last_child(self) -> Option<Self> {
+        self.into_link(|node| node.last_child)
+    }
+
+    /// Moves the cursor to the previous sibling of the node, if any.
+    pub fn into_previous_sibling(self) -> Option<Self> {
+        self.into_link(|node| node.previous_sibling)
+    }
+
+    /// Moves the cursor to the next sibling of the node, if any.
+    pub fn into_next_sibling(self) -> Option<Self> {
+        self.into_link(|node| node.next_sibling)
+    }
+
+    /// Moves the cursor to the node linked to the current one, if any.
+    fn into_link<F: FnOnce(&Node<T>) -> Option<NodeId>>(self, link: F) -> Option<Self> {
+        let NodeMut { arena, id } = self;
+        link(&arena[id]).map(move |id| NodeMut::new(arena, id))
+    }
+}
@@ -0,0 +1,208 @@
+//! Tree diffs.
+
+#[cfg(not(feature = "std"))]
+use alloc::{vec, vec::Vec};
+
+use crate::{Arena, NodeError, NodeId};
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+/// A single edit operation on a tree, as produced by [`NodeId::diff`].
+///
+/// Nodes are addressed by paths relative to the root of the edited tree: each
+/// element is the index of a child, so `[]` is the root itself and `[1, 0]` is
+/// the first child of the second child of the root. Paths are resolved
+/// against the tree as it is when the edit is applied, i.e. after all previous
+/// edits of the script.
+///
+/// [`NodeId::diff`]: struct.NodeId.html#method.diff
+pub enum TreeEdit<T> {
+    /// Inserts a new leaf with the given data, so that it ends up at `path`.
+    ///
+    /// The parent is the node at the path without its last element, and the
+    /// last element is the child index to insert at, which may be equal to
+    /// the number of children of the parent to append.
+    Insert {
+        /// Position of the new node.
+        path: Vec<usize>,
+        /// Data of the new node.
+        data: T,
+    },
+    /// Removes the node at `path` and its descendants.
+    Delete {
+        /// Position of the removed node.
+        path: Vec<usize>,
+    },
+    /// Replaces the data of the node at `path`.
+    Update {
+        /// Position of the updated node.
+        path: Vec<usize>,
+        /// New data of the node.
+        data: T,
+    },
+    /// Moves the node at `from`, with its descendants, so that it ends up at
+    /// `to`.
+    ///
+    /// `to` is resolved like the path of an [`Insert`] after the node has been
+    /// taken out of the tree, so it can not point into the moved subtree.
+    ///
+    /// [`Insert`]: enum.TreeEdit.html#variant.Insert
+    Move {
+        /// Current position of the moved node.
+        from: Vec<usize>,
+        /// New position of the moved node.
+        to: Vec<usize>,
+    },
+}
+
+/// Computes the edits turning the subtree of `a` into the one of `b`.
+///
+/// Children are matched by position: the common prefix of both child lists is
+/// compared recursively, superfluous children of `a` are deleted (last first,
+/// so that the remaining paths stay valid) and missing ones are inserted.
+pub(crate) fn diff<T: PartialEq + Clone>(
+    a: NodeId,
+    a_arena: &Arena<T>,
+    b: NodeId,
+    b_arena: &Arena<T>,
+) -> Vec<TreeEdit<T>> {
+    let mut edits = Vec::new();
+    // Pairs of matched nodes and their path, still to be compared.
+    let mut stack = vec![(a, b, Vec::new())];
+    while let Some((a, b, path)) = stack.pop() {
+        if a_arena[a].data != b_arena[b].data {
+            edits.push(TreeEdit::Update {
+                path: path.clone(),
+                data: b_arena[b].data.clone(),
+            });
+        }
+
+        let a_children = a.children(a_arena).collect::<Vec<_>>();
+        let b_children = b.children(b_arena).collect::<Vec<_>>();
+        let common = a_children.len().min(b_children.len());
+        for index in (common..a_children.len()).rev() {
+            edits.push(TreeEdit::Delete {
+                path: child_path(&path, index),
+            });
+        }
+        for (index, &b_child) in b_children.iter().enumerate().skip(common) {
+            push_inserts(&mut edits, b_child, b_arena, child_path(&path, index));
+        }
+        for index in (0..common).rev() {
+            stack.push((a_children[index], b_children[index], child_path(&path, index)));
+        }
+    }
+    edits
+}
+
+/// Applies the given edits to the subtree of `root`, in order.
+///
+/// Stops at the first edit whose paths can not be resolved, leaving the
+/// previous edits applied.
+pub(crate) fn apply_edits<T: Clone>(
+    root: NodeId,
+    arena: &mut Arena<T>,
+    edits: &[TreeEdit<T>],
+) -> Result<(), NodeError> {
+    if !arena.nodes.contains(root.get_index()) {
+        return Err(NodeError::Removed);
+    }
+    for edit in edits {
+        match edit {
+            TreeEdit::Insert { path, data } => {
+                let (parent, next) = resolve_slot(root, arena, path)?;
+                let new = arena.new_node(data.clone());
+                match next {
+                    Some(next) => next.insert_before(new, arena),
+                    None => parent.append(new, arena),
+                }
+            }
+            TreeEdit::Delete { path } => {
+                if path.is_empty() {
+                    return Err(NodeError::InvalidPath);
+                }
+                resolve(root, arena, path)?.remove_subtree(arena);
+            }
+            TreeEdit::Update { path, data } => {
+                let node = resolve(root, arena, path)?;
+                arena[node].data = data.clone();
+            }
+            TreeEdit::Move { from, to } => {
+                if from.is_empty() {
+                    return Err(NodeError::InvalidPath);
+                }
+                let node = resolve(root, arena, from)?;
+                let (old_parent, old_next) = (arena[node].parent, arena[node].next_sibling);
+                node.detach(arena);
+                let (parent, next) = match resolve_slot(root, arena, to) {
+                    Ok(slot) => slot,
+                    Err(err) => {
+                        // Put the node back where it was.
+                        match (old_next, old_parent) {
+                            (Some(old_next), _) => old_next.insert_before(node, arena),
+                            (None, Some(old_parent)) => old_parent.append(node, arena),
+                            (None, None) => unreachable!("`from` is below the root"),
+                        }
+                        return Err(err);
+                    }
+                };
+                match next {
+                    Some(next) => next.insert_before(node, arena),
+                    None => parent.append(node, arena),
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Returns the node at `path` below `root`.
+fn resolve<T>(root: NodeId, arena: &Arena<T>, path: &[usize]) -> Result<NodeId, NodeError> {
+    path.iter().try_fold(root, |node, &index| {
+        node.children(arena).nth(index).ok_or(NodeError::InvalidPath)
+    })
+}
+
+/// Returns the parent and the future next sibling of a node to be inserted at
+/// `path` below `root`.
+fn resolve_slot<T>(
+    root: NodeId,
+    arena: &Arena<T>,
+    path: &[usize],
+) -> Result<(NodeId, Option<NodeId>), NodeError> {
+    let (&index, parent_path) = path.split_last().ok_or(NodeError::InvalidPath)?;
+    let parent = resolve(root, arena, parent_path)?;
+    let mut children = parent.children(arena);
+    match children.nth(index) {
+        Some(next) => Ok((parent, Some(next))),
+        None if parent.children(arena).count() == index => Ok((parent, None)),
+        None => Err(NodeError::InvalidPath),
+    }
+}
+
+/// Pushes the edits inserting a copy of the subtree of `node` at `path`.
+fn push_inserts<T: Clone>(
+    edits: &mut Vec<TreeEdit<T>>,
+    node: NodeId,
+    arena: &Arena<T>,
+    path: Vec<usize>,
+) {
+    let mut stack = vec![(node, path)];
+    while let Some((node, path)) = stack.pop() {
+        edits.push(TreeEdit::Insert {
+            path: path.clone(),
+            data: arena[node].data.clone(),
+        });
+        let children = node.children(arena).collect::<Vec<_>>();
+        for (index, child) in children.into_iter().enumerate().rev() {
+            stack.push((child, child_path(&path, index)));
+        }
+    }
+}
+
+/// Returns the path of the `index`th child of the node at `path`.
+fn child_path(path: &[usize], index: usize) -> Vec<usize> {
+    let mut child = Vec::with_capacity(path.len() + 1);
+    child.extend_from_slice(path);
+    child.push(index);
+    child
+}
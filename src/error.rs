@@ -20,6 +20,23 @@ pub enum NodeError {
     InsertAfterSelf,
     /// Attempt to insert a removed node, or insert to a removed node.
     Removed,
+    /// Attempt to edit a position which does not exist in the tree.
+    InvalidPath,
+    /// Attempt to move a node relative to its parent, but it has none.
+    NoParent,
+    /// Attempt to move a node relative to its previous sibling, but it has
+    /// none.
+    NoPreviousSibling,
+    /// Attempt to replace a node with one of its ancestors or descendants.
+    ReplaceWithRelated,
+    /// Attempt to borrow the same node several times.
+    DuplicateNode,
+    /// Attempt to link nodes by keys, but several nodes have the same key.
+    DuplicateKey,
+    /// Attempt to link a node by keys to a parent key no node has.
+    DanglingParentKey,
+    /// Attempt to link nodes by keys, but their parent keys form a cycle.
+    ParentKeyCycle,
 }
 
 impl NodeError {
@@ -30,6 +47,16 @@ impl NodeError {
             NodeError::InsertBeforeSelf => "Can not insert a node before itself",
             NodeError::InsertAfterSelf => "Can not insert a node after itself",
             NodeError::Removed => "Removed node cannot have any parent, siblings, and children",
+            NodeError::InvalidPath => "Path does not point to a valid position in the tree",
+            NodeError::NoParent => "Node has no parent",
+            NodeError::NoPreviousSibling => "Node has no previous sibling",
+            NodeError::ReplaceWithRelated => {
+                "Can not replace a node with one of its ancestors or descendants"
+            }
+            NodeError::DuplicateNode => "Can not borrow the same node several times",
+            NodeError::DuplicateKey => "Several nodes have the same key",
+            NodeError::DanglingParentKey => "No node has the parent key",
+            NodeError::ParentKeyCycle => "Parent keys form a cycle",
         }
     }
 }
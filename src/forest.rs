@@ -0,0 +1,318 @@
+//! Forest.
+
+#[cfg(not(feature = "std"))]
+use alloc::{collections::BTreeSet, vec::Vec};
+#[cfg(not(feature = "std"))]
+use core::ops::{Index, IndexMut};
+#[cfg(feature = "std")]
+use std::{
+    collections::BTreeSet,
+    ops::{Index, IndexMut},
+};
+
+use crate::{Arena, Node, NodeError, NodeId};
+
+#[derive(Clone, Debug)]
+/// An [`Arena`] which keeps track of its root nodes.
+///
+/// Roots are the nodes without a parent. Finding them in a plain `Arena`
+/// requires a scan over all nodes, whereas a `Forest` updates its set of roots
+/// whenever a structural change goes through it, so [`roots`] only costs
+/// O(roots).
+///
+/// Structural changes must be made through the `Forest` methods for the root
+/// set to stay correct, which is why the inner arena is only handed out by
+/// shared reference.
+///
+/// [`Arena`]: struct.Arena.html
+/// [`roots`]: struct.Forest.html#method.roots
+pub struct Forest<T> {
+    arena: Arena<T>,
+    roots: BTreeSet<NodeId>,
+}
+
+impl<T> Forest<T> {
+    /// Creates a new empty `Forest`.
+    pub fn new() -> Forest<T> {
+        Self::default()
+    }
+
+    /// Returns a reference to the inner arena.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use generational_indextree::Forest;
+    /// let mut forest = Forest::new();
+    /// let foo = forest.add_tree("foo");
+    /// let bar = forest.add_tree("bar");
+    /// forest.append(foo, bar);
+    ///
+    /// assert_eq!(foo.children(forest.arena()).collect::<Vec<_>>(), vec![bar]);
+    /// ```
+    pub fn arena(&self) -> &Arena<T> {
+        &self.arena
+    }
+
+    /// Consumes the forest and returns the inner arena.
+    pub fn into_arena(self) -> Arena<T> {
+        self.arena
+    }
+
+    /// Creates a new tree, consisting of a single root node with the given
+    /// data.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use generational_indextree::Forest;
+    /// let mut forest = Forest::new();
+    /// let foo = forest.add_tree("foo");
+    ///
+    /// assert_eq!(*forest[foo].get(), "foo");
+    /// assert_eq!(forest.roots().collect::<Vec<_>>(), vec![foo]);
+    /// ```
+    pub fn add_tree(&mut self, data: T) -> NodeId {
+        let id = self.arena.new_node(data);
+        self.roots.insert(id);
+        id
+    }
+
+    /// Returns an iterator of the IDs of all root nodes, in storage-order.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use generational_indextree::Forest;
+    /// let mut forest = Forest::new();
+    /// let foo = forest.add_tree("foo");
+    /// let bar = forest.add_tree("bar");
+    /// let baz = forest.add_tree("baz");
+    /// forest.append(foo, bar);
+    ///
+    /// assert_eq!(forest.roots().collect::<Vec<_>>(), vec![foo, baz]);
+    ///
+    /// forest.detach(bar);
+    /// assert_eq!(forest.roots().collect::<Vec<_>>(), vec![foo, bar, baz]);
+    /// ```
+    pub fn roots(&self) -> impl Iterator<Item = NodeId> + '_ {
+        self.roots.iter().copied()
+    }
+
+    /// Returns `true` if the given node is a root of this forest.
+    pub fn is_root(&self, id: NodeId) -> bool {
+        self.roots.contains(&id)
+    }
+
+    /// Returns a reference to the node with the given id if in the forest.
+    ///
+    /// See [`Arena::get`].
+    ///
+    /// [`Arena::get`]: struct.Arena.html#method.get
+    pub fn get(&self, id: NodeId) -> Option<&Node<T>> {
+        self.arena.get(id)
+    }
+
+    /// Returns a mutable reference to the node with the given id if in the
+    /// forest.
+    ///
+    /// See [`Arena::get_mut`].
+    ///
+    /// [`Arena::get_mut`]: struct.Arena.html#method.get_mut
+    pub fn get_mut(&mut self, id: NodeId) -> Option<&mut Node<T>> {
+        self.arena.get_mut(id)
+    }
+
+    /// Appends a new child to the given parent, updating the roots.
+    ///
+    /// # Panics
+    ///
+    /// Panics under the same conditions as [`NodeId::append`].
+    ///
+    /// [`NodeId::append`]: struct.NodeId.html#method.append
+    pub fn append(&mut self, parent: NodeId, new_child: NodeId) {
+        self.checked_append(parent, new_child)
+            .expect("Preconditions not met: invalid argument");
+    }
+
+    /// Appends a new child to the given parent, updating the roots.
+    ///
+    /// # Failures
+    ///
+    /// Fails under the same conditions as [`NodeId::checked_append`].
+    ///
+    /// [`NodeId::checked_append`]: struct.NodeId.html#method.checked_append
+    pub fn checked_append(&mut self, parent: NodeId, new_child: NodeId) -> Result<(), NodeError> {
+        parent.checked_append(new_child, &mut self.arena)?;
+        self.update_root(new_child);
+        Ok(())
+    }
+
+    /// Prepends a new child to the given parent, updating the roots.
+    ///
+    /// # Panics
+    ///
+    /// Panics under the same conditions as [`NodeId::prepend`].
+    ///
+    /// [`NodeId::prepend`]: struct.NodeId.html#method.prepend
+    pub fn prepend(&mut self, parent: NodeId, new_child: NodeId) {
+        self.checked_prepend(parent, new_child)
+            .expect("Preconditions not met: invalid argument");
+    }
+
+    /// Prepends a new child to the given parent, updating the roots.
+    ///
+    /// # Failures
+    ///
+    /// Fails under the same conditions as [`NodeId::checked_prepend`].
+    ///
+    /// [`NodeId::checked_prepend`]: struct.NodeId.html#method.checked_prepend
+    pub fn checked_prepend(&mut self, parent: NodeId, new_child: NodeId) -> Result<(), NodeError> {
+        parent.checked_prepend(new_child, &mut self.arena)?;
+        self.update_root(new_child);
+        Ok(())
+    }
+
+    /// Inserts a new sibling after the given node, updating the roots.
+    ///
+    /// # Panics
+    ///
+    /// Panics under the same conditions as [`NodeId::insert_after`].
+    ///
+    /// [`NodeId::insert_after`]: struct.NodeId.html#method.insert_after
+    pub fn insert_after(&mut self, node: NodeId, new_sibling: NodeId) {
+        self.checked_insert_after(node, new_sibling)
+            .expect("Preconditions not met: invalid argument");
+    }
+
+    /// Inserts a new sibling after the given node, updating the roots.
+    ///
+    /// # Failures
+    ///
+    /// Fails under the same conditions as [`NodeId::checked_insert_after`].
+    ///
+    /// [`NodeId::checked_insert_after`]: struct.NodeId.html#method.checked_insert_after
+    pub fn checked_insert_after(&mut self, node: NodeId, new_sibling: NodeId) -> Result<(), NodeError> {
+        node.checked_insert_after(new_sibling, &mut self.arena)?;
+        self.update_root(new_sibling);
+        Ok(())
+    }
+
+    /// Inserts a new sibling before the given node, updating the roots.
+    ///
+    /// # Panics
+    ///
+    /// Panics under the same conditions as [`NodeId::insert_before`].
+    ///
+    /// [`NodeId::insert_before`]: struct.NodeId.html#method.insert_before
+    pub fn insert_before(&mut self, node: NodeId, new_sibling: NodeId) {
+        self.checked_insert_before(node, new_sibling)
+            .expect("Preconditions not met: invalid argument");
+    }
+
+    /// Inserts a new sibling before the given node, updating the roots.
+    ///
+    /// # Failures
+    ///
+    /// Fails under the same conditions as [`NodeId::checked_insert_before`].
+    ///
+    /// [`NodeId::checked_insert_before`]: struct.NodeId.html#method.checked_insert_before
+    pub fn checked_insert_before(&mut self, node: NodeId, new_sibling: NodeId) -> Result<(), NodeError> {
+        node.checked_insert_before(new_sibling, &mut self.arena)?;
+        self.update_root(new_sibling);
+        Ok(())
+    }
+
+    /// Detaches a node from its parent and siblings, making it a root.
+    ///
+    /// See [`NodeId::detach`].
+    ///
+    /// [`NodeId::detach`]: struct.NodeId.html#method.detach
+    pub fn detach(&mut self, node: NodeId) {
+        node.detach(&mut self.arena);
+        self.roots.insert(node);
+    }
+
+    /// Removes a node from the forest, updating the roots.
+    ///
+    /// Children of a removed root become roots themselves.
+    /// See [`NodeId::remove`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use generational_indextree::Forest;
+    /// let mut forest = Forest::new();
+    /// let foo = forest.add_tree("foo");
+    /// let bar = forest.add_tree("bar");
+    /// forest.append(foo, bar);
+    ///
+    /// forest.remove(foo);
+    /// assert_eq!(forest.roots().collect::<Vec<_>>(), vec![bar]);
+    /// ```
+    ///
+    /// [`NodeId::remove`]: struct.NodeId.html#method.remove
+    pub fn remove(&mut self, node: NodeId) {
+        let children = node.children(&self.arena).collect::<Vec<_>>();
+        node.remove(&mut self.arena);
+        self.roots.remove(&node);
+        for child in children {
+            self.update_root(child);
+        }
+    }
+
+    /// Removes a node and its descendants from the forest, updating the roots.
+    ///
+    /// See [`NodeId::remove_subtree`].
+    ///
+    /// [`NodeId::remove_subtree`]: struct.NodeId.html#method.remove_subtree
+    pub fn remove_subtree(&mut self, node: NodeId) {
+        node.remove_subtree(&mut self.arena);
+        self.roots.remove(&node);
+    }
+
+    /// Adds the node to the roots if it has no parent, and removes it
+    /// otherwise.
+    fn update_root(&mut self, node: NodeId) {
+        if self.arena[node].parent.is_none() {
+            self.roots.insert(node);
+        } else {
+            self.roots.remove(&node);
+        }
+    }
+}
+
+impl<T> Default for Forest<T> {
+    fn default() -> Self {
+        Self {
+            arena: Arena::new(),
+            roots: BTreeSet::new(),
+        }
+    }
+}
+
+impl<T> From<Arena<T>> for Forest<T> {
+    /// Creates a forest from an arena, scanning it once for roots.
+    fn from(arena: Arena<T>) -> Self {
+        let roots = arena
+            .iter_pairs()
+            .filter(|(_, node)| node.parent.is_none())
+            .map(|(id, _)| id)
+            .collect();
+        Self { arena, roots }
+    }
+}
+
+impl<T> Index<NodeId> for Forest<T> {
+    type Output = Node<T>;
+
+    fn index(&self, node: NodeId) -> &Node<T> {
+        &self.arena[node]
+    }
+}
+
+impl<T> IndexMut<NodeId> for Forest<T> {
+    fn index_mut(&mut self, node: NodeId) -> &mut Node<T> {
+        &mut self.arena[node]
+    }
+}
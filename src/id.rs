@@ -1,41 +1,158 @@
 //! Node ID.
 
 #[cfg(not(feature = "std"))]
-use core::fmt;
+use alloc::{collections::VecDeque, string::String, vec::Vec};
+#[cfg(not(feature = "std"))]
+use core::{
+    cmp::Ordering,
+    convert::Infallible,
+    fmt,
+    hash::{Hash, Hasher},
+    iter::Sum,
+    num::NonZeroUsize,
+    ops::Range,
+};
 #[cfg(feature = "std")]
-use std::fmt;
+use std::{
+    cmp::Ordering,
+    collections::{hash_map::DefaultHasher, HashMap, VecDeque},
+    convert::Infallible,
+    fmt,
+    hash::{Hash, Hasher},
+    iter::Sum,
+    num::NonZeroUsize,
+    ops::Range,
+};
 
 use generational_arena::Index;
 #[cfg(feature = "deser")]
 use serde::{Deserialize, Serialize};
+#[cfg(feature = "json")]
+use serde::de::DeserializeOwned;
+#[cfg(feature = "json")]
+use std::io;
+
+#[cfg(feature = "deser")]
+use crate::nested::SerializeTree;
 
 use crate::{
-    Ancestors, Arena, Children, Descendants, FollowingSiblings,
-    NodeError, PrecedingSiblings, relations::insert_with_neighbors, ReverseChildren, ReverseTraverse, siblings_range::SiblingsRange,
-    Traverse,
+    Ancestors, Arena, Children, Descendants, diff, EditAction, FollowingSiblings, NodeEdge,
+    Node, NodeError, PrecedingSiblings, relations::{connect_neighbors, insert_with_neighbors}, ReverseChildren, ReverseTraverse, siblings_range::SiblingsRange,
+    Traverse, TreeEdit, WeakNodeId,
 };
 
-#[derive(PartialEq, Eq, PartialOrd, Ord, Copy, Clone, Debug, Hash)]
+#[derive(Copy, Clone)]
 #[cfg_attr(feature = "deser", derive(Deserialize, Serialize))]
+#[cfg_attr(feature = "deser", serde(from = "SerdeNodeId", into = "SerdeNodeId"))]
 /// A node identifier within a particular [`Arena`].
 ///
 /// This ID is used to get [`Node`] references from an [`Arena`].
 ///
+/// In debug builds the ID also remembers which arena created it, and using it
+/// with another arena panics. This check is compiled out of release builds.
+///
+/// IDs are totally ordered by their slot in the arena, then by generation.
+/// This is storage-order, which is reproducible for the same sequence of
+/// insertions and removals but unrelated to the position of the nodes in
+/// their trees; use [`Arena::cmp_document_order`] for the latter.
+///
+/// ```
+/// # use generational_indextree::Arena;
+/// # use std::collections::BTreeSet;
+/// let mut arena = Arena::new();
+/// let foo = arena.new_node("foo");
+/// let bar = arena.new_node("bar");
+/// bar.append(foo, &mut arena);
+///
+/// let visited = vec![bar, foo].into_iter().collect::<BTreeSet<_>>();
+/// assert_eq!(visited.into_iter().collect::<Vec<_>>(), vec![foo, bar]);
+/// ```
+///
 /// [`Arena`]: struct.Arena.html
 /// [`Node`]: struct.Node.html
+/// [`Arena::cmp_document_order`]: struct.Arena.html#method.cmp_document_order
 pub struct NodeId {
+    /// Slot of the node in the arena, plus one so that `Option<NodeId>` can
+    /// use `0` as `None` and is no larger than `NodeId`.
+    slot: NonZeroUsize,
+    /// Generation of the slot when the node was created.
+    generation: u64,
+    /// Stamp of the arena which created this ID, `0` if unknown.
+    #[cfg(debug_assertions)]
+    arena_id: usize,
+}
+
+/// The serialized form of a `NodeId`, which is the one of its `Index`.
+#[cfg(feature = "deser")]
+#[derive(Deserialize, Serialize)]
+struct SerdeNodeId {
     index: Index,
 }
 
+#[cfg(feature = "deser")]
+impl From<SerdeNodeId> for NodeId {
+    fn from(id: SerdeNodeId) -> NodeId {
+        NodeId::from_raw_index(id.index, 0)
+    }
+}
+
+#[cfg(feature = "deser")]
+impl From<NodeId> for SerdeNodeId {
+    fn from(id: NodeId) -> SerdeNodeId {
+        SerdeNodeId {
+            index: id.get_index(),
+        }
+    }
+}
+
+// The arena stamp only exists in debug builds, so it must not take part in
+// comparisons. Like `Index`, IDs are ordered by slot, then by generation.
+impl PartialEq for NodeId {
+    fn eq(&self, other: &Self) -> bool {
+        (self.slot, self.generation) == (other.slot, other.generation)
+    }
+}
+
+impl Eq for NodeId {}
+
+impl PartialOrd for NodeId {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for NodeId {
+    fn cmp(&self, other: &Self) -> Ordering {
+        (self.slot, self.generation).cmp(&(other.slot, other.generation))
+    }
+}
+
+impl Hash for NodeId {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.slot.hash(state);
+        self.generation.hash(state);
+    }
+}
+
+impl fmt::Debug for NodeId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut debug = f.debug_struct("NodeId");
+        debug.field("index", &self.get_index());
+        #[cfg(debug_assertions)]
+        debug.field("arena_id", &self.arena_id);
+        debug.finish()
+    }
+}
+
 impl fmt::Display for NodeId {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "{:?}", self.index)
+        write!(f, "{:?}", self.get_index())
     }
 }
 
-impl Into<Index> for NodeId {
-    fn into(self) -> Index {
-        self.index
+impl From<NodeId> for Index {
+    fn from(id: NodeId) -> Index {
+        id.get_index()
     }
 }
 
@@ -43,18 +160,89 @@ impl Into<Index> for NodeId {
 impl NodeId {
     /// Returns index.
     pub(crate) fn get_index(self) -> Index {
-        self.index
+        Index::from_raw_parts(self.slot.get() - 1, self.generation)
     }
 
-    /// Creates a new `NodeId` from the given index.
-    pub(crate) fn from_index(index: Index) -> Self {
-        NodeId { index }
+    /// Creates a new `NodeId` from the given index in the given arena.
+    #[cfg_attr(not(debug_assertions), allow(unused_variables))]
+    pub(crate) fn from_index<T>(index: Index, arena: &Arena<T>) -> Self {
+        NodeId::from_raw_index(index, arena.stamp())
     }
 
-    /// Returns an iterator of IDs of this node and its ancestors.
+    /// Creates a new `NodeId` from the given index, stamped with the given
+    /// arena stamp in debug builds.
+    #[cfg_attr(not(debug_assertions), allow(unused_variables))]
+    pub(crate) fn from_raw_index(index: Index, arena_id: usize) -> Self {
+        let (slot, generation) = index.into_raw_parts();
+        NodeId {
+            slot: NonZeroUsize::new(slot + 1)
+                .expect("Should never fail: slots are bounded by the maximum allocation size"),
+            generation,
+            #[cfg(debug_assertions)]
+            arena_id,
+        }
+    }
+
+    /// Returns a placeholder for the ID of a node written without it, which
+    /// is replaced once the node is back in its arena.
+    #[cfg(feature = "deser")]
+    pub(crate) fn unknown() -> Self {
+        NodeId::from_raw_index(Index::from_raw_parts(0, 0), 0)
+    }
+
+    /// Returns a weak version of this ID, which has to be checked against the
+    /// arena before each use.
     ///
-    /// Use [`.skip(1)`][`skip`] or call `.next()` once on the iterator to skip
-    /// the node itself.
+    /// See [`WeakNodeId`].
+    ///
+    /// [`WeakNodeId`]: struct.WeakNodeId.html
+    pub fn downgrade(self) -> WeakNodeId {
+        WeakNodeId::from(self)
+    }
+
+    /// Returns `true` if both IDs refer to the same node, i.e. the same slot
+    /// of the arena in the same generation.
+    ///
+    /// This is pure identity: the data of the nodes is never looked at, and
+    /// no arena is needed. It is what `==` currently does on IDs, spelled out
+    /// for code which relies on it. An ID of a removed node is not the same
+    /// node as the one later created in its slot.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use generational_indextree::Arena;
+    /// let mut arena = Arena::new();
+    /// let foo = arena.new_node("foo");
+    /// let bar = arena.new_node("foo");
+    /// assert!(foo.same_node(foo));
+    /// assert!(!foo.same_node(bar));
+    ///
+    /// foo.remove(&mut arena);
+    /// let baz = arena.new_node("foo");
+    /// assert!(!foo.same_node(baz));
+    /// ```
+    pub fn same_node(self, other: NodeId) -> bool {
+        (self.slot, self.generation) == (other.slot, other.generation)
+    }
+
+    /// Returns `true` if this ID may have been created by the given arena.
+    ///
+    /// This is always `true` in release builds, and for IDs or arenas which
+    /// were deserialized.
+    #[cfg_attr(not(debug_assertions), allow(unused_variables))]
+    pub(crate) fn belongs_to<T>(self, arena: &Arena<T>) -> bool {
+        #[cfg(debug_assertions)]
+        {
+            self.arena_id == 0 || arena.arena_id == 0 || self.arena_id == arena.arena_id
+        }
+        #[cfg(not(debug_assertions))]
+        {
+            true
+        }
+    }
+
+    /// Returns `true` if this node has a parent and no siblings.
     ///
     /// # Examples
     ///
@@ -66,38 +254,25 @@ impl NodeId {
     /// # n1.append(n1_1, &mut arena);
     /// # let n1_1_1 = arena.new_node("1_1_1");
     /// # n1_1.append(n1_1_1, &mut arena);
-    /// # let n1_1_1_1 = arena.new_node("1_1_1_1");
-    /// # n1_1_1.append(n1_1_1_1, &mut arena);
     /// # let n1_2 = arena.new_node("1_2");
     /// # n1.append(n1_2, &mut arena);
-    /// # let n1_3 = arena.new_node("1_3");
-    /// # n1.append(n1_3, &mut arena);
     /// #
     /// // arena
     /// // `-- 1
     /// //     |-- 1_1
     /// //     |   `-- 1_1_1
-    /// //     |       `-- 1_1_1_1
-    /// //     _-- 1_2
-    /// //     `-- 1_3
+    /// //     `-- 1_2
     ///
-    /// let mut iter = n1_1_1.ancestors(&arena);
-    /// assert_eq!(iter.next(), Some(n1_1_1));
-    /// assert_eq!(iter.next(), Some(n1_1));
-    /// assert_eq!(iter.next(), Some(n1));
-    /// assert_eq!(iter.next(), None);
+    /// assert!(n1_1_1.is_only_child(&arena));
+    /// assert!(!n1_1.is_only_child(&arena));
+    /// assert!(!n1.is_only_child(&arena));
     /// ```
-    ///
-    /// [`skip`]: https://doc.rust-lang.org/stable/std/iter/trait.Iterator.html#method.skip
-    pub fn ancestors<T>(self, arena: &Arena<T>) -> Ancestors<'_, T> {
-        Ancestors::new(arena, self)
+    pub fn is_only_child<T>(self, arena: &Arena<T>) -> bool {
+        let node = &arena[self];
+        node.parent.is_some() && node.previous_sibling.is_none() && node.next_sibling.is_none()
     }
 
-    /// Returns an iterator of IDs of this node and the siblings before
-    /// it.
-    ///
-    /// Use [`.skip(1)`][`skip`] or call `.next()` once on the iterator to skip
-    /// the node itself.
+    /// Returns `true` if this node has a parent and no previous sibling.
     ///
     /// # Examples
     ///
@@ -107,36 +282,24 @@ impl NodeId {
     /// # let n1 = arena.new_node("1");
     /// # let n1_1 = arena.new_node("1_1");
     /// # n1.append(n1_1, &mut arena);
-    /// # let n1_1_1 = arena.new_node("1_1_1");
-    /// # n1_1.append(n1_1_1, &mut arena);
     /// # let n1_2 = arena.new_node("1_2");
     /// # n1.append(n1_2, &mut arena);
-    /// # let n1_3 = arena.new_node("1_3");
-    /// # n1.append(n1_3, &mut arena);
     /// #
     /// // arena
     /// // `-- 1
     /// //     |-- 1_1
-    /// //     |   `-- 1_1_1
-    /// //     |-- 1_2
-    /// //     `-- 1_3
+    /// //     `-- 1_2
     ///
-    /// let mut iter = n1_2.preceding_siblings(&arena);
-    /// assert_eq!(iter.next(), Some(n1_2));
-    /// assert_eq!(iter.next(), Some(n1_1));
-    /// assert_eq!(iter.next(), None);
+    /// assert!(n1_1.is_first_child(&arena));
+    /// assert!(!n1_2.is_first_child(&arena));
+    /// assert!(!n1.is_first_child(&arena));
     /// ```
-    ///
-    /// [`skip`]: https://doc.rust-lang.org/stable/std/iter/trait.Iterator.html#method.skip
-    pub fn preceding_siblings<T>(self, arena: &Arena<T>) -> PrecedingSiblings<'_, T> {
-        PrecedingSiblings::new(arena, self)
+    pub fn is_first_child<T>(self, arena: &Arena<T>) -> bool {
+        let node = &arena[self];
+        node.parent.is_some() && node.previous_sibling.is_none()
     }
 
-    /// Returns an iterator of IDs of this node and the siblings after
-    /// it.
-    ///
-    /// Use [`.skip(1)`][`skip`] or call `.next()` once on the iterator to skip
-    /// the node itself.
+    /// Returns `true` if this node has a parent and no next sibling.
     ///
     /// # Examples
     ///
@@ -146,32 +309,27 @@ impl NodeId {
     /// # let n1 = arena.new_node("1");
     /// # let n1_1 = arena.new_node("1_1");
     /// # n1.append(n1_1, &mut arena);
-    /// # let n1_1_1 = arena.new_node("1_1_1");
-    /// # n1_1.append(n1_1_1, &mut arena);
     /// # let n1_2 = arena.new_node("1_2");
     /// # n1.append(n1_2, &mut arena);
-    /// # let n1_3 = arena.new_node("1_3");
-    /// # n1.append(n1_3, &mut arena);
     /// #
     /// // arena
     /// // `-- 1
     /// //     |-- 1_1
-    /// //     |   `-- 1_1_1
-    /// //     |-- 1_2
-    /// //     `-- 1_3
+    /// //     `-- 1_2
     ///
-    /// let mut iter = n1_2.following_siblings(&arena);
-    /// assert_eq!(iter.next(), Some(n1_2));
-    /// assert_eq!(iter.next(), Some(n1_3));
-    /// assert_eq!(iter.next(), None);
+    /// assert!(n1_2.is_last_child(&arena));
+    /// assert!(!n1_1.is_last_child(&arena));
+    /// assert!(!n1.is_last_child(&arena));
     /// ```
-    ///
-    /// [`skip`]: https://doc.rust-lang.org/stable/std/iter/trait.Iterator.html#method.skip
-    pub fn following_siblings<T>(self, arena: &Arena<T>) -> FollowingSiblings<'_, T> {
-        FollowingSiblings::new(arena, self)
+    pub fn is_last_child<T>(self, arena: &Arena<T>) -> bool {
+        let node = &arena[self];
+        node.parent.is_some() && node.next_sibling.is_none()
     }
 
-    /// Returns an iterator of IDs of this node’s children.
+    /// Returns `true` if this node and `other` are distinct nodes with the
+    /// same parent.
+    ///
+    /// Roots are never siblings, even if they are linked as siblings.
     ///
     /// # Examples
     ///
@@ -181,32 +339,29 @@ impl NodeId {
     /// # let n1 = arena.new_node("1");
     /// # let n1_1 = arena.new_node("1_1");
     /// # n1.append(n1_1, &mut arena);
-    /// # let n1_1_1 = arena.new_node("1_1_1");
-    /// # n1_1.append(n1_1_1, &mut arena);
     /// # let n1_2 = arena.new_node("1_2");
     /// # n1.append(n1_2, &mut arena);
-    /// # let n1_3 = arena.new_node("1_3");
-    /// # n1.append(n1_3, &mut arena);
+    /// # let n2 = arena.new_node("2");
     /// #
     /// // arena
-    /// // `-- 1
-    /// //     |-- 1_1
-    /// //     |   `-- 1_1_1
-    /// //     |-- 1_2
-    /// //     `-- 1_3
+    /// // |-- 1
+    /// // |   |-- 1_1
+    /// // |   `-- 1_2
+    /// // `-- 2
     ///
-    /// let mut iter = n1.children(&arena);
-    /// assert_eq!(iter.next(), Some(n1_1));
-    /// assert_eq!(iter.next(), Some(n1_2));
-    /// assert_eq!(iter.next(), Some(n1_3));
-    /// assert_eq!(iter.next(), None);
+    /// assert!(n1_1.is_sibling_of(n1_2, &arena));
+    /// assert!(!n1_1.is_sibling_of(n1_1, &arena));
+    /// assert!(!n1.is_sibling_of(n2, &arena));
     /// ```
-    pub fn children<T>(self, arena: &Arena<T>) -> Children<'_, T> {
-        Children::new(arena, self)
+    pub fn is_sibling_of<T>(self, other: NodeId, arena: &Arena<T>) -> bool {
+        self != other && arena[self].parent.is_some() && arena[self].parent == arena[other].parent
     }
 
-    /// Returns an iterator of IDs of this node’s children, in reverse
-    /// order.
+    /// Returns `true` if `candidate` is one of the ancestors of this node,
+    /// not counting the node itself.
+    ///
+    /// This walks up from this node, and stops as soon as `candidate` is
+    /// found.
     ///
     /// # Examples
     ///
@@ -220,31 +375,24 @@ impl NodeId {
     /// # n1_1.append(n1_1_1, &mut arena);
     /// # let n1_2 = arena.new_node("1_2");
     /// # n1.append(n1_2, &mut arena);
-    /// # let n1_3 = arena.new_node("1_3");
-    /// # n1.append(n1_3, &mut arena);
     /// #
     /// // arena
     /// // `-- 1
     /// //     |-- 1_1
     /// //     |   `-- 1_1_1
-    /// //     |-- 1_2
-    /// //     `-- 1_3
+    /// //     `-- 1_2
     ///
-    /// let mut iter = n1.reverse_children(&arena);
-    /// assert_eq!(iter.next(), Some(n1_3));
-    /// assert_eq!(iter.next(), Some(n1_2));
-    /// assert_eq!(iter.next(), Some(n1_1));
-    /// assert_eq!(iter.next(), None);
+    /// assert!(n1_1_1.has_ancestor(n1, &arena));
+    /// assert!(n1_1_1.has_ancestor(n1_1, &arena));
+    /// assert!(!n1_1_1.has_ancestor(n1_1_1, &arena));
+    /// assert!(!n1_1_1.has_ancestor(n1_2, &arena));
     /// ```
-    pub fn reverse_children<T>(self, arena: &Arena<T>) -> ReverseChildren<'_, T> {
-        ReverseChildren::new(arena, self)
+    pub fn has_ancestor<T>(self, candidate: NodeId, arena: &Arena<T>) -> bool {
+        self.ancestors(arena).skip(1).any(|id| id == candidate)
     }
 
-    /// An iterator of the IDs of a given node and its descendants, as a pre-order depth-first search where children are visited in insertion order.
-    ///
-    /// i.e. node -> first child -> second child
+    /// Returns an iterator of IDs of this node and its ancestors.
     ///
-    /// Parent nodes appear before the descendants.
     /// Use [`.skip(1)`][`skip`] or call `.next()` once on the iterator to skip
     /// the node itself.
     ///
@@ -270,111 +418,129 @@ impl NodeId {
     /// //     |-- 1_1
     /// //     |   `-- 1_1_1
     /// //     |       `-- 1_1_1_1
-    /// //     |-- 1_2
+    /// //     _-- 1_2
     /// //     `-- 1_3
     ///
-    /// let mut iter = n1.descendants(&arena);
-    /// assert_eq!(iter.next(), Some(n1));
-    /// assert_eq!(iter.next(), Some(n1_1));
+    /// let mut iter = n1_1_1.ancestors(&arena);
     /// assert_eq!(iter.next(), Some(n1_1_1));
-    /// assert_eq!(iter.next(), Some(n1_1_1_1));
-    /// assert_eq!(iter.next(), Some(n1_2));
-    /// assert_eq!(iter.next(), Some(n1_3));
+    /// assert_eq!(iter.next(), Some(n1_1));
+    /// assert_eq!(iter.next(), Some(n1));
     /// assert_eq!(iter.next(), None);
     /// ```
     ///
     /// [`skip`]: https://doc.rust-lang.org/stable/std/iter/trait.Iterator.html#method.skip
-    pub fn descendants<T>(self, arena: &Arena<T>) -> Descendants<'_, T> {
-        Descendants::new(arena, self)
+    pub fn ancestors<T>(self, arena: &Arena<T>) -> Ancestors<'_, T> {
+        Ancestors::new(arena, self)
     }
 
-    /// An iterator of the "sides" of a node visited during a depth-first pre-order traversal,
-    /// where node sides are visited start to end and children are visited in insertion order.
-    ///
-    /// i.e. node.start -> first child -> second child -> node.end
+    /// Returns the root of the tree containing this node, i.e. its topmost
+    /// ancestor, or the node itself if it has no parent.
     ///
     /// # Examples
     ///
     /// ```
-    /// # use generational_indextree::{Arena, NodeEdge};
+    /// # use generational_indextree::Arena;
     /// # let mut arena = Arena::new();
     /// # let n1 = arena.new_node("1");
     /// # let n1_1 = arena.new_node("1_1");
     /// # n1.append(n1_1, &mut arena);
     /// # let n1_1_1 = arena.new_node("1_1_1");
     /// # n1_1.append(n1_1_1, &mut arena);
-    /// # let n1_2 = arena.new_node("1_2");
-    /// # n1.append(n1_2, &mut arena);
-    /// # let n1_3 = arena.new_node("1_3");
-    /// # n1.append(n1_3, &mut arena);
     /// #
     /// // arena
     /// // `-- 1
-    /// //     |-- 1_1
-    /// //     |   `-- 1_1_1
-    /// //     |-- 1_2
-    /// //     `-- 1_3
+    /// //     `-- 1_1
+    /// //         `-- 1_1_1
     ///
-    /// let mut iter = n1.traverse(&arena);
-    /// assert_eq!(iter.next(), Some(NodeEdge::Start(n1)));
-    /// assert_eq!(iter.next(), Some(NodeEdge::Start(n1_1)));
-    /// assert_eq!(iter.next(), Some(NodeEdge::Start(n1_1_1)));
-    /// assert_eq!(iter.next(), Some(NodeEdge::End(n1_1_1)));
-    /// assert_eq!(iter.next(), Some(NodeEdge::End(n1_1)));
-    /// assert_eq!(iter.next(), Some(NodeEdge::Start(n1_2)));
-    /// assert_eq!(iter.next(), Some(NodeEdge::End(n1_2)));
-    /// assert_eq!(iter.next(), Some(NodeEdge::Start(n1_3)));
-    /// assert_eq!(iter.next(), Some(NodeEdge::End(n1_3)));
-    /// assert_eq!(iter.next(), Some(NodeEdge::End(n1)));
-    /// assert_eq!(iter.next(), None);
+    /// assert_eq!(n1_1_1.root(&arena), n1);
+    /// assert_eq!(n1.root(&arena), n1);
     /// ```
-    pub fn traverse<T>(self, arena: &Arena<T>) -> Traverse<'_, T> {
-        Traverse::new(arena, self)
+    pub fn root<T>(self, arena: &Arena<T>) -> NodeId {
+        let mut node = self;
+        while let Some(parent) = arena[node].parent {
+            node = parent;
+        }
+        node
     }
 
-    /// An iterator of the "sides" of a node visited during a depth-first pre-order traversal,
-    /// where nodes are visited end to start and children are visited in reverse insertion order.
+    /// Returns the ancestor `n + 1` levels above this node.
     ///
-    /// i.e. node.end -> second child -> first child -> node.start
+    /// The count is 0-based: `nth_ancestor(arena, 0)` is the parent,
+    /// `nth_ancestor(arena, 1)` the grandparent, and so on. Returns `None` if
+    /// the node does not have that many ancestors.
     ///
     /// # Examples
     ///
     /// ```
-    /// # use generational_indextree::{Arena, NodeEdge};
+    /// # use generational_indextree::Arena;
     /// # let mut arena = Arena::new();
     /// # let n1 = arena.new_node("1");
     /// # let n1_1 = arena.new_node("1_1");
     /// # n1.append(n1_1, &mut arena);
     /// # let n1_1_1 = arena.new_node("1_1_1");
     /// # n1_1.append(n1_1_1, &mut arena);
-    /// # let n1_2 = arena.new_node("1_2");
-    /// # n1.append(n1_2, &mut arena);
-    /// # let n1_3 = arena.new_node("1_3");
-    /// # n1.append(n1_3, &mut arena);
     /// #
     /// // arena
     /// // `-- 1
-    /// //     |-- 1_1
-    /// //     |   `-- 1_1_1
-    /// //     |-- 1_2
-    /// //     `-- 1_3
+    /// //     `-- 1_1
+    /// //         `-- 1_1_1
     ///
-    /// let mut iter = n1.reverse_traverse(&arena);
-    /// assert_eq!(iter.next(), Some(NodeEdge::End(n1)));
-    /// assert_eq!(iter.next(), Some(NodeEdge::End(n1_3)));
-    /// assert_eq!(iter.next(), Some(NodeEdge::Start(n1_3)));
-    /// assert_eq!(iter.next(), Some(NodeEdge::End(n1_2)));
-    /// assert_eq!(iter.next(), Some(NodeEdge::Start(n1_2)));
-    /// assert_eq!(iter.next(), Some(NodeEdge::End(n1_1)));
-    /// assert_eq!(iter.next(), Some(NodeEdge::End(n1_1_1)));
-    /// assert_eq!(iter.next(), Some(NodeEdge::Start(n1_1_1)));
-    /// assert_eq!(iter.next(), Some(NodeEdge::Start(n1_1)));
-    /// assert_eq!(iter.next(), Some(NodeEdge::Start(n1)));
-    /// assert_eq!(iter.next(), None);
+    /// assert_eq!(n1_1_1.nth_ancestor(&arena, 0), Some(n1_1));
+    /// assert_eq!(n1_1_1.nth_ancestor(&arena, 1), Some(n1));
+    /// assert_eq!(n1_1_1.nth_ancestor(&arena, 2), None);
     /// ```
+    pub fn nth_ancestor<T>(self, arena: &Arena<T>, n: usize) -> Option<NodeId> {
+        self.ancestors(arena).nth(n.checked_add(1)?)
+    }
+
+    /// Returns the names of the ancestors of this node and of the node
+    /// itself, from the root down, joined with `sep`.
+    ///
+    /// # Examples
     ///
     /// ```
-    /// # use generational_indextree::{Arena, NodeEdge};
+    /// # use generational_indextree::Arena;
+    /// # let mut arena = Arena::new();
+    /// # let n1 = arena.new_node("usr");
+    /// # let n1_1 = arena.new_node("share");
+    /// # n1.append(n1_1, &mut arena);
+    /// # let n1_1_1 = arena.new_node("doc");
+    /// # n1_1.append(n1_1_1, &mut arena);
+    /// #
+    /// // arena
+    /// // `-- usr
+    /// //     `-- share
+    /// //         `-- doc
+    ///
+    /// let path = n1_1_1.path_string(&arena, "/", |name| name.to_string());
+    /// assert_eq!(path, "usr/share/doc");
+    /// assert_eq!(n1.path_string(&arena, "/", |name| name.to_string()), "usr");
+    /// ```
+    pub fn path_string<T, F: Fn(&T) -> String>(
+        self,
+        arena: &Arena<T>,
+        sep: &str,
+        name: F,
+    ) -> String {
+        let mut names = self
+            .ancestors(arena)
+            .map(|node| name(&arena[node].data))
+            .collect::<Vec<_>>();
+        names.reverse();
+        names.join(sep)
+    }
+
+    /// Renders the subtree rooted at this node as text, one node per line,
+    /// each line being the label of the node prefixed by `indent` once per
+    /// level below this node.
+    ///
+    /// This is a plain alternative to box-drawing tree output, e.g. for logs.
+    /// Every line, including the last one, ends with a newline.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use generational_indextree::Arena;
     /// # let mut arena = Arena::new();
     /// # let n1 = arena.new_node("1");
     /// # let n1_1 = arena.new_node("1_1");
@@ -383,263 +549,4369 @@ impl NodeId {
     /// # n1_1.append(n1_1_1, &mut arena);
     /// # let n1_2 = arena.new_node("1_2");
     /// # n1.append(n1_2, &mut arena);
-    /// # let n1_3 = arena.new_node("1_3");
+    /// #
+    /// // arena
+    /// // `-- 1
+    /// //     |-- 1_1
+    /// //     |   `-- 1_1_1
+    /// //     `-- 1_2
+    ///
+    /// let text = n1.to_indented(&arena, "  ", |name| name.to_string());
+    /// assert_eq!(text, "1\n  1_1\n    1_1_1\n  1_2\n");
+    /// ```
+    pub fn to_indented<T, F: Fn(&T) -> String>(
+        self,
+        arena: &Arena<T>,
+        indent: &str,
+        label: F,
+    ) -> String {
+        let mut text = String::new();
+        let mut depth = 0;
+        for edge in self.traverse(arena) {
+            match edge {
+                NodeEdge::Start(id) => {
+                    text.push_str(&indent.repeat(depth));
+                    text.push_str(&label(&arena[id].data));
+                    text.push('\n');
+                    depth += 1;
+                }
+                NodeEdge::End(_) => depth -= 1,
+            }
+        }
+        text
+    }
+
+    /// Returns the node reached by descending from this node along `path`.
+    ///
+    /// At each step, the first child whose `key` equals the next element of
+    /// the path is taken. Returns `None` if no child matches at some step, and
+    /// this node for an empty path. This is the inverse of [`path_string`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use generational_indextree::Arena;
+    /// # let mut arena = Arena::new();
+    /// # let n1 = arena.new_node("usr");
+    /// # let n1_1 = arena.new_node("share");
+    /// # n1.append(n1_1, &mut arena);
+    /// # let n1_1_1 = arena.new_node("doc");
+    /// # n1_1.append(n1_1_1, &mut arena);
+    /// # let n1_2 = arena.new_node("lib");
+    /// # n1.append(n1_2, &mut arena);
+    /// #
+    /// // arena
+    /// // `-- usr
+    /// //     |-- share
+    /// //     |   `-- doc
+    /// //     `-- lib
+    ///
+    /// assert_eq!(n1.resolve_path(&arena, &["share", "doc"], |&name| name), Some(n1_1_1));
+    /// assert_eq!(n1.resolve_path(&arena, &["lib", "doc"], |&name| name), None);
+    /// assert_eq!(n1.resolve_path(&arena, &[], |&name| name), Some(n1));
+    /// ```
+    ///
+    /// [`path_string`]: struct.NodeId.html#method.path_string
+    pub fn resolve_path<T, K: PartialEq, F: Fn(&T) -> K>(
+        self,
+        arena: &Arena<T>,
+        path: &[K],
+        key: F,
+    ) -> Option<NodeId> {
+        path.iter().try_fold(self, |node, step| {
+            node.children(arena)
+                .find(|&child| key(&arena[child].data) == *step)
+        })
+    }
+
+    /// Returns an iterator of references to the data of this node and its
+    /// ancestors, in the same order as [`ancestors`].
+    ///
+    /// Use [`.skip(1)`][`skip`] or call `.next()` once on the iterator to skip
+    /// the node itself.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use generational_indextree::Arena;
+    /// # let mut arena = Arena::new();
+    /// # let n1 = arena.new_node("1");
+    /// # let n1_1 = arena.new_node("1_1");
+    /// # n1.append(n1_1, &mut arena);
+    /// # let n1_1_1 = arena.new_node("1_1_1");
+    /// # n1_1.append(n1_1_1, &mut arena);
+    /// #
+    /// // arena
+    /// // `-- 1
+    /// //     `-- 1_1
+    /// //         `-- 1_1_1
+    ///
+    /// let breadcrumbs = n1_1_1.ancestors_data(&arena).skip(1).collect::<Vec<_>>();
+    /// assert_eq!(breadcrumbs, vec![&"1_1", &"1"]);
+    /// ```
+    ///
+    /// [`ancestors`]: struct.NodeId.html#method.ancestors
+    /// [`skip`]: https://doc.rust-lang.org/stable/std/iter/trait.Iterator.html#method.skip
+    pub fn ancestors_data<T>(self, arena: &Arena<T>) -> impl Iterator<Item = &T> {
+        self.ancestors(arena).map(move |id| &arena[id].data)
+    }
+
+    /// Returns an iterator of IDs of this node and the siblings before
+    /// it.
+    ///
+    /// Use [`.skip(1)`][`skip`] or call `.next()` once on the iterator to skip
+    /// the node itself.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use generational_indextree::Arena;
+    /// # let mut arena = Arena::new();
+    /// # let n1 = arena.new_node("1");
+    /// # let n1_1 = arena.new_node("1_1");
+    /// # n1.append(n1_1, &mut arena);
+    /// # let n1_1_1 = arena.new_node("1_1_1");
+    /// # n1_1.append(n1_1_1, &mut arena);
+    /// # let n1_2 = arena.new_node("1_2");
+    /// # n1.append(n1_2, &mut arena);
+    /// # let n1_3 = arena.new_node("1_3");
+    /// # n1.append(n1_3, &mut arena);
+    /// #
+    /// // arena
+    /// // `-- 1
+    /// //     |-- 1_1
+    /// //     |   `-- 1_1_1
+    /// //     |-- 1_2
+    /// //     `-- 1_3
+    ///
+    /// let mut iter = n1_2.preceding_siblings(&arena);
+    /// assert_eq!(iter.next(), Some(n1_2));
+    /// assert_eq!(iter.next(), Some(n1_1));
+    /// assert_eq!(iter.next(), None);
+    /// ```
+    ///
+    /// [`skip`]: https://doc.rust-lang.org/stable/std/iter/trait.Iterator.html#method.skip
+    pub fn preceding_siblings<T>(self, arena: &Arena<T>) -> PrecedingSiblings<'_, T> {
+        PrecedingSiblings::new(arena, self)
+    }
+
+    /// Returns an iterator of IDs of this node and the siblings after
+    /// it.
+    ///
+    /// Use [`.skip(1)`][`skip`] or call `.next()` once on the iterator to skip
+    /// the node itself.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use generational_indextree::Arena;
+    /// # let mut arena = Arena::new();
+    /// # let n1 = arena.new_node("1");
+    /// # let n1_1 = arena.new_node("1_1");
+    /// # n1.append(n1_1, &mut arena);
+    /// # let n1_1_1 = arena.new_node("1_1_1");
+    /// # n1_1.append(n1_1_1, &mut arena);
+    /// # let n1_2 = arena.new_node("1_2");
+    /// # n1.append(n1_2, &mut arena);
+    /// # let n1_3 = arena.new_node("1_3");
+    /// # n1.append(n1_3, &mut arena);
+    /// #
+    /// // arena
+    /// // `-- 1
+    /// //     |-- 1_1
+    /// //     |   `-- 1_1_1
+    /// //     |-- 1_2
+    /// //     `-- 1_3
+    ///
+    /// let mut iter = n1_2.following_siblings(&arena);
+    /// assert_eq!(iter.next(), Some(n1_2));
+    /// assert_eq!(iter.next(), Some(n1_3));
+    /// assert_eq!(iter.next(), None);
+    /// ```
+    ///
+    /// [`skip`]: https://doc.rust-lang.org/stable/std/iter/trait.Iterator.html#method.skip
+    pub fn following_siblings<T>(self, arena: &Arena<T>) -> FollowingSiblings<'_, T> {
+        FollowingSiblings::new(arena, self)
+    }
+
+    /// Returns an iterator of IDs of the other children of this node's
+    /// parent, in order.
+    ///
+    /// Unlike [`preceding_siblings`] and [`following_siblings`], this does not
+    /// include the node itself. Nothing is yielded for a node without parent.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use generational_indextree::Arena;
+    /// # let mut arena = Arena::new();
+    /// # let n1 = arena.new_node("1");
+    /// # let n1_1 = arena.new_node("1_1");
+    /// # n1.append(n1_1, &mut arena);
+    /// # let n1_2 = arena.new_node("1_2");
+    /// # n1.append(n1_2, &mut arena);
+    /// # let n1_3 = arena.new_node("1_3");
+    /// # n1.append(n1_3, &mut arena);
+    /// #
+    /// // arena
+    /// // `-- 1
+    /// //     |-- 1_1
+    /// //     |-- 1_2
+    /// //     `-- 1_3
+    ///
+    /// assert_eq!(n1_2.siblings(&arena).collect::<Vec<_>>(), vec![n1_1, n1_3]);
+    /// assert_eq!(n1.siblings(&arena).next(), None);
+    /// ```
+    ///
+    /// [`preceding_siblings`]: struct.NodeId.html#method.preceding_siblings
+    /// [`following_siblings`]: struct.NodeId.html#method.following_siblings
+    pub fn siblings<T>(self, arena: &Arena<T>) -> impl Iterator<Item = NodeId> + '_ {
+        arena[self]
+            .parent
+            .into_iter()
+            .flat_map(move |parent| parent.children(arena))
+            .filter(move |&sibling| sibling != self)
+    }
+
+    /// Returns an iterator of IDs of this node’s children.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use generational_indextree::Arena;
+    /// # let mut arena = Arena::new();
+    /// # let n1 = arena.new_node("1");
+    /// # let n1_1 = arena.new_node("1_1");
+    /// # n1.append(n1_1, &mut arena);
+    /// # let n1_1_1 = arena.new_node("1_1_1");
+    /// # n1_1.append(n1_1_1, &mut arena);
+    /// # let n1_2 = arena.new_node("1_2");
+    /// # n1.append(n1_2, &mut arena);
+    /// # let n1_3 = arena.new_node("1_3");
+    /// # n1.append(n1_3, &mut arena);
+    /// #
+    /// // arena
+    /// // `-- 1
+    /// //     |-- 1_1
+    /// //     |   `-- 1_1_1
+    /// //     |-- 1_2
+    /// //     `-- 1_3
+    ///
+    /// let mut iter = n1.children(&arena);
+    /// assert_eq!(iter.next(), Some(n1_1));
+    /// assert_eq!(iter.next(), Some(n1_2));
+    /// assert_eq!(iter.next(), Some(n1_3));
+    /// assert_eq!(iter.next(), None);
+    /// ```
+    pub fn children<T>(self, arena: &Arena<T>) -> Children<'_, T> {
+        Children::new(arena, self)
+    }
+
+    /// Returns the IDs of the children of this node, in order, as an owned
+    /// vector.
+    ///
+    /// Unlike [`children`], the result does not borrow the arena, so it can be
+    /// used to modify the children while going through them. The children are
+    /// counted first so that the vector is allocated only once.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use generational_indextree::Arena;
+    /// # let mut arena = Arena::new();
+    /// # let n1 = arena.new_node(1);
+    /// # let n1_1 = arena.new_node(2);
+    /// # n1.append(n1_1, &mut arena);
+    /// # let n1_2 = arena.new_node(3);
+    /// # n1.append(n1_2, &mut arena);
+    /// #
+    /// // arena
+    /// // `-- 1
+    /// //     |-- 2
+    /// //     `-- 3
+    ///
+    /// for child in n1.children_vec(&arena) {
+    ///     let copy = arena.new_node(*arena[child].get() * 10);
+    ///     child.insert_after(copy, &mut arena);
+    /// }
+    /// assert_eq!(n1.children_data(&arena).collect::<Vec<_>>(), vec![&2, &20, &3, &30]);
+    /// assert!(n1_1.children_vec(&arena).is_empty());
+    /// ```
+    ///
+    /// [`children`]: struct.NodeId.html#method.children
+    pub fn children_vec<T>(self, arena: &Arena<T>) -> Vec<NodeId> {
+        let mut children = Vec::with_capacity(self.children(arena).count());
+        children.extend(self.children(arena));
+        children
+    }
+
+    /// Returns an iterator of references to the data of this node’s
+    /// children, in the same order as [`children`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use generational_indextree::Arena;
+    /// # let mut arena = Arena::new();
+    /// # let n1 = arena.new_node("1");
+    /// # let n1_1 = arena.new_node("1_1");
+    /// # n1.append(n1_1, &mut arena);
+    /// # let n1_1_1 = arena.new_node("1_1_1");
+    /// # n1_1.append(n1_1_1, &mut arena);
+    /// # let n1_2 = arena.new_node("1_2");
+    /// # n1.append(n1_2, &mut arena);
+    /// #
+    /// // arena
+    /// // `-- 1
+    /// //     |-- 1_1
+    /// //     |   `-- 1_1_1
+    /// //     `-- 1_2
+    ///
+    /// let mut iter = n1.children_data(&arena);
+    /// assert_eq!(iter.next(), Some(&"1_1"));
+    /// assert_eq!(iter.next(), Some(&"1_2"));
+    /// assert_eq!(iter.next(), None);
+    /// ```
+    ///
+    /// [`children`]: struct.NodeId.html#method.children
+    pub fn children_data<T>(self, arena: &Arena<T>) -> impl Iterator<Item = &T> {
+        self.children(arena).map(move |id| &arena[id].data)
+    }
+
+    /// Calls `f` with the data of each child of this node, in order.
+    ///
+    /// This is a callback rather than an iterator of `&mut T`, as such an
+    /// iterator would have to borrow the arena mutably for the data while
+    /// reading the sibling links from it. The IDs are collected before the
+    /// first call instead.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use generational_indextree::Arena;
+    /// # let mut arena = Arena::new();
+    /// # let n1 = arena.new_node(1);
+    /// # let n1_1 = arena.new_node(2);
+    /// # n1.append(n1_1, &mut arena);
+    /// # let n1_1_1 = arena.new_node(3);
+    /// # n1_1.append(n1_1_1, &mut arena);
+    /// # let n1_2 = arena.new_node(4);
+    /// # n1.append(n1_2, &mut arena);
+    /// #
+    /// // arena
+    /// // `-- 1
+    /// //     |-- 2
+    /// //     |   `-- 3
+    /// //     `-- 4
+    ///
+    /// n1.children_mut(&mut arena, |_, data| *data *= 10);
+    /// assert_eq!(n1.children_data(&arena).collect::<Vec<_>>(), vec![&20, &40]);
+    /// assert_eq!(*arena[n1_1_1].get(), 3);
+    /// ```
+    pub fn children_mut<T, F: FnMut(NodeId, &mut T)>(self, arena: &mut Arena<T>, mut f: F) {
+        for child in self.children(arena).collect::<Vec<_>>() {
+            f(child, &mut arena[child].data);
+        }
+    }
+
+    /// Returns the first child of this node whose data matches `pred`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use generational_indextree::Arena;
+    /// # let mut arena = Arena::new();
+    /// # let n1 = arena.new_node(1);
+    /// # let n1_1 = arena.new_node(2);
+    /// # n1.append(n1_1, &mut arena);
+    /// # let n1_1_1 = arena.new_node(5);
+    /// # n1_1.append(n1_1_1, &mut arena);
+    /// # let n1_2 = arena.new_node(4);
+    /// # n1.append(n1_2, &mut arena);
+    /// #
+    /// // arena
+    /// // `-- 1
+    /// //     |-- 2
+    /// //     |   `-- 5
+    /// //     `-- 4
+    ///
+    /// assert_eq!(n1.first_child_matching(&arena, |&data| data > 3), Some(n1_2));
+    /// assert_eq!(n1.first_child_matching(&arena, |&data| data > 4), None);
+    /// ```
+    pub fn first_child_matching<T, F: Fn(&T) -> bool>(
+        self,
+        arena: &Arena<T>,
+        pred: F,
+    ) -> Option<NodeId> {
+        self.children(arena).find(|&child| pred(&arena[child].data))
+    }
+
+    /// Returns an iterator of IDs of this node’s children, in reverse
+    /// order.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use generational_indextree::Arena;
+    /// # let mut arena = Arena::new();
+    /// # let n1 = arena.new_node("1");
+    /// # let n1_1 = arena.new_node("1_1");
+    /// # n1.append(n1_1, &mut arena);
+    /// # let n1_1_1 = arena.new_node("1_1_1");
+    /// # n1_1.append(n1_1_1, &mut arena);
+    /// # let n1_2 = arena.new_node("1_2");
+    /// # n1.append(n1_2, &mut arena);
+    /// # let n1_3 = arena.new_node("1_3");
+    /// # n1.append(n1_3, &mut arena);
+    /// #
+    /// // arena
+    /// // `-- 1
+    /// //     |-- 1_1
+    /// //     |   `-- 1_1_1
+    /// //     |-- 1_2
+    /// //     `-- 1_3
+    ///
+    /// let mut iter = n1.reverse_children(&arena);
+    /// assert_eq!(iter.next(), Some(n1_3));
+    /// assert_eq!(iter.next(), Some(n1_2));
+    /// assert_eq!(iter.next(), Some(n1_1));
+    /// assert_eq!(iter.next(), None);
+    /// ```
+    pub fn reverse_children<T>(self, arena: &Arena<T>) -> ReverseChildren<'_, T> {
+        ReverseChildren::new(arena, self)
+    }
+
+    /// An iterator of the IDs of a given node and its descendants, as a pre-order depth-first search where children are visited in insertion order.
+    ///
+    /// i.e. node -> first child -> second child
+    ///
+    /// Parent nodes appear before the descendants.
+    /// Use [`.skip(1)`][`skip`] or call `.next()` once on the iterator to skip
+    /// the node itself.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use generational_indextree::Arena;
+    /// # let mut arena = Arena::new();
+    /// # let n1 = arena.new_node("1");
+    /// # let n1_1 = arena.new_node("1_1");
+    /// # n1.append(n1_1, &mut arena);
+    /// # let n1_1_1 = arena.new_node("1_1_1");
+    /// # n1_1.append(n1_1_1, &mut arena);
+    /// # let n1_1_1_1 = arena.new_node("1_1_1_1");
+    /// # n1_1_1.append(n1_1_1_1, &mut arena);
+    /// # let n1_2 = arena.new_node("1_2");
+    /// # n1.append(n1_2, &mut arena);
+    /// # let n1_3 = arena.new_node("1_3");
+    /// # n1.append(n1_3, &mut arena);
+    /// #
+    /// // arena
+    /// // `-- 1
+    /// //     |-- 1_1
+    /// //     |   `-- 1_1_1
+    /// //     |       `-- 1_1_1_1
+    /// //     |-- 1_2
+    /// //     `-- 1_3
+    ///
+    /// let mut iter = n1.descendants(&arena);
+    /// assert_eq!(iter.next(), Some(n1));
+    /// assert_eq!(iter.next(), Some(n1_1));
+    /// assert_eq!(iter.next(), Some(n1_1_1));
+    /// assert_eq!(iter.next(), Some(n1_1_1_1));
+    /// assert_eq!(iter.next(), Some(n1_2));
+    /// assert_eq!(iter.next(), Some(n1_3));
+    /// assert_eq!(iter.next(), None);
+    /// ```
+    ///
+    /// [`skip`]: https://doc.rust-lang.org/stable/std/iter/trait.Iterator.html#method.skip
+    pub fn descendants<T>(self, arena: &Arena<T>) -> Descendants<'_, T> {
+        Descendants::new(arena, self)
+    }
+
+    /// Returns an iterator of IDs of this node and its descendants whose data
+    /// matches `pred`, in preorder.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use generational_indextree::Arena;
+    /// # let mut arena = Arena::new();
+    /// # let n1 = arena.new_node(1);
+    /// # let n1_1 = arena.new_node(2);
+    /// # n1.append(n1_1, &mut arena);
+    /// # let n1_1_1 = arena.new_node(5);
+    /// # n1_1.append(n1_1_1, &mut arena);
+    /// # let n1_2 = arena.new_node(4);
+    /// # n1.append(n1_2, &mut arena);
+    /// #
+    /// // arena
+    /// // `-- 1
+    /// //     |-- 2
+    /// //     |   `-- 5
+    /// //     `-- 4
+    ///
+    /// let iter = n1.descendants_matching(&arena, |&data| data > 1);
+    /// assert_eq!(iter.collect::<Vec<_>>(), vec![n1_1, n1_1_1, n1_2]);
+    /// ```
+    pub fn descendants_matching<'a, T, F: Fn(&T) -> bool + 'a>(
+        self,
+        arena: &'a Arena<T>,
+        pred: F,
+    ) -> impl Iterator<Item = NodeId> + 'a {
+        self.descendants(arena)
+            .filter(move |&node| pred(&arena[node].data))
+    }
+
+    /// Returns an iterator of IDs of this node and its descendants, in
+    /// post-order: every node comes after its descendants, so this node is
+    /// last.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use generational_indextree::Arena;
+    /// # let mut arena = Arena::new();
+    /// # let n1 = arena.new_node("1");
+    /// # let n1_1 = arena.new_node("1_1");
+    /// # n1.append(n1_1, &mut arena);
+    /// # let n1_1_1 = arena.new_node("1_1_1");
+    /// # n1_1.append(n1_1_1, &mut arena);
+    /// # let n1_2 = arena.new_node("1_2");
+    /// # n1.append(n1_2, &mut arena);
+    /// #
+    /// // arena
+    /// // `-- 1
+    /// //     |-- 1_1
+    /// //     |   `-- 1_1_1
+    /// //     `-- 1_2
+    ///
+    /// let iter = n1.descendants_postorder(&arena);
+    /// assert_eq!(iter.collect::<Vec<_>>(), vec![n1_1_1, n1_1, n1_2, n1]);
+    /// ```
+    pub fn descendants_postorder<T>(self, arena: &Arena<T>) -> impl Iterator<Item = NodeId> + '_ {
+        self.traverse(arena).filter_map(|edge| match edge {
+            NodeEdge::Start(_) => None,
+            NodeEdge::End(node) => Some(node),
+        })
+    }
+
+    /// Returns references to the data of this node and its descendants, in
+    /// post-order.
+    ///
+    /// This is the order in which an expression tree is evaluated; see
+    /// [`descendants_postorder`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use generational_indextree::Arena;
+    /// # let mut arena = Arena::new();
+    /// # let n1 = arena.new_node("+");
+    /// # let n1_1 = arena.new_node("*");
+    /// # n1.append(n1_1, &mut arena);
+    /// # let n1_1_1 = arena.new_node("2");
+    /// # n1_1.append(n1_1_1, &mut arena);
+    /// # let n1_1_2 = arena.new_node("3");
+    /// # n1_1.append(n1_1_2, &mut arena);
+    /// # let n1_2 = arena.new_node("4");
+    /// # n1.append(n1_2, &mut arena);
+    /// #
+    /// // arena
+    /// // `-- +
+    /// //     |-- *
+    /// //     |   |-- 2
+    /// //     |   `-- 3
+    /// //     `-- 4
+    ///
+    /// assert_eq!(n1.postorder_data(&arena), vec![&"2", &"3", &"*", &"4", &"+"]);
+    /// ```
+    ///
+    /// [`descendants_postorder`]: struct.NodeId.html#method.descendants_postorder
+    pub fn postorder_data<T>(self, arena: &Arena<T>) -> Vec<&T> {
+        self.descendants_postorder(arena)
+            .map(|node| &arena[node].data)
+            .collect()
+    }
+
+    /// An iterator of the "sides" of a node visited during a depth-first pre-order traversal,
+    /// where node sides are visited start to end and children are visited in insertion order.
+    ///
+    /// i.e. node.start -> first child -> second child -> node.end
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use generational_indextree::{Arena, NodeEdge};
+    /// # let mut arena = Arena::new();
+    /// # let n1 = arena.new_node("1");
+    /// # let n1_1 = arena.new_node("1_1");
+    /// # n1.append(n1_1, &mut arena);
+    /// # let n1_1_1 = arena.new_node("1_1_1");
+    /// # n1_1.append(n1_1_1, &mut arena);
+    /// # let n1_2 = arena.new_node("1_2");
+    /// # n1.append(n1_2, &mut arena);
+    /// # let n1_3 = arena.new_node("1_3");
+    /// # n1.append(n1_3, &mut arena);
+    /// #
+    /// // arena
+    /// // `-- 1
+    /// //     |-- 1_1
+    /// //     |   `-- 1_1_1
+    /// //     |-- 1_2
+    /// //     `-- 1_3
+    ///
+    /// let mut iter = n1.traverse(&arena);
+    /// assert_eq!(iter.next(), Some(NodeEdge::Start(n1)));
+    /// assert_eq!(iter.next(), Some(NodeEdge::Start(n1_1)));
+    /// assert_eq!(iter.next(), Some(NodeEdge::Start(n1_1_1)));
+    /// assert_eq!(iter.next(), Some(NodeEdge::End(n1_1_1)));
+    /// assert_eq!(iter.next(), Some(NodeEdge::End(n1_1)));
+    /// assert_eq!(iter.next(), Some(NodeEdge::Start(n1_2)));
+    /// assert_eq!(iter.next(), Some(NodeEdge::End(n1_2)));
+    /// assert_eq!(iter.next(), Some(NodeEdge::Start(n1_3)));
+    /// assert_eq!(iter.next(), Some(NodeEdge::End(n1_3)));
+    /// assert_eq!(iter.next(), Some(NodeEdge::End(n1)));
+    /// assert_eq!(iter.next(), None);
+    /// ```
+    pub fn traverse<T>(self, arena: &Arena<T>) -> Traverse<'_, T> {
+        Traverse::new(arena, self)
+    }
+
+    /// Writes the edges of [`traverse`] into `buffer`, and returns them.
+    ///
+    /// The buffer is cleared first, and keeps its capacity, so reusing the
+    /// same buffer across calls, e.g. once per frame, stops allocating once it
+    /// has grown to the size of the tree. `traverse` itself never allocates;
+    /// this is for walks which need the edges collected, e.g. to mutate the
+    /// arena while going through them, as the returned slice only borrows the
+    /// buffer.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use generational_indextree::{Arena, NodeEdge};
+    /// # let mut arena = Arena::new();
+    /// # let n1 = arena.new_node(1);
+    /// # let n1_1 = arena.new_node(2);
+    /// # n1.append(n1_1, &mut arena);
+    /// #
+    /// // arena
+    /// // `-- 1
+    /// //     `-- 2
+    ///
+    /// let mut buffer = Vec::new();
+    /// for _ in 0..2 {
+    ///     for &edge in n1.traverse_with(&arena, &mut buffer) {
+    ///         if let NodeEdge::End(id) = edge {
+    ///             *arena[id].get_mut() *= 10;
+    ///         }
+    ///     }
+    /// }
+    /// assert_eq!(buffer.len(), 4);
+    /// assert_eq!(*arena[n1_1].get(), 200);
+    /// ```
+    ///
+    /// [`traverse`]: struct.NodeId.html#method.traverse
+    pub fn traverse_with<'b, T>(
+        self,
+        arena: &Arena<T>,
+        buffer: &'b mut Vec<NodeEdge>,
+    ) -> &'b [NodeEdge] {
+        buffer.clear();
+        buffer.extend(self.traverse(arena));
+        buffer
+    }
+
+    /// An iterator of the "sides" of a node visited during a depth-first pre-order traversal,
+    /// where nodes are visited end to start and children are visited in reverse insertion order.
+    ///
+    /// i.e. node.end -> second child -> first child -> node.start
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use generational_indextree::{Arena, NodeEdge};
+    /// # let mut arena = Arena::new();
+    /// # let n1 = arena.new_node("1");
+    /// # let n1_1 = arena.new_node("1_1");
+    /// # n1.append(n1_1, &mut arena);
+    /// # let n1_1_1 = arena.new_node("1_1_1");
+    /// # n1_1.append(n1_1_1, &mut arena);
+    /// # let n1_2 = arena.new_node("1_2");
+    /// # n1.append(n1_2, &mut arena);
+    /// # let n1_3 = arena.new_node("1_3");
+    /// # n1.append(n1_3, &mut arena);
+    /// #
+    /// // arena
+    /// // `-- 1
+    /// //     |-- 1_1
+    /// //     |   `-- 1_1_1
+    /// //     |-- 1_2
+    /// //     `-- 1_3
+    ///
+    /// let mut iter = n1.reverse_traverse(&arena);
+    /// assert_eq!(iter.next(), Some(NodeEdge::End(n1)));
+    /// assert_eq!(iter.next(), Some(NodeEdge::End(n1_3)));
+    /// assert_eq!(iter.next(), Some(NodeEdge::Start(n1_3)));
+    /// assert_eq!(iter.next(), Some(NodeEdge::End(n1_2)));
+    /// assert_eq!(iter.next(), Some(NodeEdge::Start(n1_2)));
+    /// assert_eq!(iter.next(), Some(NodeEdge::End(n1_1)));
+    /// assert_eq!(iter.next(), Some(NodeEdge::End(n1_1_1)));
+    /// assert_eq!(iter.next(), Some(NodeEdge::Start(n1_1_1)));
+    /// assert_eq!(iter.next(), Some(NodeEdge::Start(n1_1)));
+    /// assert_eq!(iter.next(), Some(NodeEdge::Start(n1)));
+    /// assert_eq!(iter.next(), None);
+    /// ```
+    ///
+    /// ```
+    /// # use generational_indextree::{Arena, NodeEdge};
+    /// # let mut arena = Arena::new();
+    /// # let n1 = arena.new_node("1");
+    /// # let n1_1 = arena.new_node("1_1");
+    /// # n1.append(n1_1, &mut arena);
+    /// # let n1_1_1 = arena.new_node("1_1_1");
+    /// # n1_1.append(n1_1_1, &mut arena);
+    /// # let n1_2 = arena.new_node("1_2");
+    /// # n1.append(n1_2, &mut arena);
+    /// # let n1_3 = arena.new_node("1_3");
+    /// # n1.append(n1_3, &mut arena);
+    /// #
+    /// # // arena
+    /// # // `-- 1
+    /// # //     |-- 1_1
+    /// # //     |   `-- 1_1_1
+    /// # //     |-- 1_2
+    /// # //     `-- 1_3
+    /// #
+    /// let traverse = n1.traverse(&arena).collect::<Vec<_>>();
+    /// let mut reverse = n1.reverse_traverse(&arena).collect::<Vec<_>>();
+    /// reverse.reverse();
+    /// assert_eq!(traverse, reverse);
+    /// ```
+    pub fn reverse_traverse<T>(self, arena: &Arena<T>) -> ReverseTraverse<'_, T> {
+        ReverseTraverse::new(arena, self)
+    }
+
+    /// Returns an iterator of the IDs, depths and data of this node and its
+    /// descendants, in the same order as [`descendants`].
+    ///
+    /// The depth is relative to this node, which has depth `0`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use generational_indextree::Arena;
+    /// # let mut arena = Arena::new();
+    /// # let n1 = arena.new_node("1");
+    /// # let n1_1 = arena.new_node("1_1");
+    /// # n1.append(n1_1, &mut arena);
+    /// # let n1_1_1 = arena.new_node("1_1_1");
+    /// # n1_1.append(n1_1_1, &mut arena);
+    /// # let n1_2 = arena.new_node("1_2");
+    /// # n1.append(n1_2, &mut arena);
+    /// #
+    /// // arena
+    /// // `-- 1
+    /// //     |-- 1_1
+    /// //     |   `-- 1_1_1
+    /// //     `-- 1_2
+    ///
+    /// let mut iter = n1.render_rows(&arena);
+    /// assert_eq!(iter.next(), Some((n1, 0, &"1")));
+    /// assert_eq!(iter.next(), Some((n1_1, 1, &"1_1")));
+    /// assert_eq!(iter.next(), Some((n1_1_1, 2, &"1_1_1")));
+    /// assert_eq!(iter.next(), Some((n1_2, 1, &"1_2")));
+    /// assert_eq!(iter.next(), None);
+    /// ```
+    ///
+    /// [`descendants`]: struct.NodeId.html#method.descendants
+    pub fn render_rows<T>(self, arena: &Arena<T>) -> impl Iterator<Item = (NodeId, usize, &T)> {
+        let mut depth = 0;
+        self.traverse(arena).filter_map(move |edge| match edge {
+            NodeEdge::Start(id) => {
+                depth += 1;
+                Some((id, depth - 1, &arena[id].data))
+            }
+            NodeEdge::End(_) => {
+                depth -= 1;
+                None
+            }
+        })
+    }
+
+    /// Returns the IDs of this node and its descendants, in preorder.
+    ///
+    /// This is the same as collecting [`descendants`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use generational_indextree::Arena;
+    /// # let mut arena = Arena::new();
+    /// # let n1 = arena.new_node("1");
+    /// # let n1_1 = arena.new_node("1_1");
+    /// # n1.append(n1_1, &mut arena);
+    /// # let n1_1_1 = arena.new_node("1_1_1");
+    /// # n1_1.append(n1_1_1, &mut arena);
+    /// # let n1_2 = arena.new_node("1_2");
+    /// # n1.append(n1_2, &mut arena);
+    /// #
+    /// // arena
+    /// // `-- 1
+    /// //     |-- 1_1
+    /// //     |   `-- 1_1_1
+    /// //     `-- 1_2
+    ///
+    /// assert_eq!(n1.to_vec(&arena), vec![n1, n1_1, n1_1_1, n1_2]);
+    /// assert_eq!(n1_2.to_vec(&arena), vec![n1_2]);
+    /// ```
+    ///
+    /// [`descendants`]: struct.NodeId.html#method.descendants
+    pub fn to_vec<T>(self, arena: &Arena<T>) -> Vec<NodeId> {
+        self.descendants(arena).collect()
+    }
+
+    /// Calls `f` on this node and its descendants, in preorder.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use generational_indextree::Arena;
+    /// # let mut arena = Arena::new();
+    /// # let n1 = arena.new_node("1");
+    /// # let n1_1 = arena.new_node("1_1");
+    /// # n1.append(n1_1, &mut arena);
+    /// # let n1_1_1 = arena.new_node("1_1_1");
+    /// # n1_1.append(n1_1_1, &mut arena);
+    /// # let n1_2 = arena.new_node("1_2");
+    /// # n1.append(n1_2, &mut arena);
+    /// #
+    /// // arena
+    /// // `-- 1
+    /// //     |-- 1_1
+    /// //     |   `-- 1_1_1
+    /// //     `-- 1_2
+    ///
+    /// let mut visited = Vec::new();
+    /// n1.visit_preorder(&arena, |_, node| visited.push(*node.get()));
+    /// assert_eq!(visited, vec!["1", "1_1", "1_1_1", "1_2"]);
+    /// ```
+    pub fn visit_preorder<T, F: FnMut(NodeId, &Node<T>)>(self, arena: &Arena<T>, mut f: F) {
+        for node in self.descendants(arena) {
+            f(node, &arena[node]);
+        }
+    }
+
+    /// Calls `f` on this node and its descendants, in post-order, i.e. every
+    /// node after its descendants.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use generational_indextree::Arena;
+    /// # let mut arena = Arena::new();
+    /// # let n1 = arena.new_node("1");
+    /// # let n1_1 = arena.new_node("1_1");
+    /// # n1.append(n1_1, &mut arena);
+    /// # let n1_1_1 = arena.new_node("1_1_1");
+    /// # n1_1.append(n1_1_1, &mut arena);
+    /// # let n1_2 = arena.new_node("1_2");
+    /// # n1.append(n1_2, &mut arena);
+    /// #
+    /// // arena
+    /// // `-- 1
+    /// //     |-- 1_1
+    /// //     |   `-- 1_1_1
+    /// //     `-- 1_2
+    ///
+    /// let mut visited = Vec::new();
+    /// n1.visit_postorder(&arena, |_, node| visited.push(*node.get()));
+    /// assert_eq!(visited, vec!["1_1_1", "1_1", "1_2", "1"]);
+    /// ```
+    pub fn visit_postorder<T, F: FnMut(NodeId, &Node<T>)>(self, arena: &Arena<T>, mut f: F) {
+        for node in self.descendants_postorder(arena) {
+            f(node, &arena[node]);
+        }
+    }
+
+    /// Calls `f` with the data of this node, if it is in the arena.
+    ///
+    /// Returns `false` if the node has been removed, in which case `f` is not
+    /// called.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use generational_indextree::Arena;
+    /// let mut arena = Arena::new();
+    /// let foo = arena.new_node(1);
+    ///
+    /// assert!(foo.update(&mut arena, |data| *data += 1));
+    /// assert_eq!(*arena[foo].get(), 2);
+    ///
+    /// foo.remove(&mut arena);
+    /// assert!(!foo.update(&mut arena, |data| *data += 1));
+    /// ```
+    pub fn update<T, F: FnOnce(&mut T)>(self, arena: &mut Arena<T>, f: F) -> bool {
+        match arena.get_mut(self) {
+            Some(node) => {
+                f(&mut node.data);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Calls `f` with the data of this node and its descendants, in preorder.
+    ///
+    /// The IDs are collected before the first call, since the closure holds
+    /// the mutable borrow of the data while the walk needs the links. The
+    /// closure only gets the data, so the structure of the tree can not
+    /// change during the walk.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use generational_indextree::Arena;
+    /// # let mut arena = Arena::new();
+    /// # let n1 = arena.new_node(1);
+    /// # let n1_1 = arena.new_node(2);
+    /// # n1.append(n1_1, &mut arena);
+    /// # let n1_2 = arena.new_node(3);
+    /// # n1.append(n1_2, &mut arena);
+    /// #
+    /// // arena
+    /// // `-- 1
+    /// //     |-- 2
+    /// //     `-- 3
+    ///
+    /// let mut index = 0;
+    /// n1.visit_data_mut(&mut arena, |_, data| {
+    ///     *data *= 10;
+    ///     *data += index;
+    ///     index += 1;
+    /// });
+    /// assert_eq!(*arena[n1].get(), 10);
+    /// assert_eq!(*arena[n1_1].get(), 21);
+    /// assert_eq!(*arena[n1_2].get(), 32);
+    /// ```
+    pub fn visit_data_mut<T, F: FnMut(NodeId, &mut T)>(self, arena: &mut Arena<T>, mut f: F) {
+        for node in self.to_vec(arena) {
+            f(node, &mut arena[node].data);
+        }
+    }
+
+    /// Applies `replace` to the data of every node of the subtree rooted at
+    /// this node for which `matches` returns `true`, and returns the number
+    /// of replaced nodes.
+    ///
+    /// Nodes are visited in preorder, and the structure of the tree is not
+    /// changed.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use generational_indextree::Arena;
+    /// # let mut arena = Arena::new();
+    /// # let n1 = arena.new_node("todo");
+    /// # let n1_1 = arena.new_node("done");
+    /// # n1.append(n1_1, &mut arena);
+    /// # let n1_2 = arena.new_node("todo");
+    /// # n1.append(n1_2, &mut arena);
+    /// #
+    /// // arena
+    /// // `-- todo
+    /// //     |-- done
+    /// //     `-- todo
+    ///
+    /// let replaced = n1.replace_matching(&mut arena, |data| *data == "todo", |data| *data = "done");
+    /// assert_eq!(replaced, 2);
+    /// assert!(n1.descendants(&arena).all(|id| *arena[id].get() == "done"));
+    /// ```
+    pub fn replace_matching<T, F: Fn(&T) -> bool, G: FnMut(&mut T)>(
+        self,
+        arena: &mut Arena<T>,
+        matches: F,
+        mut replace: G,
+    ) -> usize {
+        let mut count = 0;
+        self.visit_data_mut(arena, |_, data| {
+            if matches(data) {
+                replace(data);
+                count += 1;
+            }
+        });
+        count
+    }
+
+    /// Walks the subtree rooted at this node in preorder, letting `f` modify
+    /// the arena at every node, and acting on the returned [`EditAction`].
+    ///
+    /// The next node is only looked up after `f` has returned, so `f` may
+    /// change the tree, e.g. add children to the current node, which are then
+    /// visited. It must not remove the current node itself, but return
+    /// [`EditAction::RemoveSubtree`] instead.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use generational_indextree::{Arena, EditAction};
+    /// # let mut arena = Arena::new();
+    /// # let n1 = arena.new_node(1);
+    /// # let n1_1 = arena.new_node(-2);
+    /// # n1.append(n1_1, &mut arena);
+    /// # let n1_1_1 = arena.new_node(3);
+    /// # n1_1.append(n1_1_1, &mut arena);
+    /// # let n1_2 = arena.new_node(4);
+    /// # n1.append(n1_2, &mut arena);
+    /// # let n1_2_1 = arena.new_node(5);
+    /// # n1_2.append(n1_2_1, &mut arena);
+    /// #
+    /// // arena
+    /// // `-- 1
+    /// //     |-- -2
+    /// //     |   `-- 3
+    /// //     `-- 4
+    /// //         `-- 5
+    ///
+    /// let mut visited = Vec::new();
+    /// n1.edit_preorder(&mut arena, |id, arena| {
+    ///     let data = *arena[id].get();
+    ///     visited.push(data);
+    ///     if data < 0 {
+    ///         EditAction::RemoveSubtree
+    ///     } else if data == 4 {
+    ///         *arena[id].get_mut() = 40;
+    ///         EditAction::SkipChildren
+    ///     } else {
+    ///         EditAction::Keep
+    ///     }
+    /// });
+    /// // arena
+    /// // `-- 1
+    /// //     `-- 40
+    /// //         `-- 5
+    ///
+    /// assert_eq!(visited, vec![1, -2, 4]);
+    /// assert_eq!(arena.count(), 3);
+    /// assert_eq!(*arena[n1_2].get(), 40);
+    /// ```
+    ///
+    /// [`EditAction`]: enum.EditAction.html
+    /// [`EditAction::RemoveSubtree`]: enum.EditAction.html#variant.RemoveSubtree
+    pub fn edit_preorder<T, F: FnMut(NodeId, &mut Arena<T>) -> EditAction>(
+        self,
+        arena: &mut Arena<T>,
+        mut f: F,
+    ) {
+        let mut current = Some(self);
+        while let Some(id) = current {
+            current = match f(id, arena) {
+                EditAction::Keep => arena[id]
+                    .first_child
+                    .or_else(|| id.next_after_subtree(self, arena)),
+                EditAction::SkipChildren => id.next_after_subtree(self, arena),
+                EditAction::RemoveSubtree => {
+                    let next = id.next_after_subtree(self, arena);
+                    id.remove_subtree(arena);
+                    next
+                }
+            };
+        }
+    }
+
+    /// Walks the subtree rooted at this node in preorder while growing it:
+    /// `f` is called on every visited node, and may append children to it,
+    /// which are visited next.
+    ///
+    /// This is the core loop of generated trees, e.g. game trees. At most
+    /// `max_nodes` nodes are visited, to bound expansions which would not
+    /// stop by themselves, and the number of visited nodes is returned. `f`
+    /// may edit the subtree of the node it is given, but must not detach or
+    /// remove that node or its ancestors.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use generational_indextree::{Arena, NodeId};
+    /// let mut arena = Arena::new();
+    /// let root = arena.new_node(0);
+    ///
+    /// let expand = |id: NodeId, arena: &mut Arena<u32>| {
+    ///     let depth = *arena[id].get();
+    ///     if depth < 2 {
+    ///         for _ in 0..2 {
+    ///             let child = arena.new_node(depth + 1);
+    ///             id.append(child, arena);
+    ///         }
+    ///     }
+    /// };
+    /// assert_eq!(root.expand_dfs(&mut arena, 100, expand), 7);
+    /// // arena
+    /// // `-- 0
+    /// //     |-- 1
+    /// //     |   |-- 2
+    /// //     |   `-- 2
+    /// //     `-- 1
+    /// //         |-- 2
+    /// //         `-- 2
+    ///
+    /// let data = root.descendants(&arena).map(|id| *arena[id].get()).collect::<Vec<_>>();
+    /// assert_eq!(data, vec![0, 1, 2, 2, 1, 2, 2]);
+    ///
+    /// let other = arena.new_node(0);
+    /// assert_eq!(other.expand_dfs(&mut arena, 3, expand), 3);
+    /// assert_eq!(other.descendants(&arena).count(), 5);
+    /// ```
+    pub fn expand_dfs<T, F: FnMut(NodeId, &mut Arena<T>)>(
+        self,
+        arena: &mut Arena<T>,
+        max_nodes: usize,
+        mut f: F,
+    ) -> usize {
+        let mut visited = 0;
+        let mut current = Some(self);
+        while let Some(id) = current.filter(|_| visited < max_nodes) {
+            f(id, arena);
+            visited += 1;
+            current = arena[id]
+                .first_child
+                .or_else(|| id.next_after_subtree(self, arena));
+        }
+        visited
+    }
+
+    /// Returns the node following the subtree of this node in the preorder
+    /// of the subtree of `root`.
+    fn next_after_subtree<T>(self, root: NodeId, arena: &Arena<T>) -> Option<NodeId> {
+        let mut node = self;
+        while node != root {
+            let current = &arena[node];
+            match current.next_sibling {
+                Some(next_sibling) => return Some(next_sibling),
+                None => node = current.parent?,
+            }
+        }
+        None
+    }
+
+    /// Reduces the subtree rooted at this node bottom-up.
+    ///
+    /// The result for a node is `f(data, results)`, where `results` are the
+    /// results of its children, in order. The subtree is walked iteratively,
+    /// so deep trees do not overflow the stack.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use generational_indextree::Arena;
+    /// # let mut arena = Arena::new();
+    /// # let n1 = arena.new_node(1);
+    /// # let n1_1 = arena.new_node(2);
+    /// # n1.append(n1_1, &mut arena);
+    /// # let n1_1_1 = arena.new_node(3);
+    /// # n1_1.append(n1_1_1, &mut arena);
+    /// # let n1_2 = arena.new_node(4);
+    /// # n1.append(n1_2, &mut arena);
+    /// #
+    /// // arena
+    /// // `-- 1
+    /// //     |-- 2
+    /// //     |   `-- 3
+    /// //     `-- 4
+    ///
+    /// let sum = n1.fold(&arena, |&data, sums: Vec<i32>| data + sums.iter().sum::<i32>());
+    /// assert_eq!(sum, 10);
+    ///
+    /// let height = n1.fold(&arena, |_, heights: Vec<usize>| {
+    ///     heights.into_iter().map(|height| height + 1).max().unwrap_or(0)
+    /// });
+    /// assert_eq!(height, 2);
+    /// ```
+    pub fn fold<T, B, F: FnMut(&T, Vec<B>) -> B>(self, arena: &Arena<T>, mut f: F) -> B {
+        match self.try_fold(arena, |data, results| Ok::<_, Infallible>(f(data, results))) {
+            Ok(result) => result,
+            Err(never) => match never {},
+        }
+    }
+
+    /// Reduces the subtree rooted at this node bottom-up, stopping at the
+    /// first error.
+    ///
+    /// This is the fallible version of [`fold`]: nodes are reduced in
+    /// post-order, and the first `Err` returned by `f` is returned without
+    /// visiting the remaining nodes.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use generational_indextree::Arena;
+    /// # let mut arena = Arena::new();
+    /// # let n1 = arena.new_node(1);
+    /// # let n1_1 = arena.new_node(-2);
+    /// # n1.append(n1_1, &mut arena);
+    /// # let n1_2 = arena.new_node(3);
+    /// # n1.append(n1_2, &mut arena);
+    /// #
+    /// // arena
+    /// // `-- 1
+    /// //     |-- -2
+    /// //     `-- 3
+    ///
+    /// let mut visited = Vec::new();
+    /// let sum = n1.try_fold(&arena, |&data, sums: Vec<i32>| {
+    ///     visited.push(data);
+    ///     if data < 0 {
+    ///         return Err(data);
+    ///     }
+    ///     Ok(data + sums.iter().sum::<i32>())
+    /// });
+    /// assert_eq!(sum, Err(-2));
+    /// assert_eq!(visited, vec![-2]);
+    ///
+    /// assert_eq!(n1_2.try_fold(&arena, |&data, _| Ok::<_, ()>(data)), Ok(3));
+    /// ```
+    ///
+    /// [`fold`]: struct.NodeId.html#method.fold
+    pub fn try_fold<T, B, E, F: FnMut(&T, Vec<B>) -> Result<B, E>>(
+        self,
+        arena: &Arena<T>,
+        mut f: F,
+    ) -> Result<B, E> {
+        // Results of the children of every node on the current path.
+        let mut stack: Vec<Vec<B>> = Vec::new();
+        for edge in self.traverse(arena) {
+            match edge {
+                NodeEdge::Start(_) => stack.push(Vec::new()),
+                NodeEdge::End(node) => {
+                    let results = stack.pop().expect("Should never fail: edges are balanced");
+                    let result = f(&arena[node].data, results)?;
+                    match stack.last_mut() {
+                        Some(parent_results) => parent_results.push(result),
+                        None => return Ok(result),
+                    }
+                }
+            }
+        }
+        unreachable!("Should never fail: the traversal ends with this node")
+    }
+
+    /// Returns the number of nodes in the subtree rooted at this node, the
+    /// node itself included, whose data matches `pred`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use generational_indextree::Arena;
+    /// # let mut arena = Arena::new();
+    /// # let n1 = arena.new_node(1);
+    /// # let n1_1 = arena.new_node(2);
+    /// # n1.append(n1_1, &mut arena);
+    /// # let n1_1_1 = arena.new_node(3);
+    /// # n1_1.append(n1_1_1, &mut arena);
+    /// # let n1_2 = arena.new_node(4);
+    /// # n1.append(n1_2, &mut arena);
+    /// #
+    /// // arena
+    /// // `-- 1
+    /// //     |-- 2
+    /// //     |   `-- 3
+    /// //     `-- 4
+    ///
+    /// assert_eq!(n1.count_matching(&arena, |&data| data % 2 == 0), 2);
+    /// assert_eq!(n1_1.count_matching(&arena, |&data| data > 1), 2);
+    /// ```
+    pub fn count_matching<T, F: Fn(&T) -> bool>(self, arena: &Arena<T>, pred: F) -> usize {
+        self.descendants(arena)
+            .filter(|&node| pred(&arena[node].data))
+            .count()
+    }
+
+    /// Maps the data of every node in the subtree rooted at this node, the
+    /// node itself included, through `f` and returns the sum.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use generational_indextree::Arena;
+    /// # let mut arena = Arena::new();
+    /// # let n1 = arena.new_node(("1", 10));
+    /// # let n1_1 = arena.new_node(("1_1", 20));
+    /// # n1.append(n1_1, &mut arena);
+    /// # let n1_1_1 = arena.new_node(("1_1_1", 30));
+    /// # n1_1.append(n1_1_1, &mut arena);
+    /// # let n1_2 = arena.new_node(("1_2", 40));
+    /// # n1.append(n1_2, &mut arena);
+    /// #
+    /// // arena
+    /// // `-- 1 (10)
+    /// //     |-- 1_1 (20)
+    /// //     |   `-- 1_1_1 (30)
+    /// //     `-- 1_2 (40)
+    ///
+    /// assert_eq!(n1.sum_by(&arena, |&(_, size)| size), 100);
+    /// assert_eq!(n1_1.sum_by(&arena, |&(_, size)| size as f64), 50.0);
+    /// ```
+    pub fn sum_by<T, N: Sum, F: Fn(&T) -> N>(self, arena: &Arena<T>, f: F) -> N {
+        self.descendants(arena).map(|node| f(&arena[node].data)).sum()
+    }
+
+    /// Returns `true` if the data of this node and all its descendants
+    /// matches `pred`.
+    ///
+    /// Stops at the first node which does not match.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use generational_indextree::Arena;
+    /// # let mut arena = Arena::new();
+    /// # let n1 = arena.new_node(1);
+    /// # let n1_1 = arena.new_node(2);
+    /// # n1.append(n1_1, &mut arena);
+    /// # let n1_2 = arena.new_node(4);
+    /// # n1.append(n1_2, &mut arena);
+    /// #
+    /// // arena
+    /// // `-- 1
+    /// //     |-- 2
+    /// //     `-- 4
+    ///
+    /// assert!(n1.all_descendants(&arena, |&data| data > 0));
+    /// assert!(!n1.all_descendants(&arena, |&data| data % 2 == 0));
+    /// ```
+    pub fn all_descendants<T, F: Fn(&T) -> bool>(self, arena: &Arena<T>, pred: F) -> bool {
+        self.descendants(arena).all(|node| pred(&arena[node].data))
+    }
+
+    /// Returns `true` if the data of this node or any of its descendants
+    /// matches `pred`.
+    ///
+    /// Stops at the first node which matches.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use generational_indextree::Arena;
+    /// # let mut arena = Arena::new();
+    /// # let n1 = arena.new_node(1);
+    /// # let n1_1 = arena.new_node(2);
+    /// # n1.append(n1_1, &mut arena);
+    /// # let n1_2 = arena.new_node(4);
+    /// # n1.append(n1_2, &mut arena);
+    /// #
+    /// // arena
+    /// // `-- 1
+    /// //     |-- 2
+    /// //     `-- 4
+    ///
+    /// assert!(n1.any_descendant(&arena, |&data| data == 4));
+    /// assert!(!n1_1.any_descendant(&arena, |&data| data == 4));
+    /// ```
+    pub fn any_descendant<T, F: Fn(&T) -> bool>(self, arena: &Arena<T>, pred: F) -> bool {
+        self.descendants(arena).any(|node| pred(&arena[node].data))
+    }
+
+    /// Returns the node of the subtree rooted at this node, the node itself
+    /// included, whose data has the greatest key.
+    ///
+    /// Of several nodes with the greatest key, the first in preorder is
+    /// returned.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use generational_indextree::Arena;
+    /// # let mut arena = Arena::new();
+    /// # let n1 = arena.new_node(1);
+    /// # let n1_1 = arena.new_node(3);
+    /// # n1.append(n1_1, &mut arena);
+    /// # let n1_1_1 = arena.new_node(2);
+    /// # n1_1.append(n1_1_1, &mut arena);
+    /// # let n1_2 = arena.new_node(3);
+    /// # n1.append(n1_2, &mut arena);
+    /// #
+    /// // arena
+    /// // `-- 1
+    /// //     |-- 3
+    /// //     |   `-- 2
+    /// //     `-- 3
+    ///
+    /// assert_eq!(n1.max_by_key(&arena, |&data| data), n1_1);
+    /// assert_eq!(n1_1_1.max_by_key(&arena, |&data| data), n1_1_1);
+    /// ```
+    pub fn max_by_key<T, B: Ord, F: Fn(&T) -> B>(self, arena: &Arena<T>, key: F) -> NodeId {
+        self.extremum_by_key(arena, key, Ordering::Greater)
+    }
+
+    /// Returns the node of the subtree rooted at this node, the node itself
+    /// included, whose data has the smallest key.
+    ///
+    /// Of several nodes with the smallest key, the first in preorder is
+    /// returned.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use generational_indextree::Arena;
+    /// # let mut arena = Arena::new();
+    /// # let n1 = arena.new_node(3);
+    /// # let n1_1 = arena.new_node(1);
+    /// # n1.append(n1_1, &mut arena);
+    /// # let n1_1_1 = arena.new_node(2);
+    /// # n1_1.append(n1_1_1, &mut arena);
+    /// # let n1_2 = arena.new_node(1);
+    /// # n1.append(n1_2, &mut arena);
+    /// #
+    /// // arena
+    /// // `-- 3
+    /// //     |-- 1
+    /// //     |   `-- 2
+    /// //     `-- 1
+    ///
+    /// assert_eq!(n1.min_by_key(&arena, |&data| data), n1_1);
+    /// ```
+    pub fn min_by_key<T, B: Ord, F: Fn(&T) -> B>(self, arena: &Arena<T>, key: F) -> NodeId {
+        self.extremum_by_key(arena, key, Ordering::Less)
+    }
+
+    /// Returns the first node of the subtree, in preorder, whose key compares
+    /// as `wanted` to the keys of all the nodes before it.
+    fn extremum_by_key<T, B: Ord, F: Fn(&T) -> B>(
+        self,
+        arena: &Arena<T>,
+        key: F,
+        wanted: Ordering,
+    ) -> NodeId {
+        let mut best = self;
+        let mut best_key = key(&arena[self].data);
+        for node in self.descendants(arena).skip(1) {
+            let node_key = key(&arena[node].data);
+            if node_key.cmp(&best_key) == wanted {
+                best = node;
+                best_key = node_key;
+            }
+        }
+        best
+    }
+
+    /// Returns `true` if the subtrees rooted at this node and at `other`
+    /// contain the same data the same number of times, regardless of their
+    /// shape.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use generational_indextree::Arena;
+    /// let mut arena = Arena::new();
+    /// let a = arena.new_node(1);
+    /// a.append(arena.new_node(2), &mut arena);
+    /// a.append(arena.new_node(2), &mut arena);
+    /// // arena
+    /// // `-- 1
+    /// //     |-- 2
+    /// //     `-- 2
+    ///
+    /// let mut other_arena = Arena::new();
+    /// let b = other_arena.new_node(2);
+    /// let b_1 = other_arena.new_node(1);
+    /// b.append(b_1, &mut other_arena);
+    /// b_1.append(other_arena.new_node(2), &mut other_arena);
+    /// // other_arena
+    /// // `-- 2
+    /// //     `-- 1
+    /// //         `-- 2
+    ///
+    /// assert!(a.data_multiset_eq(&arena, b, &other_arena));
+    /// assert!(!a.data_multiset_eq(&arena, b_1, &other_arena));
+    /// ```
+    #[cfg(feature = "std")]
+    pub fn data_multiset_eq<T: Hash + Eq>(
+        self,
+        self_arena: &Arena<T>,
+        other: NodeId,
+        other_arena: &Arena<T>,
+    ) -> bool {
+        // Count up for this subtree and down for the other one.
+        let mut counts = HashMap::<&T, isize>::new();
+        for node in self.descendants(self_arena) {
+            *counts.entry(&self_arena[node].data).or_insert(0) += 1;
+        }
+        for node in other.descendants(other_arena) {
+            *counts.entry(&other_arena[node].data).or_insert(0) -= 1;
+        }
+        counts.values().all(|&count| count == 0)
+    }
+
+    /// Returns a map from the key of every node of the subtree rooted at this
+    /// node, including the node itself, to the ID of that node.
+    ///
+    /// When several nodes have the same key, the first one in preorder wins,
+    /// so an ancestor wins over its descendants, and a node over the nodes
+    /// of its following siblings.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use generational_indextree::Arena;
+    /// # let mut arena = Arena::new();
+    /// # let n1 = arena.new_node("a/1");
+    /// # let n1_1 = arena.new_node("b/1");
+    /// # n1.append(n1_1, &mut arena);
+    /// # let n1_1_1 = arena.new_node("a/2");
+    /// # n1_1.append(n1_1_1, &mut arena);
+    /// # let n1_2 = arena.new_node("c/1");
+    /// # n1.append(n1_2, &mut arena);
+    /// #
+    /// // arena
+    /// // `-- a/1
+    /// //     |-- b/1
+    /// //     |   `-- a/2
+    /// //     `-- c/1
+    ///
+    /// let index = n1.index_by_key(&arena, |data| data.split('/').next().unwrap());
+    /// assert_eq!(index.len(), 3);
+    /// assert_eq!(index["a"], n1);
+    /// assert_eq!(index["b"], n1_1);
+    /// assert_eq!(index["c"], n1_2);
+    /// ```
+    #[cfg(feature = "std")]
+    pub fn index_by_key<T, K: Hash + Eq, F: Fn(&T) -> K>(
+        self,
+        arena: &Arena<T>,
+        key: F,
+    ) -> HashMap<K, NodeId> {
+        let mut index = HashMap::new();
+        for id in self.descendants(arena) {
+            index.entry(key(&arena[id].data)).or_insert(id);
+        }
+        index
+    }
+
+    /// Returns `true` if the subtree rooted at this node has the same shape as
+    /// the one rooted at `other`, ignoring the data of the nodes.
+    ///
+    /// Two subtrees have the same shape if their roots have the same number of
+    /// children, and children at the same position have the same shape in
+    /// turn. The arenas may hold different types of data.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use generational_indextree::Arena;
+    /// let mut template = Arena::new();
+    /// let t = template.new_node("section");
+    /// let t_1 = template.new_node("title");
+    /// t.append(t_1, &mut template);
+    ///
+    /// let mut document = Arena::new();
+    /// let d = document.new_node(1);
+    /// let d_1 = document.new_node(2);
+    /// d.append(d_1, &mut document);
+    /// assert!(t.same_shape(&template, d, &document));
+    ///
+    /// let d_1_1 = document.new_node(3);
+    /// d_1.append(d_1_1, &mut document);
+    /// assert!(!t.same_shape(&template, d, &document));
+    /// ```
+    pub fn same_shape<T, U>(
+        self,
+        self_arena: &Arena<T>,
+        other: NodeId,
+        other_arena: &Arena<U>,
+    ) -> bool {
+        // Equal sequences of edge kinds mean equal nesting.
+        let is_start = |edge| matches!(edge, NodeEdge::Start(_));
+        self.traverse(self_arena)
+            .map(is_start)
+            .eq(other.traverse(other_arena).map(is_start))
+    }
+
+    /// Returns a structural hash of the subtree rooted at this node.
+    ///
+    /// The hash of a node combines the hash of its data with the hashes of its
+    /// children, in order, so subtrees with the same shape and data hash
+    /// equal, while different ones almost always differ. Hashes are computed
+    /// with a fixed key, so they are stable across runs of the same build,
+    /// but should not be persisted across versions of the standard library.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use generational_indextree::Arena;
+    /// let mut arena = Arena::new();
+    /// let a = arena.new_node("x");
+    /// a.append(arena.new_node("y"), &mut arena);
+    /// let b = arena.new_node("x");
+    /// b.append(arena.new_node("y"), &mut arena);
+    /// assert_eq!(a.subtree_hash(&arena), b.subtree_hash(&arena));
+    ///
+    /// b.append(arena.new_node("z"), &mut arena);
+    /// assert_ne!(a.subtree_hash(&arena), b.subtree_hash(&arena));
+    /// ```
+    #[cfg(feature = "std")]
+    pub fn subtree_hash<T: Hash>(self, arena: &Arena<T>) -> u64 {
+        self.fold(arena, |data, children: Vec<u64>| node_hash(data, &children))
+    }
+
+    /// Returns the height of the subtree rooted at this node, i.e. the number
+    /// of edges on the longest path from this node down to a leaf.
+    ///
+    /// A leaf has a height of 0. Comparing the heights of the children of a
+    /// node tells how balanced its subtree is.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use generational_indextree::Arena;
+    /// # let mut arena = Arena::new();
+    /// # let n1 = arena.new_node("1");
+    /// # let n1_1 = arena.new_node("1_1");
+    /// # n1.append(n1_1, &mut arena);
+    /// # let n1_1_1 = arena.new_node("1_1_1");
+    /// # n1_1.append(n1_1_1, &mut arena);
+    /// # let n1_2 = arena.new_node("1_2");
+    /// # n1.append(n1_2, &mut arena);
+    /// #
+    /// // arena
+    /// // `-- 1
+    /// //     |-- 1_1
+    /// //     |   `-- 1_1_1
+    /// //     `-- 1_2
+    ///
+    /// assert_eq!(n1.height(&arena), 2);
+    /// assert_eq!(n1_1.height(&arena), 1);
+    /// assert_eq!(n1_2.height(&arena), 0);
+    /// ```
+    pub fn height<T>(self, arena: &Arena<T>) -> usize {
+        let mut depth = 0;
+        let mut max_depth = 0;
+        for edge in self.traverse(arena) {
+            match edge {
+                NodeEdge::Start(_) => {
+                    depth += 1;
+                    max_depth = max_depth.max(depth);
+                }
+                NodeEdge::End(_) => depth -= 1,
+            }
+        }
+        max_depth - 1
+    }
+
+    /// Returns the number of nodes at each depth of the subtree rooted at this
+    /// node.
+    ///
+    /// The element at index `i` is the number of descendants `i` levels below
+    /// this node, so the first element is always 1, and the length is the
+    /// [`height`] plus one.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use generational_indextree::Arena;
+    /// # let mut arena = Arena::new();
+    /// # let n1 = arena.new_node("1");
+    /// # let n1_1 = arena.new_node("1_1");
+    /// # n1.append(n1_1, &mut arena);
+    /// # let n1_1_1 = arena.new_node("1_1_1");
+    /// # n1_1.append(n1_1_1, &mut arena);
+    /// # let n1_2 = arena.new_node("1_2");
+    /// # n1.append(n1_2, &mut arena);
+    /// # let n1_3 = arena.new_node("1_3");
+    /// # n1.append(n1_3, &mut arena);
+    /// #
+    /// // arena
+    /// // `-- 1
+    /// //     |-- 1_1
+    /// //     |   `-- 1_1_1
+    /// //     |-- 1_2
+    /// //     `-- 1_3
+    ///
+    /// assert_eq!(n1.count_by_depth(&arena), vec![1, 3, 1]);
+    /// ```
+    ///
+    /// [`height`]: struct.NodeId.html#method.height
+    pub fn count_by_depth<T>(self, arena: &Arena<T>) -> Vec<usize> {
+        let mut counts = Vec::new();
+        let mut depth = 0;
+        for edge in self.traverse(arena) {
+            match edge {
+                NodeEdge::Start(_) => {
+                    if counts.len() == depth {
+                        counts.push(0);
+                    }
+                    counts[depth] += 1;
+                    depth += 1;
+                }
+                NodeEdge::End(_) => depth -= 1,
+            }
+        }
+        counts
+    }
+
+    /// Returns an iterator of the levels of the subtree rooted at this node,
+    /// each one being the nodes at the same depth, from left to right.
+    ///
+    /// The first level is `[self]`, and every following one is computed from
+    /// the previous one when the iterator advances, so only one level is held
+    /// at a time.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use generational_indextree::Arena;
+    /// # let mut arena = Arena::new();
+    /// # let n1 = arena.new_node("1");
+    /// # let n1_1 = arena.new_node("1_1");
+    /// # n1.append(n1_1, &mut arena);
+    /// # let n1_1_1 = arena.new_node("1_1_1");
+    /// # n1_1.append(n1_1_1, &mut arena);
+    /// # let n1_2 = arena.new_node("1_2");
+    /// # n1.append(n1_2, &mut arena);
+    /// # let n1_2_1 = arena.new_node("1_2_1");
+    /// # n1_2.append(n1_2_1, &mut arena);
+    /// #
+    /// // arena
+    /// // `-- 1
+    /// //     |-- 1_1
+    /// //     |   `-- 1_1_1
+    /// //     `-- 1_2
+    /// //         `-- 1_2_1
+    ///
+    /// let mut levels = n1.levels(&arena);
+    /// assert_eq!(levels.next(), Some(vec![n1]));
+    /// assert_eq!(levels.next(), Some(vec![n1_1, n1_2]));
+    /// assert_eq!(levels.next(), Some(vec![n1_1_1, n1_2_1]));
+    /// assert_eq!(levels.next(), None);
+    /// ```
+    pub fn levels<T>(self, arena: &Arena<T>) -> impl Iterator<Item = Vec<NodeId>> + '_ {
+        let first = core::iter::once(self).collect::<Vec<_>>();
+        core::iter::successors(Some(first), move |level| {
+            let next = level
+                .iter()
+                .flat_map(|&id| id.children(arena))
+                .collect::<Vec<_>>();
+            if next.is_empty() {
+                None
+            } else {
+                Some(next)
+            }
+        })
+    }
+
+    /// Returns an iterator of the nodes of the subtree rooted at this node,
+    /// level by level from the deepest one up, ending at this node.
+    ///
+    /// Within a level, nodes come from left to right, as with [`levels`].
+    /// All levels are collected up front, so this holds the whole subtree.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use generational_indextree::Arena;
+    /// # let mut arena = Arena::new();
+    /// # let n1 = arena.new_node("1");
+    /// # let n1_1 = arena.new_node("1_1");
+    /// # n1.append(n1_1, &mut arena);
+    /// # let n1_1_1 = arena.new_node("1_1_1");
+    /// # n1_1.append(n1_1_1, &mut arena);
+    /// # let n1_2 = arena.new_node("1_2");
+    /// # n1.append(n1_2, &mut arena);
+    /// # let n1_2_1 = arena.new_node("1_2_1");
+    /// # n1_2.append(n1_2_1, &mut arena);
+    /// #
+    /// // arena
+    /// // `-- 1
+    /// //     |-- 1_1
+    /// //     |   `-- 1_1_1
+    /// //     `-- 1_2
+    /// //         `-- 1_2_1
+    ///
+    /// let order = n1.breadth_first_reverse(&arena).collect::<Vec<_>>();
+    /// assert_eq!(order, vec![n1_1_1, n1_2_1, n1_1, n1_2, n1]);
+    /// ```
+    ///
+    /// [`levels`]: struct.NodeId.html#method.levels
+    pub fn breadth_first_reverse<T>(self, arena: &Arena<T>) -> impl Iterator<Item = NodeId> {
+        let levels = self.levels(arena).collect::<Vec<_>>();
+        levels.into_iter().rev().flatten()
+    }
+
+    /// Returns the depth, relative to this node, and the number of nodes of
+    /// the level of the subtree with the most nodes.
+    ///
+    /// Of several levels with the most nodes, the shallowest is returned. See
+    /// [`count_by_depth`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use generational_indextree::Arena;
+    /// # let mut arena = Arena::new();
+    /// # let n1 = arena.new_node("1");
+    /// # let n1_1 = arena.new_node("1_1");
+    /// # n1.append(n1_1, &mut arena);
+    /// # let n1_1_1 = arena.new_node("1_1_1");
+    /// # n1_1.append(n1_1_1, &mut arena);
+    /// # let n1_1_2 = arena.new_node("1_1_2");
+    /// # n1_1.append(n1_1_2, &mut arena);
+    /// # let n1_2 = arena.new_node("1_2");
+    /// # n1.append(n1_2, &mut arena);
+    /// #
+    /// // arena
+    /// // `-- 1
+    /// //     |-- 1_1
+    /// //     |   |-- 1_1_1
+    /// //     |   `-- 1_1_2
+    /// //     `-- 1_2
+    ///
+    /// assert_eq!(n1.widest_level(&arena), (1, 2));
+    /// assert_eq!(n1_2.widest_level(&arena), (0, 1));
+    /// ```
+    ///
+    /// [`count_by_depth`]: struct.NodeId.html#method.count_by_depth
+    pub fn widest_level<T>(self, arena: &Arena<T>) -> (usize, usize) {
+        self.count_by_depth(arena)
+            .into_iter()
+            .enumerate()
+            .max_by(|(a_depth, a_count), (b_depth, b_count)| {
+                a_count.cmp(b_count).then(b_depth.cmp(a_depth))
+            })
+            .expect("Should never fail: the subtree contains this node")
+    }
+
+    /// Returns the number of edges on the shortest path from this node down
+    /// to a leaf.
+    ///
+    /// A leaf has a depth of 0. The subtree is searched breadth-first, so
+    /// the search stops at the nearest leaf. See also [`height`] for the
+    /// longest path.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use generational_indextree::Arena;
+    /// # let mut arena = Arena::new();
+    /// # let n1 = arena.new_node("1");
+    /// # let n1_1 = arena.new_node("1_1");
+    /// # n1.append(n1_1, &mut arena);
+    /// # let n1_1_1 = arena.new_node("1_1_1");
+    /// # n1_1.append(n1_1_1, &mut arena);
+    /// # let n1_2 = arena.new_node("1_2");
+    /// # n1.append(n1_2, &mut arena);
+    /// #
+    /// // arena
+    /// // `-- 1
+    /// //     |-- 1_1
+    /// //     |   `-- 1_1_1
+    /// //     `-- 1_2
+    ///
+    /// assert_eq!(n1.min_depth_to_leaf(&arena), 1);
+    /// assert_eq!(n1_1.min_depth_to_leaf(&arena), 1);
+    /// assert_eq!(n1_2.min_depth_to_leaf(&arena), 0);
+    /// ```
+    ///
+    /// [`height`]: struct.NodeId.html#method.height
+    pub fn min_depth_to_leaf<T>(self, arena: &Arena<T>) -> usize {
+        let mut queue = VecDeque::new();
+        queue.push_back((self, 0));
+        while let Some((node, depth)) = queue.pop_front() {
+            if arena[node].first_child.is_none() {
+                return depth;
+            }
+            queue.extend(node.children(arena).map(|child| (child, depth + 1)));
+        }
+        unreachable!("Should never fail: every finite subtree has a leaf")
+    }
+
+    /// Returns the leftmost leaf of the subtree rooted at this node.
+    ///
+    /// This follows the first child down until a node without children is
+    /// reached, i.e. it is the first leaf a pre-order traversal would visit.
+    /// A leaf node returns itself.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use generational_indextree::Arena;
+    /// # let mut arena = Arena::new();
+    /// # let n1 = arena.new_node("1");
+    /// # let n1_1 = arena.new_node("1_1");
+    /// # n1.append(n1_1, &mut arena);
+    /// # let n1_1_1 = arena.new_node("1_1_1");
+    /// # n1_1.append(n1_1_1, &mut arena);
+    /// # let n1_2 = arena.new_node("1_2");
+    /// # n1.append(n1_2, &mut arena);
+    /// # let n1_3 = arena.new_node("1_3");
+    /// # n1.append(n1_3, &mut arena);
+    /// #
+    /// // arena
+    /// // `-- 1
+    /// //     |-- 1_1
+    /// //     |   `-- 1_1_1
+    /// //     |-- 1_2
+    /// //     `-- 1_3
+    ///
+    /// assert_eq!(n1.leftmost_leaf(&arena), n1_1_1);
+    /// assert_eq!(n1_2.leftmost_leaf(&arena), n1_2);
+    /// ```
+    pub fn leftmost_leaf<T>(self, arena: &Arena<T>) -> NodeId {
+        let mut node = self;
+        while let Some(first_child) = arena[node].first_child {
+            node = first_child;
+        }
+        node
+    }
+
+    /// Returns the rightmost leaf of the subtree rooted at this node.
+    ///
+    /// This follows the last child down until a node without children is
+    /// reached, i.e. it is the last leaf a pre-order traversal would visit.
+    /// A leaf node returns itself.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use generational_indextree::Arena;
+    /// # let mut arena = Arena::new();
+    /// # let n1 = arena.new_node("1");
+    /// # let n1_1 = arena.new_node("1_1");
+    /// # n1.append(n1_1, &mut arena);
+    /// # let n1_2 = arena.new_node("1_2");
+    /// # n1.append(n1_2, &mut arena);
+    /// # let n1_2_1 = arena.new_node("1_2_1");
+    /// # n1_2.append(n1_2_1, &mut arena);
+    /// #
+    /// // arena
+    /// // `-- 1
+    /// //     |-- 1_1
+    /// //     `-- 1_2
+    /// //         `-- 1_2_1
+    ///
+    /// assert_eq!(n1.rightmost_leaf(&arena), n1_2_1);
+    /// assert_eq!(n1_1.rightmost_leaf(&arena), n1_1);
+    /// ```
+    pub fn rightmost_leaf<T>(self, arena: &Arena<T>) -> NodeId {
+        let mut node = self;
+        while let Some(last_child) = arena[node].last_child {
+            node = last_child;
+        }
+        node
+    }
+
+    /// Returns the ID of the node following this one in a pre-order
+    /// traversal of the whole tree, unless this is the last node of the tree.
+    ///
+    /// This is the first child if there is one, otherwise the next sibling of
+    /// the node or of its nearest ancestor that has one.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use generational_indextree::Arena;
+    /// # let mut arena = Arena::new();
+    /// # let n1 = arena.new_node("1");
+    /// # let n1_1 = arena.new_node("1_1");
+    /// # n1.append(n1_1, &mut arena);
+    /// # let n1_1_1 = arena.new_node("1_1_1");
+    /// # n1_1.append(n1_1_1, &mut arena);
+    /// # let n1_2 = arena.new_node("1_2");
+    /// # n1.append(n1_2, &mut arena);
+    /// #
+    /// // arena
+    /// // `-- 1
+    /// //     |-- 1_1
+    /// //     |   `-- 1_1_1
+    /// //     `-- 1_2
+    ///
+    /// assert_eq!(n1.next_preorder(&arena), Some(n1_1));
+    /// assert_eq!(n1_1.next_preorder(&arena), Some(n1_1_1));
+    /// assert_eq!(n1_1_1.next_preorder(&arena), Some(n1_2));
+    /// assert_eq!(n1_2.next_preorder(&arena), None);
+    /// ```
+    pub fn next_preorder<T>(self, arena: &Arena<T>) -> Option<NodeId> {
+        if let Some(first_child) = arena[self].first_child {
+            return Some(first_child);
+        }
+        self.ancestors(arena).find_map(|ancestor| arena[ancestor].next_sibling)
+    }
+
+    /// Returns the ID of the node preceding this one in a pre-order
+    /// traversal of the whole tree, unless this is the root of the tree.
+    ///
+    /// This is the rightmost leaf of the previous sibling if there is one,
+    /// otherwise the parent.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use generational_indextree::Arena;
+    /// # let mut arena = Arena::new();
+    /// # let n1 = arena.new_node("1");
+    /// # let n1_1 = arena.new_node("1_1");
+    /// # n1.append(n1_1, &mut arena);
+    /// # let n1_1_1 = arena.new_node("1_1_1");
+    /// # n1_1.append(n1_1_1, &mut arena);
+    /// # let n1_2 = arena.new_node("1_2");
+    /// # n1.append(n1_2, &mut arena);
+    /// #
+    /// // arena
+    /// // `-- 1
+    /// //     |-- 1_1
+    /// //     |   `-- 1_1_1
+    /// //     `-- 1_2
+    ///
+    /// assert_eq!(n1_2.prev_preorder(&arena), Some(n1_1_1));
+    /// assert_eq!(n1_1_1.prev_preorder(&arena), Some(n1_1));
+    /// assert_eq!(n1_1.prev_preorder(&arena), Some(n1));
+    /// assert_eq!(n1.prev_preorder(&arena), None);
+    /// ```
+    pub fn prev_preorder<T>(self, arena: &Arena<T>) -> Option<NodeId> {
+        let node = &arena[self];
+        match node.previous_sibling {
+            Some(previous_sibling) => Some(previous_sibling.rightmost_leaf(arena)),
+            None => node.parent,
+        }
+    }
+
+    /// Returns the position of this node in a pre-order traversal of its
+    /// whole tree, i.e. the number of nodes visited before it by
+    /// [`descendants`] called on the root.
+    ///
+    /// This walks the subtrees of all preceding siblings of the node and its
+    /// ancestors, so it costs O(n) in the size of the tree. When the positions
+    /// of many nodes are needed, enumerate `root.descendants(&arena)` once
+    /// instead.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use generational_indextree::Arena;
+    /// # let mut arena = Arena::new();
+    /// # let n1 = arena.new_node("1");
+    /// # let n1_1 = arena.new_node("1_1");
+    /// # n1.append(n1_1, &mut arena);
+    /// # let n1_1_1 = arena.new_node("1_1_1");
+    /// # n1_1.append(n1_1_1, &mut arena);
+    /// # let n1_2 = arena.new_node("1_2");
+    /// # n1.append(n1_2, &mut arena);
+    /// #
+    /// // arena
+    /// // `-- 1
+    /// //     |-- 1_1
+    /// //     |   `-- 1_1_1
+    /// //     `-- 1_2
+    ///
+    /// assert_eq!(n1.preorder_index(&arena), 0);
+    /// assert_eq!(n1_1.preorder_index(&arena), 1);
+    /// assert_eq!(n1_1_1.preorder_index(&arena), 2);
+    /// assert_eq!(n1_2.preorder_index(&arena), 3);
+    /// ```
+    ///
+    /// [`descendants`]: struct.NodeId.html#method.descendants
+    pub fn preorder_index<T>(self, arena: &Arena<T>) -> usize {
+        let mut index = 0;
+        let mut node = self;
+        while let Some(parent) = arena[node].parent {
+            index += node
+                .preceding_siblings(arena)
+                .skip(1)
+                .map(|sibling| sibling.descendants(arena).count())
+                .sum::<usize>();
+            // The parent itself.
+            index += 1;
+            node = parent;
+        }
+        index
+    }
+
+    /// Returns the node at the given position in the preorder of the subtree
+    /// rooted at this node, `0` being the node itself.
+    ///
+    /// This is the inverse of [`preorder_index`] for subtrees. Returns `None`
+    /// if the subtree has fewer nodes.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use generational_indextree::Arena;
+    /// # let mut arena = Arena::new();
+    /// # let n1 = arena.new_node("1");
+    /// # let n1_1 = arena.new_node("1_1");
+    /// # n1.append(n1_1, &mut arena);
+    /// # let n1_1_1 = arena.new_node("1_1_1");
+    /// # n1_1.append(n1_1_1, &mut arena);
+    /// # let n1_2 = arena.new_node("1_2");
+    /// # n1.append(n1_2, &mut arena);
+    /// #
+    /// // arena
+    /// // `-- 1
+    /// //     |-- 1_1
+    /// //     |   `-- 1_1_1
+    /// //     `-- 1_2
+    ///
+    /// assert_eq!(n1.descendant_at(&arena, 0), Some(n1));
+    /// assert_eq!(n1.descendant_at(&arena, 3), Some(n1_2));
+    /// assert_eq!(n1.descendant_at(&arena, 4), None);
+    /// assert_eq!(n1_2.preorder_index(&arena), 3);
+    /// ```
+    ///
+    /// [`preorder_index`]: struct.NodeId.html#method.preorder_index
+    pub fn descendant_at<T>(self, arena: &Arena<T>, index: usize) -> Option<NodeId> {
+        self.descendants(arena).nth(index)
+    }
+
+    /// Returns `true` if the subtree rooted at this node, including the node
+    /// itself, has more than `limit` nodes.
+    ///
+    /// The walk stops as soon as the limit is exceeded, so this costs at most
+    /// `limit + 1` steps however large the subtree is.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use generational_indextree::Arena;
+    /// # let mut arena = Arena::new();
+    /// # let n1 = arena.new_node("1");
+    /// # let n1_1 = arena.new_node("1_1");
+    /// # n1.append(n1_1, &mut arena);
+    /// # let n1_2 = arena.new_node("1_2");
+    /// # n1.append(n1_2, &mut arena);
+    /// #
+    /// // arena
+    /// // `-- 1
+    /// //     |-- 1_1
+    /// //     `-- 1_2
+    ///
+    /// assert!(n1.exceeds_size(&arena, 2));
+    /// assert!(!n1.exceeds_size(&arena, 3));
+    /// assert!(!n1_1.exceeds_size(&arena, 1));
+    /// ```
+    pub fn exceeds_size<T>(self, arena: &Arena<T>, limit: usize) -> bool {
+        self.descendants(arena).nth(limit).is_some()
+    }
+
+    /// Moves this node, with its descendants, one level up: it becomes the
+    /// next sibling of its former parent.
+    ///
+    /// The following siblings of the node stay children of the former parent.
+    ///
+    /// # Failures
+    ///
+    /// Returns an error if the node has been removed or has no parent.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use generational_indextree::Arena;
+    /// # let mut arena = Arena::new();
+    /// # let n1 = arena.new_node("1");
+    /// # let n1_1 = arena.new_node("1_1");
+    /// # n1.append(n1_1, &mut arena);
+    /// # let n1_1_1 = arena.new_node("1_1_1");
+    /// # n1_1.append(n1_1_1, &mut arena);
+    /// # let n1_2 = arena.new_node("1_2");
+    /// # n1.append(n1_2, &mut arena);
+    /// # let n2 = arena.new_node("2");
+    /// # n1.insert_after(n2, &mut arena);
+    /// #
+    /// // arena
+    /// // |-- 1
+    /// // |   |-- 1_1
+    /// // |   |   `-- 1_1_1
+    /// // |   `-- 1_2
+    /// // `-- 2
+    ///
+    /// assert!(n1_1.promote(&mut arena).is_ok());
+    /// // arena
+    /// // |-- 1
+    /// // |   `-- 1_2
+    /// // |-- 1_1
+    /// // |   `-- 1_1_1
+    /// // `-- 2
+    ///
+    /// assert_eq!(n1.following_siblings(&arena).collect::<Vec<_>>(), vec![n1, n1_1, n2]);
+    /// assert_eq!(n1.children(&arena).collect::<Vec<_>>(), vec![n1_2]);
+    /// assert_eq!(n1_1.children(&arena).collect::<Vec<_>>(), vec![n1_1_1]);
+    ///
+    /// assert!(n1.promote(&mut arena).is_err());
+    /// ```
+    pub fn promote<T>(self, arena: &mut Arena<T>) -> Result<(), NodeError> {
+        let parent = arena.get(self).ok_or(NodeError::Removed)?.parent;
+        let parent = parent.ok_or(NodeError::NoParent)?;
+        parent.checked_insert_after(self, arena)
+    }
+
+    /// Moves this node, with its descendants, one level down: it becomes the
+    /// last child of its former previous sibling.
+    ///
+    /// # Failures
+    ///
+    /// Returns an error if the node has been removed or has no previous
+    /// sibling.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use generational_indextree::Arena;
+    /// # let mut arena = Arena::new();
+    /// # let n1 = arena.new_node("1");
+    /// # let n1_1 = arena.new_node("1_1");
+    /// # n1.append(n1_1, &mut arena);
+    /// # let n2 = arena.new_node("2");
+    /// # n1.insert_after(n2, &mut arena);
+    /// # let n2_1 = arena.new_node("2_1");
+    /// # n2.append(n2_1, &mut arena);
+    /// #
+    /// // arena
+    /// // |-- 1
+    /// // |   `-- 1_1
+    /// // `-- 2
+    /// //     `-- 2_1
+    ///
+    /// assert!(n2.demote(&mut arena).is_ok());
+    /// // arena
+    /// // `-- 1
+    /// //     |-- 1_1
+    /// //     `-- 2
+    /// //         `-- 2_1
+    ///
+    /// assert_eq!(n1.children(&arena).collect::<Vec<_>>(), vec![n1_1, n2]);
+    /// assert_eq!(n2.children(&arena).collect::<Vec<_>>(), vec![n2_1]);
+    ///
+    /// assert!(n1_1.demote(&mut arena).is_err());
+    /// ```
+    pub fn demote<T>(self, arena: &mut Arena<T>) -> Result<(), NodeError> {
+        let previous_sibling = arena.get(self).ok_or(NodeError::Removed)?.previous_sibling;
+        let previous_sibling = previous_sibling.ok_or(NodeError::NoPreviousSibling)?;
+        previous_sibling.checked_append(self, arena)
+    }
+
+    /// Swaps this node with its previous sibling.
+    ///
+    /// Returns `false` if the node is already the first sibling.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use generational_indextree::Arena;
+    /// # let mut arena = Arena::new();
+    /// # let n1 = arena.new_node("1");
+    /// # let n1_1 = arena.new_node("1_1");
+    /// # n1.append(n1_1, &mut arena);
+    /// # let n1_2 = arena.new_node("1_2");
+    /// # n1.append(n1_2, &mut arena);
+    /// #
+    /// // arena
+    /// // `-- 1
+    /// //     |-- 1_1
+    /// //     `-- 1_2
+    ///
+    /// assert!(n1_2.move_up(&mut arena));
+    /// assert_eq!(n1.children(&arena).collect::<Vec<_>>(), vec![n1_2, n1_1]);
+    /// assert_eq!(arena[n1].first_child(), Some(n1_2));
+    /// assert_eq!(arena[n1].last_child(), Some(n1_1));
+    ///
+    /// assert!(!n1_2.move_up(&mut arena));
+    /// ```
+    pub fn move_up<T>(self, arena: &mut Arena<T>) -> bool {
+        match arena[self].previous_sibling {
+            Some(previous_sibling) => {
+                previous_sibling.insert_before(self, arena);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Swaps this node with its next sibling.
+    ///
+    /// Returns `false` if the node is already the last sibling.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use generational_indextree::Arena;
+    /// # let mut arena = Arena::new();
+    /// # let n1 = arena.new_node("1");
+    /// # let n1_1 = arena.new_node("1_1");
+    /// # n1.append(n1_1, &mut arena);
+    /// # let n1_2 = arena.new_node("1_2");
+    /// # n1.append(n1_2, &mut arena);
+    /// #
+    /// // arena
+    /// // `-- 1
+    /// //     |-- 1_1
+    /// //     `-- 1_2
+    ///
+    /// assert!(n1_1.move_down(&mut arena));
+    /// assert_eq!(n1.children(&arena).collect::<Vec<_>>(), vec![n1_2, n1_1]);
+    /// assert_eq!(arena[n1].first_child(), Some(n1_2));
+    /// assert_eq!(arena[n1].last_child(), Some(n1_1));
+    ///
+    /// assert!(!n1_1.move_down(&mut arena));
+    /// ```
+    pub fn move_down<T>(self, arena: &mut Arena<T>) -> bool {
+        match arena[self].next_sibling {
+            Some(next_sibling) => {
+                next_sibling.insert_after(self, arena);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Detaches a node from its parent and siblings. Children are not affected.
+    ///
+    /// This pops the node, with its descendants, out to the top level of the
+    /// arena as a new root, and links its former siblings to each other.
+    /// Returns `true` if the node had a parent or siblings which were
+    /// unlinked, and `false` if it was already a detached root.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use generational_indextree::{Arena, NodeEdge};
+    /// # let mut arena = Arena::new();
+    /// # let n1 = arena.new_node("1");
+    /// # let n1_1 = arena.new_node("1_1");
+    /// # n1.append(n1_1, &mut arena);
+    /// # let n1_1_1 = arena.new_node("1_1_1");
+    /// # n1_1.append(n1_1_1, &mut arena);
+    /// # let n1_2 = arena.new_node("1_2");
+    /// # n1.append(n1_2, &mut arena);
+    /// # let n1_3 = arena.new_node("1_3");
+    /// # n1.append(n1_3, &mut arena);
+    /// #
+    /// // arena
+    /// // `-- (implicit)
+    /// //     `-- 1
+    /// //         |-- 1_1
+    /// //         |   `-- 1_1_1
+    /// //         |-- 1_2 *
+    /// //         `-- 1_3
+    ///
+    /// assert!(n1_2.detach(&mut arena));
+    /// // arena
+    /// // |-- (implicit)
+    /// // |   `-- 1
+    /// // |       |-- 1_1
+    /// // |       |   `-- 1_1_1
+    /// // |       `-- 1_3
+    /// // `-- (implicit)
+    /// //     `-- 1_2
+    ///
+    /// assert!(arena[n1_2].parent().is_none());
+    /// assert!(arena[n1_2].previous_sibling().is_none());
+    /// assert!(arena[n1_2].next_sibling().is_none());
+    ///
+    /// let mut iter = n1.descendants(&arena);
+    /// assert_eq!(iter.next(), Some(n1));
+    /// assert_eq!(iter.next(), Some(n1_1));
+    /// assert_eq!(iter.next(), Some(n1_1_1));
+    /// assert_eq!(iter.next(), Some(n1_3));
+    /// assert_eq!(iter.next(), None);
+    ///
+    /// assert!(!n1_2.detach(&mut arena));
+    /// ```
+    pub fn detach<T>(self, arena: &mut Arena<T>) -> bool {
+        let was_attached = !arena[self].is_detached();
+        let range = SiblingsRange::new(self, self).detach_from_siblings(arena);
+        range
+            .rewrite_parents(arena, None)
+            .expect("Should never happen: `None` as parent is always valid");
+
+        // Ensure the node is surely detached.
+        debug_assert!(
+            arena[self].is_detached(),
+            "The node should be successfully detached"
+        );
+        arena.debug_assert_valid();
+        was_attached
+    }
+
+    /// Replaces the children of this node with the given nodes, in order, and
+    /// returns the previous children.
+    ///
+    /// The previous children are detached, keeping their own descendants. The
+    /// new children are detached from wherever they were before being
+    /// attached, so they may include some of the previous children.
+    ///
+    /// # Panics
+    ///
+    /// Panics under the same conditions as [`append`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use generational_indextree::Arena;
+    /// # let mut arena = Arena::new();
+    /// # let n1 = arena.new_node("1");
+    /// # let n1_1 = arena.new_node("1_1");
+    /// # n1.append(n1_1, &mut arena);
+    /// # let n1_2 = arena.new_node("1_2");
+    /// # n1.append(n1_2, &mut arena);
+    /// #
+    /// // arena
+    /// // `-- 1
+    /// //     |-- 1_1
+    /// //     `-- 1_2
+    ///
+    /// let n2 = arena.new_node("2");
+    /// let old = n1.set_children(vec![n2, n1_1], &mut arena);
+    /// // arena
+    /// // |-- 1
+    /// // |   |-- 2
+    /// // |   `-- 1_1
+    /// // `-- 1_2
+    ///
+    /// assert_eq!(old, vec![n1_1, n1_2]);
+    /// assert_eq!(n1.children(&arena).collect::<Vec<_>>(), vec![n2, n1_1]);
+    /// assert!(arena[n1_2].parent().is_none());
+    /// ```
+    ///
+    /// [`append`]: struct.NodeId.html#method.append
+    pub fn set_children<T, I: IntoIterator<Item = NodeId>>(
+        self,
+        new_children: I,
+        arena: &mut Arena<T>,
+    ) -> Vec<NodeId> {
+        let old_children = self.children(arena).collect::<Vec<_>>();
+        // All children go at once, so their links can simply be cleared.
+        for &child in &old_children {
+            let child = &mut arena[child];
+            child.parent = None;
+            child.previous_sibling = None;
+            child.next_sibling = None;
+        }
+        let node = &mut arena[self];
+        node.first_child = None;
+        node.last_child = None;
+
+        for child in new_children {
+            self.append(child, arena);
+        }
+        old_children
+    }
+
+    /// Moves the children in the given index range under a new node with the
+    /// given data, which takes their place among the children of this node.
+    ///
+    /// Returns the ID of the new node. An empty range inserts a new leaf at
+    /// `range.start`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the range is decreasing or its end is greater than the
+    /// number of children.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use generational_indextree::Arena;
+    /// # let mut arena = Arena::new();
+    /// # let n1 = arena.new_node("1");
+    /// # let n1_1 = arena.new_node("1_1");
+    /// # n1.append(n1_1, &mut arena);
+    /// # let n1_2 = arena.new_node("1_2");
+    /// # n1.append(n1_2, &mut arena);
+    /// # let n1_3 = arena.new_node("1_3");
+    /// # n1.append(n1_3, &mut arena);
+    /// # let n1_4 = arena.new_node("1_4");
+    /// # n1.append(n1_4, &mut arena);
+    /// #
+    /// // arena
+    /// // `-- 1
+    /// //     |-- 1_1
+    /// //     |-- 1_2
+    /// //     |-- 1_3
+    /// //     `-- 1_4
+    ///
+    /// let group = n1.wrap_children(1..3, "group", &mut arena);
+    /// // arena
+    /// // `-- 1
+    /// //     |-- 1_1
+    /// //     |-- group
+    /// //     |   |-- 1_2
+    /// //     |   `-- 1_3
+    /// //     `-- 1_4
+    ///
+    /// assert_eq!(n1.children(&arena).collect::<Vec<_>>(), vec![n1_1, group, n1_4]);
+    /// assert_eq!(group.children(&arena).collect::<Vec<_>>(), vec![n1_2, n1_3]);
+    /// ```
+    pub fn wrap_children<T>(
+        self,
+        range: Range<usize>,
+        wrapper_data: T,
+        arena: &mut Arena<T>,
+    ) -> NodeId {
+        let children = self.children(arena).collect::<Vec<_>>();
+        assert!(
+            range.start <= range.end && range.end <= children.len(),
+            "Preconditions not met: invalid range"
+        );
+        let wrapper = arena.new_node(wrapper_data);
+        for &child in &children[range.clone()] {
+            wrapper.append(child, arena);
+        }
+        match children.get(range.end) {
+            Some(&next) => next.insert_before(wrapper, arena),
+            None => self.append(wrapper, arena),
+        }
+        wrapper
+    }
+
+    /// Detaches the children in the given index range from this node, and
+    /// returns their IDs in order.
+    ///
+    /// The detached children become roots, with their descendants intact, and
+    /// the remaining children are linked together. The range is clamped to
+    /// the number of children, so it may extend past the last child, and a
+    /// decreasing range detaches nothing.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use generational_indextree::Arena;
+    /// # let mut arena = Arena::new();
+    /// # let n1 = arena.new_node("1");
+    /// # let n1_1 = arena.new_node("1_1");
+    /// # n1.append(n1_1, &mut arena);
+    /// # let n1_2 = arena.new_node("1_2");
+    /// # n1.append(n1_2, &mut arena);
+    /// # let n1_2_1 = arena.new_node("1_2_1");
+    /// # n1_2.append(n1_2_1, &mut arena);
+    /// # let n1_3 = arena.new_node("1_3");
+    /// # n1.append(n1_3, &mut arena);
+    /// # let n1_4 = arena.new_node("1_4");
+    /// # n1.append(n1_4, &mut arena);
+    /// #
+    /// // arena
+    /// // `-- 1
+    /// //     |-- 1_1
+    /// //     |-- 1_2
+    /// //     |   `-- 1_2_1
+    /// //     |-- 1_3
+    /// //     `-- 1_4
+    ///
+    /// assert_eq!(n1.detach_children_range(1..3, &mut arena), vec![n1_2, n1_3]);
+    /// // arena
+    /// // |-- 1
+    /// // |   |-- 1_1
+    /// // |   `-- 1_4
+    /// // |-- 1_2
+    /// // |   `-- 1_2_1
+    /// // `-- 1_3
+    ///
+    /// assert_eq!(n1.children(&arena).collect::<Vec<_>>(), vec![n1_1, n1_4]);
+    /// assert_eq!(arena[n1_1].next_sibling(), Some(n1_4));
+    /// assert_eq!(n1_2.children(&arena).collect::<Vec<_>>(), vec![n1_2_1]);
+    /// assert!(arena[n1_3].parent().is_none());
+    ///
+    /// assert_eq!(n1.detach_children_range(1..10, &mut arena), vec![n1_4]);
+    /// assert_eq!(arena[n1].last_child(), Some(n1_1));
+    /// ```
+    pub fn detach_children_range<T>(
+        self,
+        range: Range<usize>,
+        arena: &mut Arena<T>,
+    ) -> Vec<NodeId> {
+        let detached = self
+            .children(arena)
+            .skip(range.start)
+            .take(range.end.saturating_sub(range.start))
+            .collect::<Vec<_>>();
+        for &child in &detached {
+            child.detach(arena);
+        }
+        detached
+    }
+
+    /// Detaches all children of this node, and returns their IDs in order.
+    ///
+    /// The children become roots, with their descendants intact, and this
+    /// node is left without children. This is
+    /// [`detach_children_range`] over all the children.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use generational_indextree::Arena;
+    /// # let mut arena = Arena::new();
+    /// # let n1 = arena.new_node("1");
+    /// # let n1_1 = arena.new_node("1_1");
+    /// # n1.append(n1_1, &mut arena);
+    /// # let n1_1_1 = arena.new_node("1_1_1");
+    /// # n1_1.append(n1_1_1, &mut arena);
+    /// # let n1_2 = arena.new_node("1_2");
+    /// # n1.append(n1_2, &mut arena);
+    /// #
+    /// // arena
+    /// // `-- 1
+    /// //     |-- 1_1
+    /// //     |   `-- 1_1_1
+    /// //     `-- 1_2
+    ///
+    /// assert_eq!(n1.take_children(&mut arena), vec![n1_1, n1_2]);
+    /// assert!(arena[n1].first_child().is_none());
+    /// assert!(arena[n1].last_child().is_none());
+    /// assert!(arena[n1_2].parent().is_none());
+    /// assert_eq!(n1_1.children(&arena).collect::<Vec<_>>(), vec![n1_1_1]);
+    /// ```
+    ///
+    /// [`detach_children_range`]: struct.NodeId.html#method.detach_children_range
+    pub fn take_children<T>(self, arena: &mut Arena<T>) -> Vec<NodeId> {
+        self.detach_children_range(0..usize::MAX, arena)
+    }
+
+    /// Inserts the given nodes as children of this node, in order, starting
+    /// at position `index` among the children.
+    ///
+    /// The children from `index` on are shifted after the inserted nodes. An
+    /// `index` greater than or equal to the number of children appends the
+    /// nodes at the end. This is the counterpart of
+    /// [`detach_children_range`].
+    ///
+    /// The given nodes are detached first, so they may already be children
+    /// of this node, in which case they are moved: `index` then counts the
+    /// children which are left once they are taken out.
+    ///
+    /// # Panics
+    ///
+    /// Panics under the same conditions as [`append`], for each of the nodes,
+    /// e.g. if one of them is this node or one of its ancestors.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use generational_indextree::Arena;
+    /// # let mut arena = Arena::new();
+    /// # let n1 = arena.new_node("1");
+    /// # let n1_1 = arena.new_node("1_1");
+    /// # n1.append(n1_1, &mut arena);
+    /// # let n1_2 = arena.new_node("1_2");
+    /// # n1.append(n1_2, &mut arena);
+    /// # let n1_3 = arena.new_node("1_3");
+    /// # n1.append(n1_3, &mut arena);
+    /// #
+    /// // arena
+    /// // `-- 1
+    /// //     |-- 1_1
+    /// //     |-- 1_2
+    /// //     `-- 1_3
+    ///
+    /// let cut = n1.detach_children_range(0..2, &mut arena);
+    /// n1.insert_children_at(1, cut, &mut arena);
+    /// // arena
+    /// // `-- 1
+    /// //     |-- 1_3
+    /// //     |-- 1_1
+    /// //     `-- 1_2
+    ///
+    /// assert_eq!(n1.children(&arena).collect::<Vec<_>>(), vec![n1_3, n1_1, n1_2]);
+    /// assert_eq!(arena[n1].last_child(), Some(n1_2));
+    ///
+    /// let n1_4 = arena.new_node("1_4");
+    /// n1.insert_children_at(10, vec![n1_4], &mut arena);
+    /// assert_eq!(arena[n1].last_child(), Some(n1_4));
+    /// ```
+    ///
+    /// [`detach_children_range`]: struct.NodeId.html#method.detach_children_range
+    /// [`append`]: struct.NodeId.html#method.append
+    pub fn insert_children_at<T, I: IntoIterator<Item = NodeId>>(
+        self,
+        index: usize,
+        children: I,
+        arena: &mut Arena<T>,
+    ) {
+        let children = children.into_iter().collect::<Vec<_>>();
+        for &child in &children {
+            child.detach(arena);
+        }
+        // Only now the anchor is known not to be one of the nodes.
+        let next = self.children(arena).nth(index);
+        for child in children {
+            match next {
+                Some(next) => next.insert_before(child, arena),
+                None => self.append(child, arena),
+            }
+        }
+    }
+
+    /// Wraps this node in a new node with the given data, and returns the ID
+    /// of the new node.
+    ///
+    /// The new node takes the place of this node among its siblings, and this
+    /// node, with its descendants, becomes its only child. If this node is a
+    /// root, the new node becomes the root.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the node was already [`remove`]d.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use generational_indextree::Arena;
+    /// # let mut arena = Arena::new();
+    /// # let n1 = arena.new_node("1");
+    /// # let n1_1 = arena.new_node("1_1");
+    /// # n1.append(n1_1, &mut arena);
+    /// # let n1_2 = arena.new_node("1_2");
+    /// # n1.append(n1_2, &mut arena);
+    /// # let n1_3 = arena.new_node("1_3");
+    /// # n1.append(n1_3, &mut arena);
+    /// #
+    /// // arena
+    /// // `-- 1
+    /// //     |-- 1_1
+    /// //     |-- 1_2
+    /// //     `-- 1_3
+    ///
+    /// let div = n1_2.graft_parent("div", &mut arena);
+    /// // arena
+    /// // `-- 1
+    /// //     |-- 1_1
+    /// //     |-- div
+    /// //     |   `-- 1_2
+    /// //     `-- 1_3
+    ///
+    /// assert_eq!(n1.children(&arena).collect::<Vec<_>>(), vec![n1_1, div, n1_3]);
+    /// assert_eq!(div.children(&arena).collect::<Vec<_>>(), vec![n1_2]);
+    ///
+    /// let root = n1.graft_parent("root", &mut arena);
+    /// assert_eq!(arena[root].parent(), None);
+    /// assert_eq!(arena[n1].parent(), Some(root));
+    /// ```
+    ///
+    /// [`remove`]: struct.NodeId.html#method.remove
+    pub fn graft_parent<T>(self, parent_data: T, arena: &mut Arena<T>) -> NodeId {
+        assert!(
+            arena.nodes.contains(self.get_index()),
+            "Preconditions not met: invalid argument"
+        );
+        let parent = arena.new_node(parent_data);
+        self.insert_before(parent, arena);
+        parent.append(self, arena);
+        parent
+    }
+
+    /// Removes all children of this node after the first `k`, with their
+    /// descendants, from the arena.
+    ///
+    /// Returns the IDs of the removed children, which are no longer valid.
+    /// Nothing happens if the node has at most `k` children.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use generational_indextree::Arena;
+    /// # let mut arena = Arena::new();
+    /// # let n1 = arena.new_node("1");
+    /// # let n1_1 = arena.new_node("1_1");
+    /// # n1.append(n1_1, &mut arena);
+    /// # let n1_2 = arena.new_node("1_2");
+    /// # n1.append(n1_2, &mut arena);
+    /// # let n1_2_1 = arena.new_node("1_2_1");
+    /// # n1_2.append(n1_2_1, &mut arena);
+    /// # let n1_3 = arena.new_node("1_3");
+    /// # n1.append(n1_3, &mut arena);
+    /// #
+    /// // arena
+    /// // `-- 1
+    /// //     |-- 1_1
+    /// //     |-- 1_2
+    /// //     |   `-- 1_2_1
+    /// //     `-- 1_3
+    ///
+    /// assert_eq!(n1.truncate_children(&mut arena, 1), vec![n1_2, n1_3]);
+    /// assert_eq!(n1.children(&arena).collect::<Vec<_>>(), vec![n1_1]);
+    /// assert_eq!(arena[n1].last_child(), Some(n1_1));
+    /// assert_eq!(arena.count(), 2);
+    ///
+    /// assert!(n1.truncate_children(&mut arena, 5).is_empty());
+    /// ```
+    pub fn truncate_children<T>(self, arena: &mut Arena<T>, k: usize) -> Vec<NodeId> {
+        let removed = self.children(arena).skip(k).collect::<Vec<_>>();
+        for &child in &removed {
+            child.remove_subtree(arena);
+        }
+        removed
+    }
+
+    /// Removes every child of this node whose data equals the one of the
+    /// child before it, along with its whole subtree, and returns the number
+    /// of removed children.
+    ///
+    /// Like `Vec::dedup`, only consecutive duplicates are removed, and the
+    /// first one of every run is kept. Only the data of the children is
+    /// compared, not their subtrees.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use generational_indextree::Arena;
+    /// let mut arena = Arena::new();
+    /// let root = arena.new_node("root");
+    /// for data in &["a", "a", "b", "a", "a", "a"] {
+    ///     let child = arena.new_node(*data);
+    ///     child.append(arena.new_node("leaf"), &mut arena);
+    ///     root.append(child, &mut arena);
+    /// }
+    ///
+    /// assert_eq!(root.dedup_children(&mut arena), 3);
+    /// assert_eq!(root.children_data(&arena).collect::<Vec<_>>(), vec![&"a", &"b", &"a"]);
+    /// assert_eq!(arena.count(), 7);
+    /// ```
+    pub fn dedup_children<T: PartialEq>(self, arena: &mut Arena<T>) -> usize {
+        let children = self.children(arena).collect::<Vec<_>>();
+        let mut removed = 0;
+        let mut kept = match children.first() {
+            Some(&first) => first,
+            None => return 0,
+        };
+        for &child in &children[1..] {
+            if arena[child].data == arena[kept].data {
+                child.remove_subtree(arena);
+                removed += 1;
+            } else {
+                kept = child;
+            }
+        }
+        removed
+    }
+
+    /// Restructures the children of this node into a balanced binary tree:
+    /// the children become the leaves, in order, below new inner nodes whose
+    /// data is produced by `inner_data`.
+    ///
+    /// Every inner node, and this node, then has exactly two children, and
+    /// each of them holds half of the original children, with the extra
+    /// one on the left. The original children end up `ceil(log2(n))` levels
+    /// below this node for `n` children, and `n - 2` inner nodes are created.
+    /// Nothing happens if the node has at most two children.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use generational_indextree::Arena;
+    /// let mut arena = Arena::new();
+    /// let root = arena.new_node("root");
+    /// let children = ["a", "b", "c", "d", "e"]
+    ///     .iter()
+    ///     .map(|data| arena.new_node(*data))
+    ///     .collect::<Vec<_>>();
+    /// for &child in &children {
+    ///     root.append(child, &mut arena);
+    /// }
+    ///
+    /// root.binarize(|| "+", &mut arena);
+    /// // arena
+    /// // `-- root
+    /// //     |-- +
+    /// //     |   |-- +
+    /// //     |   |   |-- a
+    /// //     |   |   `-- b
+    /// //     |   `-- c
+    /// //     `-- +
+    /// //         |-- d
+    /// //         `-- e
+    ///
+    /// let leaves = root
+    ///     .descendants(&arena)
+    ///     .filter(|&id| arena[id].first_child().is_none())
+    ///     .collect::<Vec<_>>();
+    /// assert_eq!(leaves, children);
+    /// assert_eq!(root.height(&arena), 3);
+    /// assert_eq!(arena.count(), 9);
+    /// ```
+    // `usize::div_ceil` needs Rust 1.73.
+    #[allow(clippy::manual_div_ceil)]
+    pub fn binarize<T, F: FnMut() -> T>(self, mut inner_data: F, arena: &mut Arena<T>) {
+        if self.children(arena).nth(2).is_none() {
+            return;
+        }
+        let children = self.take_children(arena);
+        let (left, right) = children.split_at((children.len() + 1) / 2);
+        let left = NodeId::binarize_range(left, &mut inner_data, arena);
+        let right = NodeId::binarize_range(right, &mut inner_data, arena);
+        self.append(left, arena);
+        self.append(right, arena);
+    }
+
+    /// Returns the root of a balanced binary tree of new inner nodes over the
+    /// given detached nodes, or the node itself if there is only one.
+    #[allow(clippy::manual_div_ceil)]
+    fn binarize_range<T, F: FnMut() -> T>(
+        nodes: &[NodeId],
+        inner_data: &mut F,
+        arena: &mut Arena<T>,
+    ) -> NodeId {
+        if let [node] = *nodes {
+            return node;
+        }
+        let (left, right) = nodes.split_at((nodes.len() + 1) / 2);
+        let inner = arena.new_node(inner_data());
+        let left = NodeId::binarize_range(left, inner_data, arena);
+        let right = NodeId::binarize_range(right, inner_data, arena);
+        inner.append(left, arena);
+        inner.append(right, arena);
+        inner
+    }
+
+    /// Removes all descendants of this node more than `max_depth` levels
+    /// below it from the arena, and returns the number of removed nodes.
+    ///
+    /// With a `max_depth` of 0, only this node is left.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use generational_indextree::Arena;
+    /// # let mut arena = Arena::new();
+    /// # let n1 = arena.new_node("1");
+    /// # let n1_1 = arena.new_node("1_1");
+    /// # n1.append(n1_1, &mut arena);
+    /// # let n1_1_1 = arena.new_node("1_1_1");
+    /// # n1_1.append(n1_1_1, &mut arena);
+    /// # let n1_1_1_1 = arena.new_node("1_1_1_1");
+    /// # n1_1_1.append(n1_1_1_1, &mut arena);
+    /// # let n1_2 = arena.new_node("1_2");
+    /// # n1.append(n1_2, &mut arena);
+    /// #
+    /// // arena
+    /// // `-- 1
+    /// //     |-- 1_1
+    /// //     |   `-- 1_1_1
+    /// //     |       `-- 1_1_1_1
+    /// //     `-- 1_2
+    ///
+    /// assert_eq!(n1.prune_to_depth(&mut arena, 1), 2);
+    /// // arena
+    /// // `-- 1
+    /// //     |-- 1_1
+    /// //     `-- 1_2
+    ///
+    /// assert_eq!(n1.height(&arena), 1);
+    /// assert!(arena.get(n1_1_1).is_none());
+    /// assert_eq!(n1.prune_to_depth(&mut arena, 0), 2);
+    /// assert_eq!(arena.count(), 1);
+    /// ```
+    pub fn prune_to_depth<T>(self, arena: &mut Arena<T>, max_depth: usize) -> usize {
+        let count = arena.count();
+        let deepest = self.levels(arena).nth(max_depth).unwrap_or_default();
+        for id in deepest {
+            id.truncate_children(arena, 0);
+        }
+        count - arena.count()
+    }
+
+    /// Splits the children of this node at `index` into two groups, each
+    /// moved under a new node, which become the only two children of this
+    /// node.
+    ///
+    /// The children before `index` go under a new node with `left_data`, the
+    /// others under one with `right_data`. Returns the IDs of the two new
+    /// nodes. See [`wrap_children`].
+    ///
+    /// # Panics
+    ///
+    /// Panics if `index` is greater than the number of children.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use generational_indextree::Arena;
+    /// # let mut arena = Arena::new();
+    /// # let n1 = arena.new_node("1");
+    /// # let n1_1 = arena.new_node("1_1");
+    /// # n1.append(n1_1, &mut arena);
+    /// # let n1_2 = arena.new_node("1_2");
+    /// # n1.append(n1_2, &mut arena);
+    /// # let n1_3 = arena.new_node("1_3");
+    /// # n1.append(n1_3, &mut arena);
+    /// #
+    /// // arena
+    /// // `-- 1
+    /// //     |-- 1_1
+    /// //     |-- 1_2
+    /// //     `-- 1_3
+    ///
+    /// let (left, right) = n1.split_children_at(1, "left", "right", &mut arena);
+    /// // arena
+    /// // `-- 1
+    /// //     |-- left
+    /// //     |   `-- 1_1
+    /// //     `-- right
+    /// //         |-- 1_2
+    /// //         `-- 1_3
+    ///
+    /// assert_eq!(n1.children(&arena).collect::<Vec<_>>(), vec![left, right]);
+    /// assert_eq!(left.children(&arena).collect::<Vec<_>>(), vec![n1_1]);
+    /// assert_eq!(right.children(&arena).collect::<Vec<_>>(), vec![n1_2, n1_3]);
+    /// ```
+    ///
+    /// [`wrap_children`]: struct.NodeId.html#method.wrap_children
+    pub fn split_children_at<T>(
+        self,
+        index: usize,
+        left_data: T,
+        right_data: T,
+        arena: &mut Arena<T>,
+    ) -> (NodeId, NodeId) {
+        let count = self.children(arena).count();
+        assert!(index <= count, "Preconditions not met: invalid index");
+        let left = self.wrap_children(0..index, left_data, arena);
+        let right = self.wrap_children(1..count - index + 1, right_data, arena);
+        (left, right)
+    }
+
+    /// Merges adjacent children of this node where `merge` accepts it.
+    ///
+    /// The children are walked from left to right, and `merge` is called with
+    /// the data of each child and a reference to the data of its next
+    /// sibling. If it returns `true`, the sibling is considered merged into
+    /// the child: its children are appended to the child, and it is removed
+    /// from the arena. The child is then compared to its new next sibling.
+    /// Otherwise the walk advances to the sibling.
+    ///
+    /// The next sibling's data is only borrowed, so that declining a merge
+    /// leaves both nodes and their IDs untouched.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use generational_indextree::Arena;
+    /// let mut arena = Arena::new();
+    /// let root = arena.new_node(String::new());
+    /// for text in &["a", "b", "", "c"] {
+    ///     root.append(arena.new_node(text.to_string()), &mut arena);
+    /// }
+    /// let image = arena.new_node("#".to_string());
+    /// root.append(image, &mut arena);
+    /// root.append(arena.new_node("d".to_string()), &mut arena);
+    ///
+    /// root.merge_adjacent_children(&mut arena, |left, right| {
+    ///     if left == "#" || right == "#" {
+    ///         return false;
+    ///     }
+    ///     left.push_str(right);
+    ///     true
+    /// });
+    ///
+    /// let texts = root.children_data(&arena).collect::<Vec<_>>();
+    /// assert_eq!(texts, vec!["abc", "#", "d"]);
+    /// assert_eq!(arena.count(), 4);
+    /// ```
+    pub fn merge_adjacent_children<T, F: FnMut(&mut T, &T) -> bool>(
+        self,
+        arena: &mut Arena<T>,
+        mut merge: F,
+    ) {
+        let mut current = arena[self].first_child;
+        while let Some(left) = current {
+            let right = match arena[left].next_sibling {
+                Some(right) => right,
+                None => break,
+            };
+            let merged = match arena.nodes.get2_mut(left.get_index(), right.get_index()) {
+                (Some(left), Some(right)) => merge(&mut left.data, &right.data),
+                _ => unreachable!("Should never fail: siblings of a live node are live"),
+            };
+            if merged {
+                for child in right.children(arena).collect::<Vec<_>>() {
+                    left.append(child, arena);
+                }
+                right.remove(arena);
+            } else {
+                current = Some(right);
+            }
+        }
+    }
+
+    /// Moves every descendant of this node up to be a direct child of it,
+    /// leaving the subtree one level deep.
+    ///
+    /// The children end up in the previous preorder of the subtree, so
+    /// iterating over them visits the nodes in the same order as
+    /// [`descendants`] did before.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use generational_indextree::Arena;
+    /// # let mut arena = Arena::new();
+    /// # let n1 = arena.new_node("1");
+    /// # let n1_1 = arena.new_node("1_1");
+    /// # n1.append(n1_1, &mut arena);
+    /// # let n1_1_1 = arena.new_node("1_1_1");
+    /// # n1_1.append(n1_1_1, &mut arena);
+    /// # let n1_1_1_1 = arena.new_node("1_1_1_1");
+    /// # n1_1_1.append(n1_1_1_1, &mut arena);
+    /// # let n1_2 = arena.new_node("1_2");
+    /// # n1.append(n1_2, &mut arena);
+    /// #
+    /// // arena
+    /// // `-- 1
+    /// //     |-- 1_1
+    /// //     |   `-- 1_1_1
+    /// //     |       `-- 1_1_1_1
+    /// //     `-- 1_2
+    ///
+    /// n1.flatten(&mut arena);
+    /// // arena
+    /// // `-- 1
+    /// //     |-- 1_1
+    /// //     |-- 1_1_1
+    /// //     |-- 1_1_1_1
+    /// //     `-- 1_2
+    ///
+    /// assert_eq!(
+    ///     n1.children(&arena).collect::<Vec<_>>(),
+    ///     vec![n1_1, n1_1_1, n1_1_1_1, n1_2]
+    /// );
+    /// assert_eq!(n1.height(&arena), 1);
+    /// ```
+    ///
+    /// [`descendants`]: struct.NodeId.html#method.descendants
+    pub fn flatten<T>(self, arena: &mut Arena<T>) {
+        let descendants = self.descendants(arena).skip(1).collect::<Vec<_>>();
+        for id in descendants {
+            self.append(id, arena);
+        }
+    }
+
+    /// Cyclically shifts the children of this node by `by` positions.
+    ///
+    /// A positive shift rotates to the left, i.e. the child at index `by`
+    /// becomes the first child; a negative one rotates to the right. The shift
+    /// is taken modulo the number of children, and nodes with less than two
+    /// children are not affected.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use generational_indextree::Arena;
+    /// # let mut arena = Arena::new();
+    /// # let n1 = arena.new_node("1");
+    /// # let n1_1 = arena.new_node("1_1");
+    /// # n1.append(n1_1, &mut arena);
+    /// # let n1_2 = arena.new_node("1_2");
+    /// # n1.append(n1_2, &mut arena);
+    /// # let n1_3 = arena.new_node("1_3");
+    /// # n1.append(n1_3, &mut arena);
+    /// #
+    /// // arena
+    /// // `-- 1
+    /// //     |-- 1_1
+    /// //     |-- 1_2
+    /// //     `-- 1_3
+    ///
+    /// n1.rotate_children(&mut arena, 1);
+    /// assert_eq!(n1.children(&arena).collect::<Vec<_>>(), vec![n1_2, n1_3, n1_1]);
+    ///
+    /// n1.rotate_children(&mut arena, -4);
+    /// assert_eq!(n1.children(&arena).collect::<Vec<_>>(), vec![n1_1, n1_2, n1_3]);
+    /// assert_eq!(arena[n1].first_child(), Some(n1_1));
+    /// assert_eq!(arena[n1].last_child(), Some(n1_3));
+    /// ```
+    pub fn rotate_children<T>(self, arena: &mut Arena<T>, by: isize) {
+        let count = self.children(arena).count();
+        if count < 2 {
+            return;
+        }
+        let shift = by.rem_euclid(count as isize) as usize;
+        if shift == 0 {
+            return;
+        }
+
+        let (first, last) = match (arena[self].first_child, arena[self].last_child) {
+            (Some(first), Some(last)) => (first, last),
+            _ => unreachable!("Should never fail: the node has children"),
+        };
+        let new_first = self
+            .children(arena)
+            .nth(shift)
+            .expect("Should never fail: the shift is less than the number of children");
+        let new_last = arena[new_first]
+            .previous_sibling
+            .expect("Should never fail: the new first child is not the first child");
+
+        // Close the sibling chain into a ring, then cut it before the new
+        // first child.
+        arena[last].next_sibling = Some(first);
+        arena[first].previous_sibling = Some(last);
+        arena[new_last].next_sibling = None;
+        arena[new_first].previous_sibling = None;
+        let node = &mut arena[self];
+        node.first_child = Some(new_first);
+        node.last_child = Some(new_last);
+    }
+
+    /// Inserts a child into the children of this node, keeping them sorted
+    /// by `cmp`.
+    ///
+    /// The children are assumed to be sorted by `cmp` already. The new child
+    /// is inserted after the existing children comparing equal to it.
+    ///
+    /// # Panics
+    ///
+    /// Panics under the same conditions as [`append`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use generational_indextree::Arena;
+    /// let mut arena = Arena::new();
+    /// let root = arena.new_node(0);
+    /// for data in &[3, 1, 2, 1] {
+    ///     let child = arena.new_node(*data);
+    ///     root.insert_child_sorted(child, &mut arena, |a, b| a.cmp(b));
+    /// }
+    ///
+    /// assert_eq!(root.children_data(&arena).collect::<Vec<_>>(), vec![&1, &1, &2, &3]);
+    /// ```
+    ///
+    /// [`append`]: struct.NodeId.html#method.append
+    pub fn insert_child_sorted<T, F: Fn(&T, &T) -> Ordering>(
+        self,
+        child: NodeId,
+        arena: &mut Arena<T>,
+        cmp: F,
+    ) {
+        let next = self.children(arena).find(|&sibling| {
+            sibling != child && cmp(&arena[sibling].data, &arena[child].data) == Ordering::Greater
+        });
+        match next {
+            Some(next) => next.insert_before(child, arena),
+            None => self.append(child, arena),
+        }
+    }
+
+    /// Returns `true` if the children of this node are sorted by `cmp`, i.e.
+    /// no child compares greater than the next one.
+    ///
+    /// This is the invariant kept by [`insert_child_sorted`], and checks it
+    /// in a single pass over the children.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use generational_indextree::Arena;
+    /// let mut arena = Arena::new();
+    /// let root = arena.new_node(0);
+    /// for data in &[1, 2, 2] {
+    ///     root.append(arena.new_node(*data), &mut arena);
+    /// }
+    /// assert!(root.children_sorted_by(&arena, |a, b| a.cmp(b)));
+    ///
+    /// root.prepend(arena.new_node(3), &mut arena);
+    /// assert!(!root.children_sorted_by(&arena, |a, b| a.cmp(b)));
+    /// ```
+    ///
+    /// [`insert_child_sorted`]: struct.NodeId.html#method.insert_child_sorted
+    pub fn children_sorted_by<T, F: Fn(&T, &T) -> Ordering>(
+        self,
+        arena: &Arena<T>,
+        cmp: F,
+    ) -> bool {
+        self.children(arena)
+            .zip(self.children(arena).skip(1))
+            .all(|(a, b)| cmp(&arena[a].data, &arena[b].data) != Ordering::Greater)
+    }
+
+    /// Creates the children of this node on demand: if it has no children
+    /// yet, `expand` is called with its data, and a child is appended for
+    /// every returned item, in order.
+    ///
+    /// Returns `true` if `expand` was called. Nothing happens if the node
+    /// already has children, so this can be called every time the node is
+    /// visited. A node for which `expand` returned nothing stays a leaf, and
+    /// is expanded again on the next call.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the node was already [`remove`]d, or under the same
+    /// conditions as [`Arena::new_node`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use generational_indextree::Arena;
+    /// let mut arena = Arena::new();
+    /// let root = arena.new_node(String::from("/"));
+    /// let list = |dir: &String| vec![format!("{}a/", dir), format!("{}b/", dir)];
+    ///
+    /// assert!(root.ensure_children(&mut arena, list));
+    /// assert!(!root.ensure_children(&mut arena, |_| unreachable!()));
+    /// assert_eq!(root.children_data(&arena).collect::<Vec<_>>(), vec!["/a/", "/b/"]);
+    /// ```
+    ///
+    /// [`remove`]: struct.NodeId.html#method.remove
+    /// [`Arena::new_node`]: struct.Arena.html#method.new_node
+    pub fn ensure_children<T, F: FnMut(&T) -> Vec<T>>(
+        self,
+        arena: &mut Arena<T>,
+        mut expand: F,
+    ) -> bool {
+        if arena[self].first_child.is_some() {
+            return false;
+        }
+        for data in expand(&arena[self].data) {
+            let child = arena.new_node(data);
+            self.append(child, arena);
+        }
+        true
+    }
+
+    /// Appends a new child to this node, after existing children.
+    ///
+    /// # Panics
+    ///
+    /// Panics if:
+    ///
+    /// * the given new child is `self`
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use generational_indextree::Arena;
+    /// let mut arena = Arena::new();
+    /// let n1 = arena.new_node("1");
+    /// let n1_1 = arena.new_node("1_1");
+    /// n1.append(n1_1, &mut arena);
+    /// let n1_2 = arena.new_node("1_2");
+    /// n1.append(n1_2, &mut arena);
+    /// let n1_3 = arena.new_node("1_3");
+    /// n1.append(n1_3, &mut arena);
+    ///
+    /// // arena
+    /// // `-- 1
+    /// //     |-- 1_1
+    /// //     |-- 1_2
+    /// //     `-- 1_3
+    ///
+    /// let mut iter = n1.descendants(&arena);
+    /// assert_eq!(iter.next(), Some(n1));
+    /// assert_eq!(iter.next(), Some(n1_1));
+    /// assert_eq!(iter.next(), Some(n1_2));
+    /// assert_eq!(iter.next(), Some(n1_3));
+    /// assert_eq!(iter.next(), None);
+    /// ```
+    ///
+    /// [`Node::is_removed()`]: struct.Node.html#method.is_removed
+    /// [`remove`]: struct.NodeId.html#method.remove
+    pub fn append<T>(self, new_child: NodeId, arena: &mut Arena<T>) {
+        self.checked_append(new_child, arena)
+            .expect("Preconditions not met: invalid argument");
+    }
+
+    /// Appends a new child to this node, after existing children.
+    ///
+    /// # Failures
+    ///
+    /// * Returns [`NodeError::AppendSelf`] error if the given new child is
+    ///   `self`.
+    /// * Returns [`NodeError::Removed`] error if the given new child or `self`
+    ///   is [`remove`]d.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use generational_indextree::Arena;
+    /// let mut arena = Arena::new();
+    /// let n1 = arena.new_node("1");
+    /// assert!(n1.checked_append(n1, &mut arena).is_err());
+    ///
+    /// let n1_1 = arena.new_node("1_1");
+    /// assert!(n1.checked_append(n1_1, &mut arena).is_ok());
+    /// ```
+    ///
+    /// [`Node::is_removed()`]: struct.Node.html#method.is_removed
+    /// [`NodeError::AppendSelf`]: enum.NodeError.html#variant.AppendSelf
+    /// [`NodeError::Removed`]: enum.NodeError.html#variant.Removed
+    /// [`remove`]: struct.NodeId.html#method.remove
+    pub fn checked_append<T>(
+        self,
+        new_child: NodeId,
+        arena: &mut Arena<T>,
+    ) -> Result<(), NodeError> {
+        if new_child == self {
+            return Err(NodeError::AppendSelf);
+        }
+        if !arena.nodes.contains(self.get_index()) || !arena.nodes.contains(new_child.get_index()) {
+            // if arena[self].is_removed() || arena[new_child].is_removed() {
+            return Err(NodeError::Removed);
+        }
+        new_child.detach(arena);
+        insert_with_neighbors(arena, new_child, Some(self), arena[self].last_child, None)
+            .expect("Should never fail: `new_child` is not `self` and they are not removed");
+        arena.debug_assert_valid();
+
+        Ok(())
+    }
+
+    /// Prepends a new child to this node, before existing children.
+    ///
+    /// # Panics
+    ///
+    /// Panics if:
+    ///
+    /// * the given new child is `self`, or
+    /// * the current node or the given new child was already [`remove`]d.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use generational_indextree::Arena;
+    /// let mut arena = Arena::new();
+    /// let n1 = arena.new_node("1");
+    /// let n1_1 = arena.new_node("1_1");
+    /// n1.prepend(n1_1, &mut arena);
+    /// let n1_2 = arena.new_node("1_2");
+    /// n1.prepend(n1_2, &mut arena);
+    /// let n1_3 = arena.new_node("1_3");
+    /// n1.prepend(n1_3, &mut arena);
+    ///
+    /// // arena
+    /// // `-- 1
+    /// //     |-- 1_3
+    /// //     |-- 1_2
+    /// //     `-- 1_1
+    ///
+    /// let mut iter = n1.descendants(&arena);
+    /// assert_eq!(iter.next(), Some(n1));
+    /// assert_eq!(iter.next(), Some(n1_3));
+    /// assert_eq!(iter.next(), Some(n1_2));
+    /// assert_eq!(iter.next(), Some(n1_1));
+    /// assert_eq!(iter.next(), None);
+    /// ```
+    ///
+    /// [`Node::is_removed()`]: struct.Node.html#method.is_removed
+    /// [`remove`]: struct.NodeId.html#method.remove
+    pub fn prepend<T>(self, new_child: NodeId, arena: &mut Arena<T>) {
+        self.checked_prepend(new_child, arena)
+            .expect("Preconditions not met: invalid argument");
+    }
+
+    /// Prepends a new child to this node, before existing children.
+    ///
+    /// # Failures
+    ///
+    /// * Returns [`NodeError::PrependSelf`] error if the given new child is
+    ///   `self`.
+    /// * Returns [`NodeError::Removed`] error if the given new child or `self`
+    ///   is [`remove`]d.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use generational_indextree::Arena;
+    /// let mut arena = Arena::new();
+    /// let n1 = arena.new_node("1");
+    /// assert!(n1.checked_prepend(n1, &mut arena).is_err());
+    ///
+    /// let n1_1 = arena.new_node("1_1");
+    /// assert!(n1.checked_prepend(n1_1, &mut arena).is_ok());
+    /// ```
+    ///
+    /// [`Node::is_removed()`]: struct.Node.html#method.is_removed
+    /// [`NodeError::PrependSelf`]: enum.NodeError.html#variant.PrependSelf
+    /// [`NodeError::Removed`]: enum.NodeError.html#variant.Removed
+    /// [`remove`]: struct.NodeId.html#method.remove
+    pub fn checked_prepend<T>(
+        self,
+        new_child: NodeId,
+        arena: &mut Arena<T>,
+    ) -> Result<(), NodeError> {
+        if new_child == self {
+            return Err(NodeError::PrependSelf);
+        }
+        if !arena.nodes.contains(self.get_index()) || !arena.nodes.contains(new_child.get_index()) {
+            return Err(NodeError::Removed);
+        }
+        insert_with_neighbors(arena, new_child, Some(self), None, arena[self].first_child)
+            .expect("Should never fail: `new_child` is not `self` and they are not removed");
+        arena.debug_assert_valid();
+
+        Ok(())
+    }
+
+    /// Inserts a new sibling after this node.
+    ///
+    /// # Panics
+    ///
+    /// Panics if:
+    ///
+    /// * the given new sibling is `self`, or
+    /// * the current node or the given new sibling was already [`remove`]d.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use generational_indextree::Arena;
+    /// # let mut arena = Arena::new();
+    /// # let n1 = arena.new_node("1");
+    /// # let n1_1 = arena.new_node("1_1");
+    /// # n1.append(n1_1, &mut arena);
+    /// # let n1_2 = arena.new_node("1_2");
+    /// # n1.append(n1_2, &mut arena);
+    /// #
+    /// // arena
+    /// // `-- 1
+    /// //     |-- 1_1
+    /// //     `-- 1_2
+    ///
+    /// let n1_3 = arena.new_node("1_3");
+    /// n1_1.insert_after(n1_3, &mut arena);
+    ///
+    /// // arena
+    /// // `-- 1
+    /// //     |-- 1_1
+    /// //     |-- 1_3
+    /// //     `-- 1_2
+    ///
+    /// let mut iter = n1.descendants(&arena);
+    /// assert_eq!(iter.next(), Some(n1));
+    /// assert_eq!(iter.next(), Some(n1_1));
+    /// assert_eq!(iter.next(), Some(n1_3));
+    /// assert_eq!(iter.next(), Some(n1_2));
+    /// assert_eq!(iter.next(), None);
+    /// ```
+    ///
+    /// [`Node::is_removed()`]: struct.Node.html#method.is_removed
+    /// [`remove`]: struct.NodeId.html#method.remove
+    pub fn insert_after<T>(self, new_sibling: NodeId, arena: &mut Arena<T>) {
+        self.checked_insert_after(new_sibling, arena)
+            .expect("Preconditions not met: invalid argument");
+    }
+
+    /// Inserts a new sibling after this node.
+    ///
+    /// # Failures
+    ///
+    /// * Returns [`NodeError::InsertAfterSelf`] error if the given new sibling
+    ///   is `self`.
+    /// * Returns [`NodeError::Removed`] error if the given new sibling or
+    ///   `self` is [`remove`]d.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use generational_indextree::Arena;
+    /// let mut arena = Arena::new();
+    /// let n1 = arena.new_node("1");
+    /// assert!(n1.checked_insert_after(n1, &mut arena).is_err());
+    ///
+    /// let n2 = arena.new_node("2");
+    /// assert!(n1.checked_insert_after(n2, &mut arena).is_ok());
+    /// ```
+    ///
+    /// [`Node::is_removed()`]: struct.Node.html#method.is_removed
+    /// [`NodeError::InsertAfterSelf`]: enum.NodeError.html#variant.InsertAfterSelf
+    /// [`NodeError::Removed`]: enum.NodeError.html#variant.Removed
+    /// [`remove`]: struct.NodeId.html#method.remove
+    pub fn checked_insert_after<T>(
+        self,
+        new_sibling: NodeId,
+        arena: &mut Arena<T>,
+    ) -> Result<(), NodeError> {
+        if new_sibling == self {
+            return Err(NodeError::InsertAfterSelf);
+        }
+        if !arena.nodes.contains(self.get_index()) || !arena.nodes.contains(new_sibling.get_index()) {
+            return Err(NodeError::Removed);
+        }
+        new_sibling.detach(arena);
+        let (next_sibling, parent) = {
+            let current = &arena[self];
+            (current.next_sibling, current.parent)
+        };
+        insert_with_neighbors(arena, new_sibling, parent, Some(self), next_sibling)
+            .expect("Should never fail: `new_sibling` is not `self` and they are not removed");
+        arena.debug_assert_valid();
+
+        Ok(())
+    }
+
+    /// Inserts a new sibling before this node.
+    ///
+    /// # Panics
+    ///
+    /// Panics if:
+    ///
+    /// * the given new sibling is `self`, or
+    /// * the current node or the given new sibling was already [`remove`]d.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use generational_indextree::Arena;
+    /// let mut arena = Arena::new();
+    /// let n1 = arena.new_node("1");
+    /// let n1_1 = arena.new_node("1_1");
+    /// n1.append(n1_1, &mut arena);
+    /// let n1_2 = arena.new_node("1_2");
+    /// n1.append(n1_2, &mut arena);
+    ///
+    /// // arena
+    /// // `-- 1
+    /// //     |-- 1_1
+    /// //     `-- 1_2
+    ///
+    /// let n1_3 = arena.new_node("1_3");
+    /// n1_2.insert_before(n1_3, &mut arena);
+    ///
+    /// // arena
+    /// // `-- 1
+    /// //     |-- 1_1
+    /// //     |-- 1_3
+    /// //     `-- 1_2
+    ///
+    /// let mut iter = n1.descendants(&arena);
+    /// assert_eq!(iter.next(), Some(n1));
+    /// assert_eq!(iter.next(), Some(n1_1));
+    /// assert_eq!(iter.next(), Some(n1_3));
+    /// assert_eq!(iter.next(), Some(n1_2));
+    /// assert_eq!(iter.next(), None);
+    /// ```
+    ///
+    /// [`remove`]: struct.NodeId.html#method.remove
+    pub fn insert_before<T>(self, new_sibling: NodeId, arena: &mut Arena<T>) {
+        self.checked_insert_before(new_sibling, arena)
+            .expect("Preconditions not met: invalid argument");
+    }
+
+    /// Inserts a new sibling before this node.
+    ///
+    /// # Failures
+    ///
+    /// * Returns [`NodeError::InsertBeforeSelf`] error if the given new sibling
+    ///   is `self`.
+    /// * Returns [`NodeError::Removed`] error if the given new sibling or
+    ///   `self` is [`remove`]d.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use generational_indextree::Arena;
+    /// let mut arena = Arena::new();
+    /// let n1 = arena.new_node("1");
+    /// assert!(n1.checked_insert_before(n1, &mut arena).is_err());
+    ///
+    /// let n2 = arena.new_node("2");
+    /// assert!(n1.checked_insert_before(n2, &mut arena).is_ok());
+    /// ```
+    ///
+    /// [`NodeError::InsertBeforeSelf`]: enum.NodeError.html#variant.InsertBeforeSelf
+    /// [`NodeError::Removed`]: enum.NodeError.html#variant.Removed
+    /// [`remove`]: struct.NodeId.html#method.remove
+    pub fn checked_insert_before<T>(
+        self,
+        new_sibling: NodeId,
+        arena: &mut Arena<T>,
+    ) -> Result<(), NodeError> {
+        if new_sibling == self {
+            return Err(NodeError::InsertBeforeSelf);
+        }
+        if !arena.nodes.contains(self.get_index()) || !arena.nodes.contains(new_sibling.get_index()) {
+            return Err(NodeError::Removed);
+        }
+        new_sibling.detach(arena);
+        let (previous_sibling, parent) = {
+            let current = &arena[self];
+            (current.previous_sibling, current.parent)
+        };
+        insert_with_neighbors(arena, new_sibling, parent, previous_sibling, Some(self))
+            .expect("Should never fail: `new_sibling` is not `self` and they are not removed");
+        arena.debug_assert_valid();
+
+        Ok(())
+    }
+
+    /// Removes a node from the arena.
+    ///
+    /// Children of the removed node will be inserted to the place where the
+    /// removed node was.
+    ///
+    /// Please note that the node will not be removed from the internal arena
+    /// storage, but marked as `removed`. Traversing the arena returns a
+    /// plain iterator and contains removed elements too.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use generational_indextree::Arena;
+    /// # let mut arena = Arena::new();
+    /// # let n1 = arena.new_node("1");
+    /// # let n1_1 = arena.new_node("1_1");
+    /// # n1.append(n1_1, &mut arena);
+    /// # let n1_2 = arena.new_node("1_2");
+    /// # n1.append(n1_2, &mut arena);
+    /// # let n1_2_1 = arena.new_node("1_2_1");
+    /// # n1_2.append(n1_2_1, &mut arena);
+    /// # let n1_2_2 = arena.new_node("1_2_2");
+    /// # n1_2.append(n1_2_2, &mut arena);
+    /// # let n1_3 = arena.new_node("1_3");
     /// # n1.append(n1_3, &mut arena);
     /// #
-    /// # // arena
-    /// # // `-- 1
-    /// # //     |-- 1_1
-    /// # //     |   `-- 1_1_1
-    /// # //     |-- 1_2
-    /// # //     `-- 1_3
+    /// // arena
+    /// // `-- 1
+    /// //     |-- 1_1
+    /// //     |-- 1_2
+    /// //     |   |-- 1_2_1
+    /// //     |   `-- 1_2_2
+    /// //     `-- 1_3
+    ///
+    /// n1_2.remove(&mut arena);
+    ///
+    /// let mut iter = n1.descendants(&arena);
+    /// assert_eq!(iter.next(), Some(n1));
+    /// assert_eq!(iter.next(), Some(n1_1));
+    /// assert_eq!(iter.next(), Some(n1_2_1));
+    /// assert_eq!(iter.next(), Some(n1_2_2));
+    /// assert_eq!(iter.next(), Some(n1_3));
+    /// assert_eq!(iter.next(), None);
+    /// ```
+    ///
+    pub fn remove<T>(self, arena: &mut Arena<T>) {
+        self.remove_take(arena);
+    }
+
+    /// Removes a node from the arena and returns its data.
+    ///
+    /// Children of the removed node will be inserted to the place where the
+    /// removed node was, as with [`remove`]. Returns `None` if the node has
+    /// already been removed.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use generational_indextree::Arena;
+    /// # let mut arena = Arena::new();
+    /// # let n1 = arena.new_node("1".to_string());
+    /// # let n1_1 = arena.new_node("1_1".to_string());
+    /// # n1.append(n1_1, &mut arena);
+    /// # let n1_1_1 = arena.new_node("1_1_1".to_string());
+    /// # n1_1.append(n1_1_1, &mut arena);
     /// #
-    /// let traverse = n1.traverse(&arena).collect::<Vec<_>>();
-    /// let mut reverse = n1.reverse_traverse(&arena).collect::<Vec<_>>();
-    /// reverse.reverse();
-    /// assert_eq!(traverse, reverse);
+    /// // arena
+    /// // `-- 1
+    /// //     `-- 1_1
+    /// //         `-- 1_1_1
+    ///
+    /// assert_eq!(n1_1.remove_take(&mut arena), Some("1_1".to_string()));
+    /// assert_eq!(n1.children(&arena).collect::<Vec<_>>(), vec![n1_1_1]);
+    ///
+    /// assert_eq!(n1_1.remove_take(&mut arena), None);
+    /// ```
+    ///
+    /// [`remove`]: struct.NodeId.html#method.remove
+    pub fn remove_take<T>(self, arena: &mut Arena<T>) -> Option<T> {
+        if !arena.nodes.contains(self.get_index()) {
+            return None;
+        }
+        debug_assert_triangle_nodes!(
+            arena,
+            arena[self].parent,
+            arena[self].previous_sibling,
+            Some(self)
+        );
+        debug_assert_triangle_nodes!(
+            arena,
+            arena[self].parent,
+            Some(self),
+            arena[self].next_sibling
+        );
+        debug_assert_triangle_nodes!(arena, Some(self), None, arena[self].first_child);
+        debug_assert_triangle_nodes!(arena, Some(self), arena[self].last_child, None);
+
+        // Retrieve needed values.
+        let (parent, previous_sibling, next_sibling, first_child, last_child) = {
+            let node = &arena[self];
+            (
+                node.parent,
+                node.previous_sibling,
+                node.next_sibling,
+                node.first_child,
+                node.last_child,
+            )
+        };
+
+        assert_eq!(first_child.is_some(), last_child.is_some());
+        self.detach(arena);
+        if let (Some(first_child), Some(last_child)) = (first_child, last_child) {
+            let range = SiblingsRange::new(first_child, last_child).detach_from_siblings(arena);
+            range
+                .transplant(arena, parent, previous_sibling, next_sibling)
+                .expect("Should never fail: neighbors and children must be consistent");
+        }
+        debug_assert!(arena[self].is_detached());
+        let data = arena.nodes.remove(self.get_index()).map(|node| node.data);
+        arena.debug_assert_valid();
+        data
+    }
+
+    /// Removes this node from the arena and returns its data, moving its last
+    /// sibling to the place where it was.
+    ///
+    /// This is the tree counterpart of `Vec::swap_remove`: the order of the
+    /// siblings changes, and the children of the removed node are appended
+    /// after all of them instead of taking its place. Use it where the order
+    /// of children does not matter. Only the node, the last sibling and
+    /// their neighbors are relinked, plus the children of the node, which
+    /// are moved as one range.
+    ///
+    /// A node without a parent has no last sibling at hand, and is removed as
+    /// with [`remove_take`]. Returns `None` if the node has already been
+    /// removed.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use generational_indextree::Arena;
+    /// # let mut arena = Arena::new();
+    /// # let n1 = arena.new_node("1");
+    /// # let n1_1 = arena.new_node("1_1");
+    /// # n1.append(n1_1, &mut arena);
+    /// # let n1_2 = arena.new_node("1_2");
+    /// # n1.append(n1_2, &mut arena);
+    /// # let n1_2_1 = arena.new_node("1_2_1");
+    /// # n1_2.append(n1_2_1, &mut arena);
+    /// # let n1_3 = arena.new_node("1_3");
+    /// # n1.append(n1_3, &mut arena);
+    /// # let n1_4 = arena.new_node("1_4");
+    /// # n1.append(n1_4, &mut arena);
+    /// #
+    /// // arena
+    /// // `-- 1
+    /// //     |-- 1_1
+    /// //     |-- 1_2
+    /// //     |   `-- 1_2_1
+    /// //     |-- 1_3
+    /// //     `-- 1_4
+    ///
+    /// assert_eq!(n1_2.swap_remove(&mut arena), Some("1_2"));
+    /// // arena
+    /// // `-- 1
+    /// //     |-- 1_1
+    /// //     |-- 1_4
+    /// //     |-- 1_3
+    /// //     `-- 1_2_1
+    ///
+    /// let children = n1.children(&arena).collect::<Vec<_>>();
+    /// assert_eq!(children, vec![n1_1, n1_4, n1_3, n1_2_1]);
+    /// assert_eq!(n1_2.swap_remove(&mut arena), None);
+    /// ```
+    ///
+    /// [`remove_take`]: struct.NodeId.html#method.remove_take
+    pub fn swap_remove<T>(self, arena: &mut Arena<T>) -> Option<T> {
+        let node = arena.get(self)?;
+        let (previous_sibling, next_sibling, first_child, last_child) = (
+            node.previous_sibling,
+            node.next_sibling,
+            node.first_child,
+            node.last_child,
+        );
+        let parent = match node.parent {
+            Some(parent) => parent,
+            None => return self.remove_take(arena),
+        };
+        let last = arena[parent]
+            .last_child
+            .expect("Should never fail: the parent has at least this child");
+
+        // Unlink the node, moving its last sibling to its place.
+        match next_sibling {
+            Some(next_sibling) if next_sibling != last => {
+                let before_last = arena[last].previous_sibling;
+                connect_neighbors(arena, Some(parent), before_last, None);
+                connect_neighbors(arena, Some(parent), previous_sibling, Some(last));
+                connect_neighbors(arena, Some(parent), Some(last), Some(next_sibling));
+            }
+            _ => connect_neighbors(arena, Some(parent), previous_sibling, next_sibling),
+        }
+        let node = &mut arena[self];
+        node.parent = None;
+        node.previous_sibling = None;
+        node.next_sibling = None;
+
+        if let (Some(first_child), Some(last_child)) = (first_child, last_child) {
+            let new_previous = arena[parent].last_child;
+            SiblingsRange::new(first_child, last_child)
+                .detach_from_siblings(arena)
+                .transplant(arena, Some(parent), new_previous, None)
+                .expect("Should never fail: the parent is not one of the children");
+        }
+        debug_assert!(arena[self].is_detached());
+        let data = arena.nodes.remove(self.get_index()).map(|node| node.data);
+        arena.debug_assert_valid();
+        data
+    }
+
+    /// Removes this node from the arena, moving its children to the place
+    /// where it was.
+    ///
+    /// The children are spliced in exactly where the node sat: the siblings
+    /// before the node stay before them, and the ones after it stay after
+    /// them. Returns the IDs of the former children, in order. If the node
+    /// has no parent, its children are detached from each other and become
+    /// separate roots.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use generational_indextree::Arena;
+    /// # let mut arena = Arena::new();
+    /// # let n1 = arena.new_node("1");
+    /// # let n1_1 = arena.new_node("1_1");
+    /// # n1.append(n1_1, &mut arena);
+    /// # let n1_1_1 = arena.new_node("1_1_1");
+    /// # n1_1.append(n1_1_1, &mut arena);
+    /// # let n1_1_2 = arena.new_node("1_1_2");
+    /// # n1_1.append(n1_1_2, &mut arena);
+    /// # let n1_2 = arena.new_node("1_2");
+    /// # n1.append(n1_2, &mut arena);
+    /// #
+    /// // arena
+    /// // `-- 1
+    /// //     |-- 1_1
+    /// //     |   |-- 1_1_1
+    /// //     |   `-- 1_1_2
+    /// //     `-- 1_2
+    ///
+    /// assert_eq!(n1_1.unwrap(&mut arena), vec![n1_1_1, n1_1_2]);
+    /// // arena
+    /// // `-- 1
+    /// //     |-- 1_1_1
+    /// //     |-- 1_1_2
+    /// //     `-- 1_2
+    ///
+    /// assert_eq!(n1.children(&arena).collect::<Vec<_>>(), vec![n1_1_1, n1_1_2, n1_2]);
+    ///
+    /// assert_eq!(n1.unwrap(&mut arena), vec![n1_1_1, n1_1_2, n1_2]);
+    /// assert!(arena[n1_1_2].parent().is_none());
+    /// assert!(arena[n1_1_2].previous_sibling().is_none());
+    /// assert!(arena[n1_1_2].next_sibling().is_none());
+    /// ```
+    pub fn unwrap<T>(self, arena: &mut Arena<T>) -> Vec<NodeId> {
+        let children = self.children(arena).collect::<Vec<_>>();
+        if arena[self].parent.is_none() {
+            for &child in &children {
+                child.detach(arena);
+            }
+        }
+        self.remove(arena);
+        children
+    }
+
+    /// Removes this node from the arena, moving its first child to the place
+    /// where it was and the other children under that first child.
+    ///
+    /// The other children are appended after the children the first child
+    /// already has, in order. Returns the ID of the promoted child, or `None`
+    /// if the node had no children, in which case it is simply removed, or
+    /// had already been removed.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use generational_indextree::Arena;
+    /// # let mut arena = Arena::new();
+    /// # let n1 = arena.new_node("1");
+    /// # let n1_1 = arena.new_node("1_1");
+    /// # n1.append(n1_1, &mut arena);
+    /// # let n1_2 = arena.new_node("1_2");
+    /// # n1.append(n1_2, &mut arena);
+    /// # let n1_2_1 = arena.new_node("1_2_1");
+    /// # n1_2.append(n1_2_1, &mut arena);
+    /// # let n1_2_1_1 = arena.new_node("1_2_1_1");
+    /// # n1_2_1.append(n1_2_1_1, &mut arena);
+    /// # let n1_2_2 = arena.new_node("1_2_2");
+    /// # n1_2.append(n1_2_2, &mut arena);
+    /// # let n1_3 = arena.new_node("1_3");
+    /// # n1.append(n1_3, &mut arena);
+    /// #
+    /// // arena
+    /// // `-- 1
+    /// //     |-- 1_1
+    /// //     |-- 1_2
+    /// //     |   |-- 1_2_1
+    /// //     |   |   `-- 1_2_1_1
+    /// //     |   `-- 1_2_2
+    /// //     `-- 1_3
+    ///
+    /// assert_eq!(n1_2.remove_promoting_first_child(&mut arena), Some(n1_2_1));
+    /// // arena
+    /// // `-- 1
+    /// //     |-- 1_1
+    /// //     |-- 1_2_1
+    /// //     |   |-- 1_2_1_1
+    /// //     |   `-- 1_2_2
+    /// //     `-- 1_3
+    ///
+    /// assert_eq!(n1.children(&arena).collect::<Vec<_>>(), vec![n1_1, n1_2_1, n1_3]);
+    /// assert_eq!(n1_2_1.children(&arena).collect::<Vec<_>>(), vec![n1_2_1_1, n1_2_2]);
+    ///
+    /// assert_eq!(n1_3.remove_promoting_first_child(&mut arena), None);
+    /// assert!(arena.get(n1_3).is_none());
+    /// ```
+    pub fn remove_promoting_first_child<T>(self, arena: &mut Arena<T>) -> Option<NodeId> {
+        let first_child = match arena.get(self)?.first_child {
+            Some(first_child) => first_child,
+            None => {
+                self.remove(arena);
+                return None;
+            }
+        };
+        let others = first_child.following_siblings(arena).skip(1).collect::<Vec<_>>();
+        for child in others {
+            first_child.append(child, arena);
+        }
+        self.remove(arena);
+        Some(first_child)
+    }
+
+    /// Removes a node and its descendants from the arena.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use generational_indextree::Arena;
+    /// # let mut arena = Arena::new();
+    /// # let n1 = arena.new_node("1");
+    /// # let n1_1 = arena.new_node("1_1");
+    /// # n1.append(n1_1, &mut arena);
+    /// # let n1_2 = arena.new_node("1_2");
+    /// # n1.append(n1_2, &mut arena);
+    /// # let n1_2_1 = arena.new_node("1_2_1");
+    /// # n1_2.append(n1_2_1, &mut arena);
+    /// # let n1_2_2 = arena.new_node("1_2_2");
+    /// # n1_2.append(n1_2_2, &mut arena);
+    /// # let n1_3 = arena.new_node("1_3");
+    /// # n1.append(n1_3, &mut arena);
+    /// #
+    /// // arena
+    /// // `-- 1
+    /// //     |-- 1_1
+    /// //     |-- 1_2
+    /// //     |   |-- 1_2_1
+    /// //     |   `-- 1_2_2
+    /// //     `-- 1_3
+    ///
+    /// n1_2.remove_subtree(&mut arena);
+    ///
+    /// let mut iter = n1.descendants(&arena);
+    /// assert_eq!(iter.next(), Some(n1));
+    /// assert_eq!(iter.next(), Some(n1_1));
+    /// assert_eq!(iter.next(), Some(n1_3));
+    /// assert_eq!(iter.next(), None);
+    /// assert_eq!(arena.count(), 3);
+    /// assert!(arena.get(n1_2_1).is_none());
     /// ```
-    pub fn reverse_traverse<T>(self, arena: &Arena<T>) -> ReverseTraverse<'_, T> {
-        ReverseTraverse::new(arena, self)
+    ///
+    pub fn remove_subtree<T>(self, arena: &mut Arena<T>) {
+        self.remove_subtree_postorder(arena, drop);
     }
 
-    /// Detaches a node from its parent and siblings. Children are not affected.
+    /// Removes a node and its descendants from the arena, and returns their
+    /// data in document order (pre-order).
     ///
     /// # Examples
     ///
     /// ```
-    /// # use generational_indextree::{Arena, NodeEdge};
+    /// # use generational_indextree::Arena;
     /// # let mut arena = Arena::new();
     /// # let n1 = arena.new_node("1");
     /// # let n1_1 = arena.new_node("1_1");
     /// # n1.append(n1_1, &mut arena);
-    /// # let n1_1_1 = arena.new_node("1_1_1");
-    /// # n1_1.append(n1_1_1, &mut arena);
     /// # let n1_2 = arena.new_node("1_2");
     /// # n1.append(n1_2, &mut arena);
+    /// # let n1_2_1 = arena.new_node("1_2_1");
+    /// # n1_2.append(n1_2_1, &mut arena);
+    /// # let n1_2_2 = arena.new_node("1_2_2");
+    /// # n1_2.append(n1_2_2, &mut arena);
     /// # let n1_3 = arena.new_node("1_3");
     /// # n1.append(n1_3, &mut arena);
     /// #
     /// // arena
-    /// // `-- (implicit)
-    /// //     `-- 1
-    /// //         |-- 1_1
-    /// //         |   `-- 1_1_1
-    /// //         |-- 1_2 *
-    /// //         `-- 1_3
-    ///
-    /// n1_2.detach(&mut arena);
-    /// // arena
-    /// // |-- (implicit)
-    /// // |   `-- 1
-    /// // |       |-- 1_1
-    /// // |       |   `-- 1_1_1
-    /// // |       `-- 1_3
-    /// // `-- (implicit)
-    /// //     `-- 1_2
-    ///
-    /// assert!(arena[n1_2].parent().is_none());
-    /// assert!(arena[n1_2].previous_sibling().is_none());
-    /// assert!(arena[n1_2].next_sibling().is_none());
+    /// // `-- 1
+    /// //     |-- 1_1
+    /// //     |-- 1_2
+    /// //     |   |-- 1_2_1
+    /// //     |   `-- 1_2_2
+    /// //     `-- 1_3
     ///
-    /// let mut iter = n1.descendants(&arena);
-    /// assert_eq!(iter.next(), Some(n1));
-    /// assert_eq!(iter.next(), Some(n1_1));
-    /// assert_eq!(iter.next(), Some(n1_1_1));
-    /// assert_eq!(iter.next(), Some(n1_3));
-    /// assert_eq!(iter.next(), None);
+    /// let removed = n1_2.remove_subtree_collect(&mut arena);
+    /// assert_eq!(removed, vec!["1_2", "1_2_1", "1_2_2"]);
+    /// assert_eq!(arena.count(), 3);
+    /// assert!(arena.get(n1_2).is_none());
     /// ```
-    pub fn detach<T>(self, arena: &mut Arena<T>) {
-        let range = SiblingsRange::new(self, self).detach_from_siblings(arena);
-        range
-            .rewrite_parents(arena, None)
-            .expect("Should never happen: `None` as parent is always valid");
-
-        // Ensure the node is surely detached.
-        debug_assert!(
-            arena[self].is_detached(),
-            "The node should be successfully detached"
-        );
+    pub fn remove_subtree_collect<T>(self, arena: &mut Arena<T>) -> Vec<T> {
+        self.detach(arena);
+        let ids = self.descendants(arena).collect::<Vec<_>>();
+        ids.into_iter()
+            .map(|id| {
+                arena
+                    .nodes
+                    .remove(id.get_index())
+                    .expect("Should never fail: descendants of a live node are live")
+                    .data
+            })
+            .collect()
     }
 
-    /// Appends a new child to this node, after existing children.
-    ///
-    /// # Panics
-    ///
-    /// Panics if:
+    /// Removes a node and its descendants from the arena, handing the data of
+    /// each removed node to `on_remove` in postorder.
     ///
-    /// * the given new child is `self`
+    /// Every node is handed over after all of its descendants, and this node
+    /// last, so resources owned by the data can be released children first.
     ///
     /// # Examples
     ///
     /// ```
     /// # use generational_indextree::Arena;
-    /// let mut arena = Arena::new();
-    /// let n1 = arena.new_node("1");
-    /// let n1_1 = arena.new_node("1_1");
-    /// n1.append(n1_1, &mut arena);
-    /// let n1_2 = arena.new_node("1_2");
-    /// n1.append(n1_2, &mut arena);
-    /// let n1_3 = arena.new_node("1_3");
-    /// n1.append(n1_3, &mut arena);
-    ///
+    /// # let mut arena = Arena::new();
+    /// # let n1 = arena.new_node("1");
+    /// # let n1_1 = arena.new_node("1_1");
+    /// # n1.append(n1_1, &mut arena);
+    /// # let n1_1_1 = arena.new_node("1_1_1");
+    /// # n1_1.append(n1_1_1, &mut arena);
+    /// # let n1_2 = arena.new_node("1_2");
+    /// # n1.append(n1_2, &mut arena);
+    /// #
     /// // arena
     /// // `-- 1
     /// //     |-- 1_1
-    /// //     |-- 1_2
-    /// //     `-- 1_3
+    /// //     |   `-- 1_1_1
+    /// //     `-- 1_2
     ///
-    /// let mut iter = n1.descendants(&arena);
-    /// assert_eq!(iter.next(), Some(n1));
-    /// assert_eq!(iter.next(), Some(n1_1));
-    /// assert_eq!(iter.next(), Some(n1_2));
-    /// assert_eq!(iter.next(), Some(n1_3));
-    /// assert_eq!(iter.next(), None);
+    /// let mut released = Vec::new();
+    /// n1.remove_subtree_postorder(&mut arena, |data| released.push(data));
+    /// assert_eq!(released, vec!["1_1_1", "1_1", "1_2", "1"]);
+    /// assert!(arena.is_empty());
     /// ```
-    ///
-    /// [`Node::is_removed()`]: struct.Node.html#method.is_removed
-    /// [`remove`]: struct.NodeId.html#method.remove
-    pub fn append<T>(self, new_child: NodeId, arena: &mut Arena<T>) {
-        self.checked_append(new_child, arena)
-            .expect("Preconditions not met: invalid argument");
+    pub fn remove_subtree_postorder<T, F: FnMut(T)>(self, arena: &mut Arena<T>, mut on_remove: F) {
+        self.detach(arena);
+
+        // Use a post-order traversal to remove the nodes, so that the parent
+        // of the current node is still available when it is freed.
+        let mut cursor = Some(self.leftmost_leaf(arena));
+        while let Some(id) = cursor {
+            let node = arena
+                .nodes
+                .remove(id.get_index())
+                .expect("Should never fail: descendants of a live node are live");
+            cursor = if id == self {
+                None
+            } else {
+                match node.next_sibling {
+                    Some(next_sibling) => Some(next_sibling.leftmost_leaf(arena)),
+                    None => node.parent,
+                }
+            };
+            on_remove(node.data);
+        }
+        arena.debug_assert_valid();
     }
 
-    /// Appends a new child to this node, after existing children.
+    /// Moves the subtree of each child of this node into an arena of its own,
+    /// and returns the new arenas along with the ID of the root in each.
     ///
-    /// # Failures
+    /// The data is moved, not cloned, and this node stays in the arena
+    /// without children. The new arenas have their own IDs: the IDs of the
+    /// moved nodes are no longer valid in either arena.
     ///
-    /// * Returns [`NodeError::AppendSelf`] error if the given new child is
-    ///   `self`.
-    /// * Returns [`NodeError::Removed`] error if the given new child or `self`
-    ///   is [`remove`]d.
+    /// # Panics
+    ///
+    /// Panics if the node was already [`remove`]d.
     ///
     /// # Examples
     ///
     /// ```
     /// # use generational_indextree::Arena;
-    /// let mut arena = Arena::new();
-    /// let n1 = arena.new_node("1");
-    /// assert!(n1.checked_append(n1, &mut arena).is_err());
+    /// # let mut arena = Arena::new();
+    /// # let n1 = arena.new_node("1");
+    /// # let n1_1 = arena.new_node("1_1");
+    /// # n1.append(n1_1, &mut arena);
+    /// # let n1_2 = arena.new_node("1_2");
+    /// # n1.append(n1_2, &mut arena);
+    /// # let n1_2_1 = arena.new_node("1_2_1");
+    /// # n1_2.append(n1_2_1, &mut arena);
+    /// # let n1_2_2 = arena.new_node("1_2_2");
+    /// # n1_2.append(n1_2_2, &mut arena);
+    /// #
+    /// // arena
+    /// // `-- 1
+    /// //     |-- 1_1
+    /// //     `-- 1_2
+    /// //         |-- 1_2_1
+    /// //         `-- 1_2_2
     ///
-    /// let n1_1 = arena.new_node("1_1");
-    /// assert!(n1.checked_append(n1_1, &mut arena).is_ok());
+    /// let pieces = n1.explode_children(&mut arena);
+    /// assert_eq!(arena.count(), 1);
+    /// assert!(arena[n1].first_child().is_none());
+    ///
+    /// let (tree, root) = &pieces[1];
+    /// let data = root.descendants(tree).map(|id| *tree[id].get()).collect::<Vec<_>>();
+    /// assert_eq!(data, vec!["1_2", "1_2_1", "1_2_2"]);
+    /// assert_eq!(pieces[0].0.count(), 1);
     /// ```
     ///
-    /// [`Node::is_removed()`]: struct.Node.html#method.is_removed
-    /// [`NodeError::AppendSelf`]: enum.NodeError.html#variant.AppendSelf
-    /// [`NodeError::Removed`]: enum.NodeError.html#variant.Removed
     /// [`remove`]: struct.NodeId.html#method.remove
-    pub fn checked_append<T>(
-        self,
-        new_child: NodeId,
-        arena: &mut Arena<T>,
-    ) -> Result<(), NodeError> {
-        if new_child == self {
-            return Err(NodeError::AppendSelf);
-        }
-        if !arena.nodes.contains(self.index) || !arena.nodes.contains(new_child.index) {
-            // if arena[self].is_removed() || arena[new_child].is_removed() {
-            return Err(NodeError::Removed);
+    pub fn explode_children<T>(self, arena: &mut Arena<T>) -> Vec<(Arena<T>, NodeId)> {
+        let children = self.children_vec(arena);
+        let mut pieces = Vec::with_capacity(children.len());
+        for child in children {
+            child.detach(arena);
+            let mut tree = Arena::with_capacity(child.descendants(arena).count());
+            let mut root = None;
+            // Nodes to move, with the parent of their copy, in preorder.
+            let mut stack = Vec::<(NodeId, Option<NodeId>)>::new();
+            stack.push((child, None));
+            while let Some((id, parent)) = stack.pop() {
+                let grandchildren = id.children_vec(arena);
+                let node = arena
+                    .nodes
+                    .remove(id.get_index())
+                    .expect("Should never fail: descendants of a live node are live");
+                let new = tree.new_node(node.data);
+                match parent {
+                    Some(parent) => parent.append(new, &mut tree),
+                    None => root = Some(new),
+                }
+                stack.extend(grandchildren.into_iter().rev().map(|id| (id, Some(new))));
+            }
+            let root = root.expect("Should never fail: the child itself is moved first");
+            pieces.push((tree, root));
         }
-        new_child.detach(arena);
-        insert_with_neighbors(arena, new_child, Some(self), arena[self].last_child, None)
-            .expect("Should never fail: `new_child` is not `self` and they are not removed");
-
-        Ok(())
+        pieces
     }
 
-    /// Prepends a new child to this node, before existing children.
-    ///
-    /// # Panics
-    ///
-    /// Panics if:
+    /// Removes the descendants of this node for which `pred` returns `false`,
+    /// with their own descendants, from the arena.
     ///
-    /// * the given new child is `self`, or
-    /// * the current node or the given new child was already [`remove`]d.
+    /// The node itself is always kept. Descendants are visited in preorder,
+    /// and those of a removed node are dropped without being visited.
     ///
     /// # Examples
     ///
     /// ```
     /// # use generational_indextree::Arena;
-    /// let mut arena = Arena::new();
-    /// let n1 = arena.new_node("1");
-    /// let n1_1 = arena.new_node("1_1");
-    /// n1.prepend(n1_1, &mut arena);
-    /// let n1_2 = arena.new_node("1_2");
-    /// n1.prepend(n1_2, &mut arena);
-    /// let n1_3 = arena.new_node("1_3");
-    /// n1.prepend(n1_3, &mut arena);
+    /// # let mut arena = Arena::new();
+    /// # let n1 = arena.new_node(1);
+    /// # let n1_1 = arena.new_node(-2);
+    /// # n1.append(n1_1, &mut arena);
+    /// # let n1_1_1 = arena.new_node(3);
+    /// # n1_1.append(n1_1_1, &mut arena);
+    /// # let n1_2 = arena.new_node(4);
+    /// # n1.append(n1_2, &mut arena);
+    /// # let n1_2_1 = arena.new_node(-5);
+    /// # n1_2.append(n1_2_1, &mut arena);
+    /// #
+    /// // arena
+    /// // `-- 1
+    /// //     |-- -2
+    /// //     |   `-- 3
+    /// //     `-- 4
+    /// //         `-- -5
     ///
+    /// n1.retain_descendants(&mut arena, |_, node| *node.get() > 0);
     /// // arena
     /// // `-- 1
-    /// //     |-- 1_3
-    /// //     |-- 1_2
-    /// //     `-- 1_1
+    /// //     `-- 4
     ///
-    /// let mut iter = n1.descendants(&arena);
-    /// assert_eq!(iter.next(), Some(n1));
-    /// assert_eq!(iter.next(), Some(n1_3));
-    /// assert_eq!(iter.next(), Some(n1_2));
-    /// assert_eq!(iter.next(), Some(n1_1));
-    /// assert_eq!(iter.next(), None);
+    /// assert_eq!(n1.descendants(&arena).collect::<Vec<_>>(), vec![n1, n1_2]);
+    /// assert_eq!(arena.count(), 2);
     /// ```
-    ///
-    /// [`Node::is_removed()`]: struct.Node.html#method.is_removed
-    /// [`remove`]: struct.NodeId.html#method.remove
-    pub fn prepend<T>(self, new_child: NodeId, arena: &mut Arena<T>) {
-        self.checked_prepend(new_child, arena)
-            .expect("Preconditions not met: invalid argument");
+    pub fn retain_descendants<T, F: FnMut(NodeId, &Node<T>) -> bool>(
+        self,
+        arena: &mut Arena<T>,
+        mut pred: F,
+    ) {
+        let mut stack = self.reverse_children(arena).collect::<Vec<_>>();
+        while let Some(node) = stack.pop() {
+            if pred(node, &arena[node]) {
+                stack.extend(node.reverse_children(arena));
+            } else {
+                node.remove_subtree(arena);
+            }
+        }
     }
 
-    /// Prepends a new child to this node, before existing children.
+    /// Puts `replacement`, with its descendants, in the place of this node,
+    /// and removes this node and its descendants from the arena.
+    ///
+    /// Returns the data of the removed nodes, in preorder.
     ///
     /// # Failures
     ///
-    /// * Returns [`NodeError::PrependSelf`] error if the given new child is
-    ///   `self`.
-    /// * Returns [`NodeError::Removed`] error if the given new child or `self`
-    ///   is [`remove`]d.
+    /// Returns an error if either node has been removed, or if `replacement`
+    /// is this node, one of its ancestors or one of its descendants.
     ///
     /// # Examples
     ///
     /// ```
     /// # use generational_indextree::Arena;
-    /// let mut arena = Arena::new();
-    /// let n1 = arena.new_node("1");
-    /// assert!(n1.checked_prepend(n1, &mut arena).is_err());
+    /// # let mut arena = Arena::new();
+    /// # let n1 = arena.new_node("1");
+    /// # let n1_1 = arena.new_node("1_1");
+    /// # n1.append(n1_1, &mut arena);
+    /// # let n1_2 = arena.new_node("1_2");
+    /// # n1.append(n1_2, &mut arena);
+    /// # let n1_2_1 = arena.new_node("1_2_1");
+    /// # n1_2.append(n1_2_1, &mut arena);
+    /// # let n1_3 = arena.new_node("1_3");
+    /// # n1.append(n1_3, &mut arena);
+    /// #
+    /// // arena
+    /// // `-- 1
+    /// //     |-- 1_1
+    /// //     |-- 1_2
+    /// //     |   `-- 1_2_1
+    /// //     `-- 1_3
     ///
-    /// let n1_1 = arena.new_node("1_1");
-    /// assert!(n1.checked_prepend(n1_1, &mut arena).is_ok());
-    /// ```
+    /// let n2 = arena.new_node("2");
+    /// let n2_1 = arena.new_node("2_1");
+    /// n2.append(n2_1, &mut arena);
     ///
-    /// [`Node::is_removed()`]: struct.Node.html#method.is_removed
-    /// [`NodeError::PrependSelf`]: enum.NodeError.html#variant.PrependSelf
-    /// [`NodeError::Removed`]: enum.NodeError.html#variant.Removed
-    /// [`remove`]: struct.NodeId.html#method.remove
-    pub fn checked_prepend<T>(
+    /// let removed = n1_2.replace_subtree_with(n2, &mut arena);
+    /// assert_eq!(removed.unwrap(), vec!["1_2", "1_2_1"]);
+    /// // arena
+    /// // `-- 1
+    /// //     |-- 1_1
+    /// //     |-- 2
+    /// //     |   `-- 2_1
+    /// //     `-- 1_3
+    ///
+    /// assert_eq!(n1.children(&arena).collect::<Vec<_>>(), vec![n1_1, n2, n1_3]);
+    /// assert!(n1.replace_subtree_with(n2_1, &mut arena).is_err());
+    /// ```
+    pub fn replace_subtree_with<T>(
         self,
-        new_child: NodeId,
+        replacement: NodeId,
         arena: &mut Arena<T>,
-    ) -> Result<(), NodeError> {
-        if new_child == self {
-            return Err(NodeError::PrependSelf);
-        }
-        if !arena.nodes.contains(self.index) || !arena.nodes.contains(new_child.index) {
+    ) -> Result<Vec<T>, NodeError> {
+        if !arena.nodes.contains(self.get_index()) || !arena.nodes.contains(replacement.get_index()) {
             return Err(NodeError::Removed);
         }
-        insert_with_neighbors(arena, new_child, Some(self), None, arena[self].first_child)
-            .expect("Should never fail: `new_child` is not `self` and they are not removed");
-
-        Ok(())
-    }
-
-    /// Inserts a new sibling after this node.
-    ///
-    /// # Panics
-    ///
-    /// Panics if:
+        if self.ancestors(arena).any(|ancestor| ancestor == replacement)
+            || replacement.ancestors(arena).any(|ancestor| ancestor == self)
+        {
+            return Err(NodeError::ReplaceWithRelated);
+        }
+        self.checked_insert_before(replacement, arena)?;
+        Ok(self.remove_subtree_collect(arena))
+    }
+
+    /// Builds a directed [`petgraph`] graph of this node and its descendants,
+    /// and returns it along with the mapping from the IDs of the nodes to
+    /// their indices in the graph.
     ///
-    /// * the given new sibling is `self`, or
-    /// * the current node or the given new sibling was already [`remove`]d.
+    /// Every node of the graph refers to the data of a node of the subtree,
+    /// and every edge goes from a parent to one of its children. The nodes
+    /// are added in preorder, and the edges in the order of the children.
     ///
     /// # Examples
     ///
@@ -657,303 +4929,264 @@ impl NodeId {
     /// //     |-- 1_1
     /// //     `-- 1_2
     ///
-    /// let n1_3 = arena.new_node("1_3");
-    /// n1_1.insert_after(n1_3, &mut arena);
-    ///
-    /// // arena
-    /// // `-- 1
-    /// //     |-- 1_1
-    /// //     |-- 1_3
-    /// //     `-- 1_2
-    ///
-    /// let mut iter = n1.descendants(&arena);
-    /// assert_eq!(iter.next(), Some(n1));
-    /// assert_eq!(iter.next(), Some(n1_1));
-    /// assert_eq!(iter.next(), Some(n1_3));
-    /// assert_eq!(iter.next(), Some(n1_2));
-    /// assert_eq!(iter.next(), None);
+    /// let (graph, indices) = n1.to_petgraph(&arena);
+    /// assert_eq!((graph.node_count(), graph.edge_count()), (3, 2));
+    /// assert_eq!(*graph[indices[&n1_2]], "1_2");
+    /// assert!(graph.contains_edge(indices[&n1], indices[&n1_1]));
     /// ```
     ///
-    /// [`Node::is_removed()`]: struct.Node.html#method.is_removed
-    /// [`remove`]: struct.NodeId.html#method.remove
-    pub fn insert_after<T>(self, new_sibling: NodeId, arena: &mut Arena<T>) {
-        self.checked_insert_after(new_sibling, arena)
-            .expect("Preconditions not met: invalid argument");
+    /// [`petgraph`]: https://docs.rs/petgraph
+    #[cfg(all(feature = "petgraph", feature = "std"))]
+    pub fn to_petgraph<T>(
+        self,
+        arena: &Arena<T>,
+    ) -> (petgraph::Graph<&T, ()>, HashMap<NodeId, petgraph::graph::NodeIndex>) {
+        let mut graph = petgraph::Graph::new();
+        let mut indices = HashMap::new();
+        for id in self.descendants(arena) {
+            let index = graph.add_node(&arena[id].data);
+            indices.insert(id, index);
+            if let Some(parent) = arena[id].parent.filter(|_| id != self) {
+                graph.add_edge(indices[&parent], index, ());
+            }
+        }
+        (graph, indices)
     }
 
-    /// Inserts a new sibling after this node.
-    ///
-    /// # Failures
+    /// Returns a serializable view of this node and its descendants, nested
+    /// as `{"data": ..., "children": [...]}`.
     ///
-    /// * Returns [`NodeError::InsertAfterSelf`] error if the given new sibling
-    ///   is `self`.
-    /// * Returns [`NodeError::Removed`] error if the given new sibling or
-    ///   `self` is [`remove`]d.
+    /// The view writes the nodes straight from the arena, e.g. with
+    /// `serde_json::to_writer`, so no intermediate copy of the tree is built.
+    /// Read it back with [`Arena::deserialize_tree`].
     ///
     /// # Examples
     ///
     /// ```
     /// # use generational_indextree::Arena;
     /// let mut arena = Arena::new();
-    /// let n1 = arena.new_node("1");
-    /// assert!(n1.checked_insert_after(n1, &mut arena).is_err());
+    /// let foo = arena.new_node("foo");
+    /// let bar = arena.new_node("bar");
+    /// foo.append(bar, &mut arena);
     ///
-    /// let n2 = arena.new_node("2");
-    /// assert!(n1.checked_insert_after(n2, &mut arena).is_ok());
+    /// let json = serde_json::to_string(&foo.serialize_tree(&arena)).unwrap();
+    /// assert_eq!(json, r#"{"data":"foo","children":[{"data":"bar","children":[]}]}"#);
     /// ```
     ///
-    /// [`Node::is_removed()`]: struct.Node.html#method.is_removed
-    /// [`NodeError::InsertAfterSelf`]: enum.NodeError.html#variant.InsertAfterSelf
-    /// [`NodeError::Removed`]: enum.NodeError.html#variant.Removed
-    /// [`remove`]: struct.NodeId.html#method.remove
-    pub fn checked_insert_after<T>(
-        self,
-        new_sibling: NodeId,
-        arena: &mut Arena<T>,
-    ) -> Result<(), NodeError> {
-        if new_sibling == self {
-            return Err(NodeError::InsertAfterSelf);
-        }
-        if !arena.nodes.contains(self.index) || !arena.nodes.contains(new_sibling.index) {
-            return Err(NodeError::Removed);
-        }
-        new_sibling.detach(arena);
-        let (next_sibling, parent) = {
-            let current = &arena[self];
-            (current.next_sibling, current.parent)
-        };
-        insert_with_neighbors(arena, new_sibling, parent, Some(self), next_sibling)
-            .expect("Should never fail: `new_sibling` is not `self` and they are not removed");
-
-        Ok(())
+    /// [`Arena::deserialize_tree`]: struct.Arena.html#method.deserialize_tree
+    #[cfg(feature = "deser")]
+    pub fn serialize_tree<T>(self, arena: &Arena<T>) -> SerializeTree<'_, T> {
+        SerializeTree::new(arena, self)
     }
 
-    /// Inserts a new sibling before this node.
-    ///
-    /// # Panics
-    ///
-    /// Panics if:
+    /// Writes this node and its descendants to `writer` as JSON, nested as
+    /// `{"data": ..., "children": [...]}`.
     ///
-    /// * the given new sibling is `self`, or
-    /// * the current node or the given new sibling was already [`remove`]d.
+    /// The output is the same as the one of [`serialize_tree`] with
+    /// `serde_json`, but the tree is walked with [`traverse`] rather than
+    /// recursively, so it can be arbitrarily deep, and only the data of one
+    /// node is serialized at a time. The writer is not buffered, wrap it in a
+    /// `BufWriter` if needed. Read the output back with
+    /// [`Arena::from_json_reader`].
     ///
     /// # Examples
     ///
     /// ```
     /// # use generational_indextree::Arena;
-    /// let mut arena = Arena::new();
-    /// let n1 = arena.new_node("1");
-    /// let n1_1 = arena.new_node("1_1");
-    /// n1.append(n1_1, &mut arena);
-    /// let n1_2 = arena.new_node("1_2");
-    /// n1.append(n1_2, &mut arena);
-    ///
-    /// // arena
-    /// // `-- 1
-    /// //     |-- 1_1
-    /// //     `-- 1_2
-    ///
-    /// let n1_3 = arena.new_node("1_3");
-    /// n1_2.insert_before(n1_3, &mut arena);
-    ///
+    /// # let mut arena = Arena::new();
+    /// # let n1 = arena.new_node(1);
+    /// # let n1_1 = arena.new_node(2);
+    /// # n1.append(n1_1, &mut arena);
+    /// # let n1_2 = arena.new_node(3);
+    /// # n1.append(n1_2, &mut arena);
+    /// #
     /// // arena
     /// // `-- 1
-    /// //     |-- 1_1
-    /// //     |-- 1_3
-    /// //     `-- 1_2
+    /// //     |-- 2
+    /// //     `-- 3
     ///
-    /// let mut iter = n1.descendants(&arena);
-    /// assert_eq!(iter.next(), Some(n1));
-    /// assert_eq!(iter.next(), Some(n1_1));
-    /// assert_eq!(iter.next(), Some(n1_3));
-    /// assert_eq!(iter.next(), Some(n1_2));
-    /// assert_eq!(iter.next(), None);
+    /// let mut json = Vec::new();
+    /// n1.to_json_writer(&arena, &mut json).unwrap();
+    /// assert_eq!(
+    ///     String::from_utf8(json).unwrap(),
+    ///     r#"{"data":1,"children":[{"data":2,"children":[]},{"data":3,"children":[]}]}"#
+    /// );
     /// ```
     ///
-    /// [`remove`]: struct.NodeId.html#method.remove
-    pub fn insert_before<T>(self, new_sibling: NodeId, arena: &mut Arena<T>) {
-        self.checked_insert_before(new_sibling, arena)
-            .expect("Preconditions not met: invalid argument");
+    /// [`serialize_tree`]: struct.NodeId.html#method.serialize_tree
+    /// [`traverse`]: struct.NodeId.html#method.traverse
+    /// [`Arena::from_json_reader`]: struct.Arena.html#method.from_json_reader
+    #[cfg(feature = "json")]
+    pub fn to_json_writer<T: Serialize, W: io::Write>(
+        self,
+        arena: &Arena<T>,
+        mut writer: W,
+    ) -> io::Result<()> {
+        for edge in self.traverse(arena) {
+            match edge {
+                NodeEdge::Start(id) => {
+                    if id != self && arena[id].previous_sibling.is_some() {
+                        writer.write_all(b",")?;
+                    }
+                    writer.write_all(br#"{"data":"#)?;
+                    serde_json::to_writer(&mut writer, &arena[id].data)?;
+                    writer.write_all(br#","children":["#)?;
+                }
+                NodeEdge::End(_) => writer.write_all(b"]}")?,
+            }
+        }
+        Ok(())
     }
 
-    /// Inserts a new sibling before this node.
+    /// Builds a tree nested as `{"data": ..., "children": [...]}` in a JSON
+    /// value and appends it as the last child of this node, returning the ID
+    /// of its root.
     ///
-    /// # Failures
+    /// This reads the format of [`Arena::deserialize_tree`], e.g. from a
+    /// partial update. `children` may be left out for leaves. The value is
+    /// walked with an explicit stack, so trees nested arbitrarily deep are
+    /// built without recursion. On error, the nodes built so far are removed,
+    /// and this node is left as it was.
     ///
-    /// * Returns [`NodeError::InsertBeforeSelf`] error if the given new sibling
-    ///   is `self`.
-    /// * Returns [`NodeError::Removed`] error if the given new sibling or
-    ///   `self` is [`remove`]d.
+    /// # Panics
+    ///
+    /// Panics if the node was already [`remove`]d.
     ///
     /// # Examples
     ///
     /// ```
     /// # use generational_indextree::Arena;
     /// let mut arena = Arena::new();
-    /// let n1 = arena.new_node("1");
-    /// assert!(n1.checked_insert_before(n1, &mut arena).is_err());
+    /// let root = arena.new_node("root".to_string());
     ///
-    /// let n2 = arena.new_node("2");
-    /// assert!(n1.checked_insert_before(n2, &mut arena).is_ok());
+    /// let update = serde_json::json!({"data": "section", "children": [{"data": "text"}]});
+    /// let section = root.append_json_tree(&update, &mut arena).unwrap();
+    /// assert_eq!(arena[section].parent(), Some(root));
+    /// assert_eq!(arena.count(), 3);
+    ///
+    /// let invalid = serde_json::json!({"data": "section", "children": [{"data": 1}]});
+    /// assert!(root.append_json_tree(&invalid, &mut arena).is_err());
+    /// assert_eq!(arena.count(), 3);
     /// ```
     ///
-    /// [`NodeError::InsertBeforeSelf`]: enum.NodeError.html#variant.InsertBeforeSelf
-    /// [`NodeError::Removed`]: enum.NodeError.html#variant.Removed
+    /// [`Arena::deserialize_tree`]: struct.Arena.html#method.deserialize_tree
     /// [`remove`]: struct.NodeId.html#method.remove
-    pub fn checked_insert_before<T>(
+    #[cfg(feature = "json")]
+    pub fn append_json_tree<T: DeserializeOwned>(
         self,
-        new_sibling: NodeId,
+        value: &serde_json::Value,
         arena: &mut Arena<T>,
-    ) -> Result<(), NodeError> {
-        if new_sibling == self {
-            return Err(NodeError::InsertBeforeSelf);
-        }
-        if !arena.nodes.contains(self.index) || !arena.nodes.contains(new_sibling.index) {
-            return Err(NodeError::Removed);
-        }
-        new_sibling.detach(arena);
-        let (previous_sibling, parent) = {
-            let current = &arena[self];
-            (current.previous_sibling, current.parent)
-        };
-        insert_with_neighbors(arena, new_sibling, parent, previous_sibling, Some(self))
-            .expect("Should never fail: `new_sibling` is not `self` and they are not removed");
-
-        Ok(())
+    ) -> Result<NodeId, serde_json::Error> {
+        assert!(
+            arena.nodes.contains(self.get_index()),
+            "Preconditions not met: invalid argument"
+        );
+        crate::nested::append_value(arena, self, value)
     }
 
-    /// Removes a node from the arena.
+    /// Computes a list of edits which turn the subtree of this node into the
+    /// subtree of `other`.
     ///
-    /// Children of the removed node will be inserted to the place where the
-    /// removed node was.
-    ///
-    /// Please note that the node will not be removed from the internal arena
-    /// storage, but marked as `removed`. Traversing the arena returns a
-    /// plain iterator and contains removed elements too.
+    /// This is a simple top-down diff: children are matched by position and
+    /// compared recursively, superfluous children are deleted and missing
+    /// ones inserted, so moves are never detected. Paths in the edits are
+    /// relative to this node; see [`TreeEdit`] for how they are resolved.
     ///
     /// # Examples
     ///
     /// ```
-    /// # use generational_indextree::Arena;
-    /// # let mut arena = Arena::new();
-    /// # let n1 = arena.new_node("1");
-    /// # let n1_1 = arena.new_node("1_1");
-    /// # n1.append(n1_1, &mut arena);
-    /// # let n1_2 = arena.new_node("1_2");
-    /// # n1.append(n1_2, &mut arena);
-    /// # let n1_2_1 = arena.new_node("1_2_1");
-    /// # n1_2.append(n1_2_1, &mut arena);
-    /// # let n1_2_2 = arena.new_node("1_2_2");
-    /// # n1_2.append(n1_2_2, &mut arena);
-    /// # let n1_3 = arena.new_node("1_3");
-    /// # n1.append(n1_3, &mut arena);
-    /// #
-    /// // arena
-    /// // `-- 1
-    /// //     |-- 1_1
-    /// //     |-- 1_2
-    /// //     |   |-- 1_2_1
-    /// //     |   `-- 1_2_2
-    /// //     `-- 1_3
+    /// # use generational_indextree::{Arena, TreeEdit};
+    /// let mut arena = Arena::new();
+    /// let a = arena.new_node("a");
+    /// let a_1 = arena.new_node("1");
+    /// a.append(a_1, &mut arena);
+    /// let a_2 = arena.new_node("2");
+    /// a.append(a_2, &mut arena);
     ///
-    /// n1_2.remove(&mut arena);
+    /// let b = arena.new_node("b");
+    /// let b_1 = arena.new_node("1");
+    /// b.append(b_1, &mut arena);
+    /// let b_1_1 = arena.new_node("1_1");
+    /// b_1.append(b_1_1, &mut arena);
     ///
-    /// let mut iter = n1.descendants(&arena);
-    /// assert_eq!(iter.next(), Some(n1));
-    /// assert_eq!(iter.next(), Some(n1_1));
-    /// assert_eq!(iter.next(), Some(n1_2_1));
-    /// assert_eq!(iter.next(), Some(n1_2_2));
-    /// assert_eq!(iter.next(), Some(n1_3));
-    /// assert_eq!(iter.next(), None);
+    /// assert_eq!(
+    ///     a.diff(&arena, b, &arena),
+    ///     vec![
+    ///         TreeEdit::Update { path: vec![], data: "b" },
+    ///         TreeEdit::Delete { path: vec![1] },
+    ///         TreeEdit::Insert { path: vec![0, 0], data: "1_1" },
+    ///     ]
+    /// );
     /// ```
     ///
-    pub fn remove<T>(self, arena: &mut Arena<T>) {
-        debug_assert_triangle_nodes!(
-            arena,
-            arena[self].parent,
-            arena[self].previous_sibling,
-            Some(self)
-        );
-        debug_assert_triangle_nodes!(
-            arena,
-            arena[self].parent,
-            Some(self),
-            arena[self].next_sibling
-        );
-        debug_assert_triangle_nodes!(arena, Some(self), None, arena[self].first_child);
-        debug_assert_triangle_nodes!(arena, Some(self), arena[self].last_child, None);
-
-        // Retrieve needed values.
-        let (parent, previous_sibling, next_sibling, first_child, last_child) = {
-            let node = &arena[self];
-            (
-                node.parent,
-                node.previous_sibling,
-                node.next_sibling,
-                node.first_child,
-                node.last_child,
-            )
-        };
-
-        assert_eq!(first_child.is_some(), last_child.is_some());
-        self.detach(arena);
-        if let (Some(first_child), Some(last_child)) = (first_child, last_child) {
-            let range = SiblingsRange::new(first_child, last_child).detach_from_siblings(arena);
-            range
-                .transplant(arena, parent, previous_sibling, next_sibling)
-                .expect("Should never fail: neighbors and children must be consistent");
-        }
-        debug_assert!(arena[self].is_detached());
-        arena.nodes.remove(self.index);
+    /// [`TreeEdit`]: enum.TreeEdit.html
+    pub fn diff<T: PartialEq + Clone>(
+        self,
+        arena: &Arena<T>,
+        other: NodeId,
+        other_arena: &Arena<T>,
+    ) -> Vec<TreeEdit<T>> {
+        diff::diff(self, arena, other, other_arena)
     }
 
-    /// Removes a node and its descendants from the arena.
+
+    /// Applies a list of edits, as produced by [`diff`], to the subtree of
+    /// this node.
+    ///
+    /// The edits are applied in order, and paths are resolved relative to
+    /// this node as described in [`TreeEdit`].
+    ///
+    /// # Failures
+    ///
+    /// * Returns [`NodeError::Removed`] error if `self` is [`remove`]d.
+    /// * Returns [`NodeError::InvalidPath`] error if a path of an edit does
+    ///   not point to a valid position, or if an edit tries to delete or move
+    ///   `self`. The edits before the failing one stay applied.
+    ///
     /// # Examples
     ///
     /// ```
     /// # use generational_indextree::Arena;
-    /// # let mut arena = Arena::new();
-    /// # let n1 = arena.new_node("1");
-    /// # let n1_1 = arena.new_node("1_1");
-    /// # n1.append(n1_1, &mut arena);
-    /// # let n1_2 = arena.new_node("1_2");
-    /// # n1.append(n1_2, &mut arena);
-    /// # let n1_2_1 = arena.new_node("1_2_1");
-    /// # n1_2.append(n1_2_1, &mut arena);
-    /// # let n1_2_2 = arena.new_node("1_2_2");
-    /// # n1_2.append(n1_2_2, &mut arena);
-    /// # let n1_3 = arena.new_node("1_3");
-    /// # n1.append(n1_3, &mut arena);
-    /// #
-    /// // arena
-    /// // `-- 1
-    /// //     |-- 1_1
-    /// //     |-- 1_2
-    /// //     |   |-- 1_2_1
-    /// //     |   `-- 1_2_2
-    /// //     `-- 1_3
+    /// let mut arena = Arena::new();
+    /// let a = arena.new_node("a");
+    /// let a_1 = arena.new_node("1");
+    /// a.append(a_1, &mut arena);
     ///
-    /// n1_2.remove_subtree(&mut arena);
+    /// let b = arena.new_node("b");
+    /// let b_1 = arena.new_node("2");
+    /// b.append(b_1, &mut arena);
+    /// let b_2 = arena.new_node("3");
+    /// b.append(b_2, &mut arena);
     ///
-    /// let mut iter = n1.descendants(&arena);
-    /// assert_eq!(iter.next(), Some(n1));
-    /// assert_eq!(iter.next(), Some(n1_1));
-    /// assert_eq!(iter.next(), Some(n1_3));
-    /// assert_eq!(iter.next(), None);
+    /// let edits = a.diff(&arena, b, &arena);
+    /// assert!(a.apply_edits(&mut arena, &edits).is_ok());
+    /// assert_eq!(*arena[a].get(), "b");
+    /// assert_eq!(a.children_data(&arena).collect::<Vec<_>>(), vec![&"2", &"3"]);
     /// ```
     ///
-    pub fn remove_subtree<T>(self, arena: &mut Arena<T>) {
-        self.detach(arena);
-
-        // // use a preorder traversal to remove node.
-        // let mut cursor = Some(self);
-        // while let Some(id) = cursor {
-        //     arena.free_node(id);
-        //     let node = &arena[id];
-        //     cursor = node
-        //         .first_child
-        //         .or(node.next_sibling)
-        //         .or_else(|| node.parent.and_then(|p| arena[p].next_sibling));
-        // }
+    /// [`diff`]: struct.NodeId.html#method.diff
+    /// [`TreeEdit`]: enum.TreeEdit.html
+    /// [`NodeError::Removed`]: enum.NodeError.html#variant.Removed
+    /// [`NodeError::InvalidPath`]: enum.NodeError.html#variant.InvalidPath
+    /// [`remove`]: struct.NodeId.html#method.remove
+    pub fn apply_edits<T: Clone>(
+        self,
+        arena: &mut Arena<T>,
+        edits: &[TreeEdit<T>],
+    ) -> Result<(), NodeError> {
+        diff::apply_edits(self, arena, edits)
     }
 }
+
+/// Combines the hash of the data of a node with the subtree hashes of its
+/// children, as done by [`NodeId::subtree_hash`].
+///
+/// [`NodeId::subtree_hash`]: struct.NodeId.html#method.subtree_hash
+#[cfg(feature = "std")]
+pub(crate) fn node_hash<T: Hash>(data: &T, children: &[u64]) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    data.hash(&mut hasher);
+    // Hashing the slice includes its length, i.e. the shape.
+    children.hash(&mut hasher);
+    hasher.finish()
+}
@@ -0,0 +1,146 @@
+//! Lowest common ancestor index.
+
+#[cfg(not(feature = "std"))]
+use alloc::{collections::BTreeMap, vec::Vec};
+#[cfg(not(feature = "std"))]
+use core::mem;
+#[cfg(feature = "std")]
+use std::{collections::BTreeMap, mem};
+
+use crate::{Arena, NodeId};
+
+#[derive(Clone, Debug)]
+/// A precomputed index answering lowest common ancestor queries on a tree.
+///
+/// The index is built once from the root of a tree, in O(n log n) time and
+/// space for a tree of n nodes, after which every [`query`] takes O(log n)
+/// time, by binary lifting over the ancestors of the nodes. This pays off
+/// when many queries hit the same tree, compared to walking the ancestors of
+/// both nodes on every query.
+///
+/// The index is a snapshot: changes to the tree after it is built are not
+/// reflected, and the index has to be built again.
+///
+/// # Examples
+///
+/// ```
+/// # use generational_indextree::{Arena, LcaIndex};
+/// # let mut arena = Arena::new();
+/// # let n1 = arena.new_node("1");
+/// # let n1_1 = arena.new_node("1_1");
+/// # n1.append(n1_1, &mut arena);
+/// # let n1_1_1 = arena.new_node("1_1_1");
+/// # n1_1.append(n1_1_1, &mut arena);
+/// # let n1_1_2 = arena.new_node("1_1_2");
+/// # n1_1.append(n1_1_2, &mut arena);
+/// # let n1_2 = arena.new_node("1_2");
+/// # n1.append(n1_2, &mut arena);
+/// # let n2 = arena.new_node("2");
+/// #
+/// // arena
+/// // |-- 1
+/// // |   |-- 1_1
+/// // |   |   |-- 1_1_1
+/// // |   |   `-- 1_1_2
+/// // |   `-- 1_2
+/// // `-- 2
+///
+/// let index = LcaIndex::build(n1, &arena);
+/// assert_eq!(index.query(n1_1_1, n1_1_2), Some(n1_1));
+/// assert_eq!(index.query(n1_1_2, n1_2), Some(n1));
+/// assert_eq!(index.query(n1_1, n1_1_1), Some(n1_1));
+/// assert_eq!(index.query(n1_1, n2), None);
+/// ```
+///
+/// [`query`]: struct.LcaIndex.html#method.query
+pub struct LcaIndex {
+    /// Position of every node of the tree, in preorder.
+    positions: BTreeMap<NodeId, usize>,
+    /// Nodes of the tree, in preorder.
+    nodes: Vec<NodeId>,
+    /// Depth of every node, the root being at depth 0.
+    depths: Vec<usize>,
+    /// `ancestors[k][i]` is the position of the `2^k`th ancestor of the node
+    /// at position `i`, the root being its own ancestor.
+    ancestors: Vec<Vec<usize>>,
+}
+
+impl LcaIndex {
+    /// Builds the index for the tree below `root`.
+    pub fn build<T>(root: NodeId, arena: &Arena<T>) -> LcaIndex {
+        let nodes = root.descendants(arena).collect::<Vec<_>>();
+        let positions = nodes
+            .iter()
+            .enumerate()
+            .map(|(position, &id)| (id, position))
+            .collect::<BTreeMap<_, _>>();
+
+        // Parents come before their children in preorder.
+        let mut parents = Vec::with_capacity(nodes.len());
+        let mut depths = Vec::with_capacity(nodes.len());
+        for (position, &id) in nodes.iter().enumerate() {
+            match arena[id].parent.filter(|_| id != root) {
+                Some(parent) => {
+                    let parent = positions[&parent];
+                    parents.push(parent);
+                    depths.push(depths[parent] + 1);
+                }
+                None => {
+                    parents.push(position);
+                    depths.push(0);
+                }
+            }
+        }
+
+        let max_depth = depths.iter().copied().max().unwrap_or(0);
+        let mut ancestors = Vec::new();
+        ancestors.push(parents);
+        while 1 << ancestors.len() <= max_depth {
+            let last = &ancestors[ancestors.len() - 1];
+            let next = last.iter().map(|&ancestor| last[ancestor]).collect();
+            ancestors.push(next);
+        }
+
+        LcaIndex {
+            positions,
+            nodes,
+            depths,
+            ancestors,
+        }
+    }
+
+    /// Returns the lowest common ancestor of `a` and `b`, i.e. the deepest
+    /// node which has both of them among its descendants.
+    ///
+    /// A node counts as its own ancestor. Returns `None` if one of the nodes
+    /// was not in the tree when the index was built.
+    pub fn query(&self, a: NodeId, b: NodeId) -> Option<NodeId> {
+        let (mut a, mut b) = (*self.positions.get(&a)?, *self.positions.get(&b)?);
+        if self.depths[a] < self.depths[b] {
+            mem::swap(&mut a, &mut b);
+        }
+
+        // Lift `a` to the depth of `b`.
+        let mut lift = self.depths[a] - self.depths[b];
+        let mut level = 0;
+        while lift > 0 {
+            if lift & 1 == 1 {
+                a = self.ancestors[level][a];
+            }
+            lift >>= 1;
+            level += 1;
+        }
+        if a == b {
+            return Some(self.nodes[a]);
+        }
+
+        // Lift both to just below their lowest common ancestor.
+        for level in self.ancestors.iter().rev() {
+            if level[a] != level[b] {
+                a = level[a];
+                b = level[b];
+            }
+        }
+        Some(self.nodes[self.ancestors[0][a]])
+    }
+}
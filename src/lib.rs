@@ -22,6 +22,31 @@
 //! a.append(b, arena);
 //! assert_eq!(b.ancestors(arena).into_iter().count(), 2);
 //! ```
+//!
+//! # Determinism
+//!
+//! Traversals and queries give the same results on every run for the same
+//! tree. Their order comes from the links of the tree, or from the order of
+//! the slots of the arena, and never from hashing: methods which need a set
+//! of visited nodes, like [`Arena::descendants_of`] and [`Arena::validate`],
+//! use a `BTreeSet` ordered by `NodeId`. The `HashMap`s used internally, for
+//! instance by [`Arena::find_duplicate_subtrees`], only serve lookups and are
+//! never iterated to build a result.
+//!
+//! Methods which return a `HashMap`, like [`Arena::stable_ids`],
+//! [`Arena::compact_with`] and [`Arena::clone_with_remap`], use the standard
+//! randomly seeded hasher, so iterating over the returned map is not
+//! reproducible. Collect it into a `BTreeMap` first when the order matters.
+//! [`NodeId::subtree_hash`] uses a fixed key, and gives the same hash on every
+//! run of the same build.
+//!
+//! [`Arena::descendants_of`]: struct.Arena.html#method.descendants_of
+//! [`Arena::validate`]: struct.Arena.html#method.validate
+//! [`Arena::find_duplicate_subtrees`]: struct.Arena.html#method.find_duplicate_subtrees
+//! [`Arena::stable_ids`]: struct.Arena.html#method.stable_ids
+//! [`Arena::compact_with`]: struct.Arena.html#method.compact_with
+//! [`Arena::clone_with_remap`]: struct.Arena.html#method.clone_with_remap
+//! [`NodeId::subtree_hash`]: struct.NodeId.html#method.subtree_hash
 #![forbid(unsafe_code)]
 #![cfg_attr(not(feature = "std"), no_std)]
 
@@ -30,21 +55,40 @@ extern crate alloc;
 
 pub use crate::{
     arena::Arena,
+    builder::ChildBuilder,
+    cursor::{NodeMut, NodeRef},
+    diff::TreeEdit,
     error::NodeError,
+    forest::Forest,
     id::NodeId,
+    lca::LcaIndex,
     node::Node,
+    tree::Tree,
     traverse::{
-        Ancestors, Children, Descendants, FollowingSiblings, NodeEdge, PrecedingSiblings,
-        ReverseChildren, ReverseTraverse, Traverse,
+        Ancestors, Children, Descendants, EditAction, FollowingSiblings, NodeEdge,
+        PrecedingSiblings, ReverseChildren, ReverseTraverse, Traverse, zip_descendants,
+        ZipDescendants,
     },
+    weak::WeakNodeId,
 };
+#[cfg(feature = "deser")]
+pub use crate::nested::{SerializeForest, SerializeTree};
 
 #[macro_use]
 pub(crate) mod relations;
 
 mod arena;
+mod builder;
+mod cursor;
+mod diff;
 pub(crate) mod error;
+mod forest;
 mod id;
+mod lca;
+#[cfg(feature = "deser")]
+mod nested;
 mod node;
 pub(crate) mod siblings_range;
 mod traverse;
+mod tree;
+mod weak;
@@ -0,0 +1,452 @@
+//! Nested tree (de)serialization.
+
+#[cfg(not(feature = "std"))]
+use alloc::vec::Vec;
+#[cfg(not(feature = "std"))]
+use core::fmt;
+#[cfg(feature = "std")]
+use std::fmt;
+
+use serde::de::{self, DeserializeSeed, Deserializer, MapAccess, SeqAccess, Visitor};
+use serde::ser::{Serialize, SerializeSeq, SerializeStruct, Serializer};
+#[cfg(feature = "json")]
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+#[cfg(feature = "json")]
+use serde_json::Value;
+
+use crate::{Arena, NodeId};
+
+/// Field names of a nested node.
+const FIELDS: &[&str] = &["data", "children"];
+
+/// Maximum depth of the nodes read, the roots being at depth `0`.
+///
+/// Reading recurses once per level, and not all deserializers bound the
+/// nesting of their input, e.g. a `serde_json::Value` does not.
+pub(crate) const MAX_DEPTH: usize = 128;
+
+#[derive(Deserialize)]
+#[serde(field_identifier, rename_all = "lowercase")]
+enum Field {
+    Data,
+    Children,
+}
+
+/// Reads a nested node and its descendants into `arena`, returning the ID of
+/// the node.
+///
+/// Nodes are created as soon as their data has been read, so apart from the
+/// arena only the current path is held in memory. On error, the nodes read so
+/// far are removed again.
+pub(crate) fn deserialize_tree<'de, T, D>(
+    arena: &mut Arena<T>,
+    deserializer: D,
+) -> Result<NodeId, D::Error>
+where
+    T: Deserialize<'de>,
+    D: Deserializer<'de>,
+{
+    TreeSeed {
+        arena,
+        parent: None,
+        depth: 0,
+    }
+    .deserialize(deserializer)
+}
+
+/// Builds the tree nested in `value` into `arena`, as the last child of
+/// `parent`, returning the ID of its root.
+///
+/// Unlike the seeds, this does not recurse, so the tree may be nested as
+/// deeply as the value is. On error, the nodes built so far are removed
+/// again.
+#[cfg(feature = "json")]
+pub(crate) fn append_value<T: DeserializeOwned>(
+    arena: &mut Arena<T>,
+    parent: NodeId,
+    value: &Value,
+) -> Result<NodeId, serde_json::Error> {
+    let mut root = None;
+    let result = build_value(arena, parent, value, &mut root);
+    if result.is_err() {
+        remove_trees(arena, root);
+    }
+    result
+}
+
+/// Builds the tree of `value`, storing its root in `root` as soon as it is
+/// created.
+#[cfg(feature = "json")]
+fn build_value<T: DeserializeOwned>(
+    arena: &mut Arena<T>,
+    parent: NodeId,
+    value: &Value,
+    root: &mut Option<NodeId>,
+) -> Result<NodeId, serde_json::Error> {
+    let (data, children) = value_parts(value)?;
+    let id = create(arena, Some(parent), T::deserialize(data)?)?;
+    *root = Some(id);
+    // The children still to be built, for each node of the path.
+    let mut stack = vec![(id, children.iter())];
+    while let Some((parent, children)) = stack.last_mut() {
+        let parent = *parent;
+        match children.next() {
+            Some(child) => {
+                let (data, children) = value_parts(child)?;
+                let child = create(arena, Some(parent), T::deserialize(data)?)?;
+                stack.push((child, children.iter()));
+            }
+            None => {
+                stack.pop();
+            }
+        }
+    }
+    Ok(id)
+}
+
+/// Returns the data and the children of a node nested in a value.
+#[cfg(feature = "json")]
+fn value_parts(value: &Value) -> Result<(&Value, &[Value]), serde_json::Error> {
+    let node = match value {
+        Value::Object(node) => node,
+        _ => return Err(de::Error::custom("expected a node with data and children")),
+    };
+    if let Some(field) = node.keys().find(|field| !FIELDS.contains(&field.as_str())) {
+        return Err(de::Error::unknown_field(field, FIELDS));
+    }
+    let data = node.get("data").ok_or_else(|| de::Error::missing_field("data"))?;
+    let children = match node.get("children") {
+        None => &[][..],
+        Some(Value::Array(children)) => children,
+        Some(_) => return Err(de::Error::custom("expected a list of nodes")),
+    };
+    Ok((data, children))
+}
+
+/// Reads a list of nested trees into `arena`, returning the IDs of their
+/// roots in order.
+///
+/// On error, the nodes read so far are removed again.
+pub(crate) fn deserialize_forest<'de, T, D>(
+    arena: &mut Arena<T>,
+    deserializer: D,
+) -> Result<Vec<NodeId>, D::Error>
+where
+    T: Deserialize<'de>,
+    D: Deserializer<'de>,
+{
+    ForestSeed { arena, depth: 0 }.deserialize(deserializer)
+}
+
+/// Creates a node with the given data, as the last child of `parent`.
+///
+/// Fails instead of panicking if the arena has reached its capacity limit.
+fn create<T, E: de::Error>(
+    arena: &mut Arena<T>,
+    parent: Option<NodeId>,
+    data: T,
+) -> Result<NodeId, E> {
+    if arena.is_at_limit() {
+        return Err(E::custom("the arena has reached its capacity limit"));
+    }
+    let id = arena.new_node(data);
+    if let Some(parent) = parent {
+        parent.append(id, arena);
+    }
+    Ok(id)
+}
+
+/// Removes the given nodes and their descendants, after a failed read.
+fn remove_trees<T, I: IntoIterator<Item = NodeId>>(arena: &mut Arena<T>, roots: I) {
+    for root in roots {
+        root.remove_subtree(arena);
+    }
+}
+
+/// Reads a node and its descendants.
+///
+/// On error, the nodes it created are removed again, so that a failed read
+/// leaves the arena as it was at every level.
+struct TreeSeed<'a, T> {
+    arena: &'a mut Arena<T>,
+    parent: Option<NodeId>,
+    /// Depth of the node, counted from the first node read.
+    depth: usize,
+}
+
+impl<'a, 'de, T: Deserialize<'de>> DeserializeSeed<'de> for TreeSeed<'a, T> {
+    type Value = NodeId;
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<NodeId, D::Error> {
+        if self.depth >= MAX_DEPTH {
+            return Err(de::Error::custom(format_args!(
+                "tree nested deeper than {} levels",
+                MAX_DEPTH
+            )));
+        }
+        deserializer.deserialize_struct("Node", FIELDS, self)
+    }
+}
+
+impl<'a, 'de, T: Deserialize<'de>> Visitor<'de> for TreeSeed<'a, T> {
+    type Value = NodeId;
+
+    fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("a node with data and children")
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<NodeId, A::Error> {
+        let TreeSeed {
+            arena,
+            parent,
+            depth,
+        } = self;
+        let data = seq
+            .next_element()?
+            .ok_or_else(|| de::Error::invalid_length(0, &"a node with data and children"))?;
+        let id = create(arena, parent, data)?;
+        let children = ChildrenSeed {
+            arena: &mut *arena,
+            parent: id,
+            depth: depth + 1,
+        };
+        let result = seq.next_element_seed(children).and_then(|children| {
+            children.ok_or_else(|| de::Error::invalid_length(1, &"a node with data and children"))
+        });
+        match result {
+            Ok(()) => Ok(id),
+            Err(error) => {
+                id.remove_subtree(arena);
+                Err(error)
+            }
+        }
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<NodeId, A::Error> {
+        let TreeSeed {
+            arena,
+            parent,
+            depth,
+        } = self;
+        let mut id = None;
+        // Children read before the data, e.g. from a map sorted by key, are
+        // kept as detached trees until their parent exists.
+        let mut pending = Vec::new();
+        let mut read_fields = || -> Result<NodeId, A::Error> {
+            while let Some(field) = map.next_key()? {
+                match field {
+                    Field::Data => {
+                        if id.is_some() {
+                            return Err(de::Error::duplicate_field("data"));
+                        }
+                        let data = map.next_value()?;
+                        let node = create(arena, parent, data)?;
+                        for child in pending.drain(..) {
+                            node.append(child, arena);
+                        }
+                        id = Some(node);
+                    }
+                    Field::Children => match id {
+                        Some(parent) => map.next_value_seed(ChildrenSeed {
+                            arena: &mut *arena,
+                            parent,
+                            depth: depth + 1,
+                        })?,
+                        None => pending.extend(map.next_value_seed(ForestSeed {
+                            arena: &mut *arena,
+                            depth: depth + 1,
+                        })?),
+                    },
+                }
+            }
+            id.ok_or_else(|| de::Error::missing_field("data"))
+        };
+        let result = read_fields();
+        if result.is_err() {
+            remove_trees(arena, id.into_iter().chain(pending));
+        }
+        result
+    }
+}
+
+/// Reads the list of children of `parent`.
+///
+/// On error, the children read so far are left for the seed of `parent` to
+/// remove.
+struct ChildrenSeed<'a, T> {
+    arena: &'a mut Arena<T>,
+    parent: NodeId,
+    /// Depth of the children.
+    depth: usize,
+}
+
+impl<'a, 'de, T: Deserialize<'de>> DeserializeSeed<'de> for ChildrenSeed<'a, T> {
+    type Value = ();
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<(), D::Error> {
+        deserializer.deserialize_seq(self)
+    }
+}
+
+impl<'a, 'de, T: Deserialize<'de>> Visitor<'de> for ChildrenSeed<'a, T> {
+    type Value = ();
+
+    fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("a list of nodes")
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<(), A::Error> {
+        let ChildrenSeed {
+            arena,
+            parent,
+            depth,
+        } = self;
+        loop {
+            let seed = TreeSeed {
+                arena: &mut *arena,
+                parent: Some(parent),
+                depth,
+            };
+            if seq.next_element_seed(seed)?.is_none() {
+                return Ok(());
+            }
+        }
+    }
+}
+
+/// Reads a list of trees.
+///
+/// On error, the trees read so far are removed again.
+struct ForestSeed<'a, T> {
+    arena: &'a mut Arena<T>,
+    /// Depth of the roots of the trees.
+    depth: usize,
+}
+
+impl<'a, 'de, T: Deserialize<'de>> DeserializeSeed<'de> for ForestSeed<'a, T> {
+    type Value = Vec<NodeId>;
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<Vec<NodeId>, D::Error> {
+        deserializer.deserialize_seq(self)
+    }
+}
+
+impl<'a, 'de, T: Deserialize<'de>> Visitor<'de> for ForestSeed<'a, T> {
+    type Value = Vec<NodeId>;
+
+    fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("a list of trees")
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Vec<NodeId>, A::Error> {
+        let ForestSeed { arena, depth } = self;
+        let mut roots = Vec::with_capacity(seq.size_hint().unwrap_or(0));
+        loop {
+            let seed = TreeSeed {
+                arena: &mut *arena,
+                parent: None,
+                depth,
+            };
+            match seq.next_element_seed(seed) {
+                Ok(Some(root)) => roots.push(root),
+                Ok(None) => return Ok(roots),
+                Err(error) => {
+                    remove_trees(arena, roots);
+                    return Err(error);
+                }
+            }
+        }
+    }
+}
+
+/// A serializable view of a node and its descendants, nested as
+/// `{"data": ..., "children": [...]}`.
+///
+/// Created by [`NodeId::serialize_tree`]. The nodes are written straight
+/// from the arena, without building an intermediate copy of the tree, and
+/// can be read back with [`Arena::deserialize_tree`].
+///
+/// [`NodeId::serialize_tree`]: struct.NodeId.html#method.serialize_tree
+/// [`Arena::deserialize_tree`]: struct.Arena.html#method.deserialize_tree
+pub struct SerializeTree<'a, T> {
+    arena: &'a Arena<T>,
+    id: NodeId,
+}
+
+impl<'a, T> SerializeTree<'a, T> {
+    pub(crate) fn new(arena: &'a Arena<T>, id: NodeId) -> Self {
+        SerializeTree { arena, id }
+    }
+}
+
+impl<'a, T: Serialize> Serialize for SerializeTree<'a, T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut node = serializer.serialize_struct("Node", FIELDS.len())?;
+        node.serialize_field("data", &self.arena[self.id].data)?;
+        node.serialize_field(
+            "children",
+            &SerializeChildren {
+                arena: self.arena,
+                parent: self.id,
+            },
+        )?;
+        node.end()
+    }
+}
+
+/// Writes the list of children of `parent`.
+struct SerializeChildren<'a, T> {
+    arena: &'a Arena<T>,
+    parent: NodeId,
+}
+
+impl<'a, T: Serialize> Serialize for SerializeChildren<'a, T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        // Some formats need the length upfront.
+        let len = self.parent.children(self.arena).count();
+        let mut children = serializer.serialize_seq(Some(len))?;
+        for child in self.parent.children(self.arena) {
+            children.serialize_element(&SerializeTree::new(self.arena, child))?;
+        }
+        children.end()
+    }
+}
+
+/// A serializable view of all the trees of an arena, as a list of nodes nested
+/// like in [`SerializeTree`].
+///
+/// Created by [`Arena::serialize_forest`]. Only the data of the nodes is
+/// written, the structure being implied by the nesting, and it can be read
+/// back with [`Arena::deserialize_forest`].
+///
+/// [`SerializeTree`]: struct.SerializeTree.html
+/// [`Arena::serialize_forest`]: struct.Arena.html#method.serialize_forest
+/// [`Arena::deserialize_forest`]: struct.Arena.html#method.deserialize_forest
+pub struct SerializeForest<'a, T> {
+    arena: &'a Arena<T>,
+}
+
+impl<'a, T> SerializeForest<'a, T> {
+    pub(crate) fn new(arena: &'a Arena<T>) -> Self {
+        SerializeForest { arena }
+    }
+
+    /// Returns the roots of the arena, in storage order.
+    fn roots(&self) -> impl Iterator<Item = NodeId> + 'a {
+        self.arena
+            .iter_pairs()
+            .filter(|(_, node)| node.parent.is_none())
+            .map(|(id, _)| id)
+    }
+}
+
+impl<'a, T: Serialize> Serialize for SerializeForest<'a, T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut trees = serializer.serialize_seq(Some(self.roots().count()))?;
+        for root in self.roots() {
+            trees.serialize_element(&SerializeTree::new(self.arena, root))?;
+        }
+        trees.end()
+    }
+}
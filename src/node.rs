@@ -1,19 +1,29 @@
 //! Node.
 
 #[cfg(not(feature = "std"))]
-use core::fmt;
+use core::{
+    fmt,
+    ops::{Deref, DerefMut},
+};
 
 #[cfg(feature = "deser")]
 use serde::{Deserialize, Serialize};
 
 #[cfg(feature = "std")]
-use std::fmt;
+use std::{
+    fmt,
+    ops::{Deref, DerefMut},
+};
 
 use crate::NodeId;
 
-#[derive(PartialEq, Eq, Clone, Debug)]
+#[derive(Clone, Debug)]
 #[cfg_attr(feature = "deser", derive(Deserialize, Serialize))]
 /// A node within a particular `Arena`.
+///
+/// Nodes compare equal if they have the same links and data. Their own ID
+/// and stable ID are left out, as they tell where and when the node was
+/// created rather than what it holds.
 pub struct Node<T> {
     // Keep these private (with read-only accessors) so that we can keep them
     // consistent. E.g. the parent of a node’s child is that node.
@@ -22,6 +32,12 @@ pub struct Node<T> {
     pub(crate) next_sibling: Option<NodeId>,
     pub(crate) first_child: Option<NodeId>,
     pub(crate) last_child: Option<NodeId>,
+    /// ID of the node itself.
+    #[cfg_attr(feature = "deser", serde(default = "NodeId::unknown"))]
+    pub(crate) id: NodeId,
+    /// Identifier which is unique over the whole history of the arena.
+    #[cfg_attr(feature = "deser", serde(default))]
+    pub(crate) stable_id: u64,
     /// The actual data which will be stored within the tree.
     pub(crate) data: T,
 }
@@ -37,14 +53,50 @@ impl<T> Node<T> {
         &mut self.data
     }
 
+    /// Returns the stable ID of this node.
+    ///
+    /// Unlike a `NodeId`, this is never reused within an arena, even after
+    /// the node is removed and its slot given to a new node.
+    /// See [`Arena::stable_ids`].
+    ///
+    /// [`Arena::stable_ids`]: struct.Arena.html#method.stable_ids
+    pub fn stable_id(&self) -> u64 {
+        self.stable_id
+    }
+
+    /// Returns the ID of this node.
+    ///
+    /// This lets code holding only a `&Node`, e.g. from [`Arena::iter`], find
+    /// the node in its arena again. A clone of the node keeps the ID of the
+    /// original, which only refers to the original node, in its arena.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use generational_indextree::Arena;
+    /// let mut arena = Arena::new();
+    /// let foo = arena.new_node("foo");
+    /// let bar = arena.new_node("bar");
+    ///
+    /// let ids = arena.iter().map(|node| node.id()).collect::<Vec<_>>();
+    /// assert_eq!(ids, vec![foo, bar]);
+    /// ```
+    ///
+    /// [`Arena::iter`]: struct.Arena.html#method.iter
+    pub fn id(&self) -> NodeId {
+        self.id
+    }
+
     /// Creates a new `Node` with the default state and the given data.
-    pub(crate) fn new(data: T) -> Self {
+    pub(crate) fn new(data: T, stable_id: u64, id: NodeId) -> Self {
         Self {
             parent: None,
             previous_sibling: None,
             next_sibling: None,
             first_child: None,
             last_child: None,
+            id,
+            stable_id,
             data,
         }
     }
@@ -248,6 +300,19 @@ impl<T> Node<T> {
     }
 }
 
+impl<T: PartialEq> PartialEq for Node<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.parent == other.parent
+            && self.previous_sibling == other.previous_sibling
+            && self.next_sibling == other.next_sibling
+            && self.first_child == other.first_child
+            && self.last_child == other.last_child
+            && self.data == other.data
+    }
+}
+
+impl<T: Eq> Eq for Node<T> {}
+
 impl<T> fmt::Display for Node<T> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         if let Some(parent) = self.parent {
@@ -278,3 +343,40 @@ impl<T> fmt::Display for Node<T> {
         Ok(())
     }
 }
+
+/// Gives direct access to the data of the node, like [`get`].
+///
+/// Methods of `Node` take precedence over the ones of the data, so e.g.
+/// `node.parent()` is always the parent of the node, even if the data has a
+/// `parent` method too. Use [`get`] to reach those.
+///
+/// # Examples
+///
+/// ```
+/// # use generational_indextree::Arena;
+/// struct Item {
+///     name: &'static str,
+/// }
+///
+/// let mut arena = Arena::new();
+/// let foo = arena.new_node(Item { name: "foo" });
+///
+/// assert_eq!(arena[foo].name, "foo");
+/// arena[foo].name = "bar";
+/// assert_eq!(arena[foo].get().name, "bar");
+/// ```
+///
+/// [`get`]: struct.Node.html#method.get
+impl<T> Deref for Node<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.data
+    }
+}
+
+impl<T> DerefMut for Node<T> {
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.data
+    }
+}
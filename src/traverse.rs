@@ -1,7 +1,22 @@
 //! Iterators.
 
+#[cfg(not(feature = "std"))]
+use alloc::{vec, vec::Vec};
+
 use crate::{Arena, Node, NodeId};
 
+/// Panics if an iterator took more than `per_node` steps for each node of
+/// `arena`, which is only possible if the links of the arena form a cycle.
+///
+/// This turns a walk which would never end into a panic in debug builds.
+#[cfg(debug_assertions)]
+fn check_steps<T>(arena: &Arena<T>, steps: usize, per_node: usize) {
+    assert!(
+        steps <= arena.count().saturating_mul(per_node),
+        "Should never fail: the links of the arena form a cycle"
+    );
+}
+
 macro_rules! impl_node_iterator {
     ($name:ident, $next:expr) => {
         impl<'a, T> Iterator for $name<'a, T> {
@@ -105,11 +120,137 @@ impl<'a, T> ReverseChildren<'a, T> {
 /// An iterator of the IDs of a given node and its descendants, as a pre-order depth-first search where children are visited in insertion order.
 ///
 /// i.e. node -> first child -> second child
-pub struct Descendants<'a, T>(Traverse<'a, T>);
+///
+/// The iterator is double-ended: walking it from the back yields the same
+/// nodes in exactly the reverse order, without buffering them. Starting from
+/// the back costs a walk down to the last node of the subtree.
+///
+/// The size of the subtree is not known upfront, so `size_hint` only tells
+/// whether a node is left. [`count_exact`] counts the remaining nodes without
+/// consuming the iterator, for callers which want to presize a buffer and
+/// can afford the extra walk.
+///
+/// In debug builds, the iterator panics if it yields more nodes than the
+/// arena holds, which is only possible if the links of the arena form a cycle.
+///
+/// [`count_exact`]: struct.Descendants.html#method.count_exact
+pub struct Descendants<'a, T> {
+    arena: &'a Arena<T>,
+    root: NodeId,
+    /// The next node yielded from the front.
+    front: NodeId,
+    /// The next node yielded from the back, `None` until first needed.
+    back: Option<NodeId>,
+    /// Whether both ends met, or the walk otherwise ended.
+    done: bool,
+    /// Number of nodes yielded so far.
+    #[cfg(debug_assertions)]
+    steps: usize,
+}
 
 impl<'a, T> Descendants<'a, T> {
     pub(crate) fn new(arena: &'a Arena<T>, current: NodeId) -> Self {
-        Self(Traverse::new(arena, current))
+        Self {
+            arena,
+            root: current,
+            front: current,
+            back: None,
+            done: false,
+            #[cfg(debug_assertions)]
+            steps: 0,
+        }
+    }
+
+    /// Returns the number of nodes left, without consuming the iterator.
+    ///
+    /// This walks over all the remaining nodes.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use generational_indextree::Arena;
+    /// # let mut arena = Arena::new();
+    /// # let n1 = arena.new_node("1");
+    /// # let n1_1 = arena.new_node("1_1");
+    /// # n1.append(n1_1, &mut arena);
+    /// # let n1_2 = arena.new_node("1_2");
+    /// # n1.append(n1_2, &mut arena);
+    /// #
+    /// // arena
+    /// // `-- 1
+    /// //     |-- 1_1
+    /// //     `-- 1_2
+    ///
+    /// let mut iter = n1.descendants(&arena);
+    /// let mut ids = Vec::with_capacity(iter.count_exact());
+    /// assert_eq!(ids.capacity(), 3);
+    /// iter.next();
+    /// assert_eq!(iter.count_exact(), 2);
+    /// ids.extend(iter);
+    /// ```
+    pub fn count_exact(&self) -> usize {
+        if self.done {
+            return 0;
+        }
+        let mut count = 1;
+        let mut node = self.front;
+        while Some(node) != self.back {
+            match self.next_of(node) {
+                Some(next) => node = next,
+                None => break,
+            }
+            count += 1;
+            #[cfg(debug_assertions)]
+            check_steps(self.arena, count, 1);
+        }
+        count
+    }
+
+    /// Records that a node was yielded.
+    fn yielded(&mut self) {
+        #[cfg(debug_assertions)]
+        {
+            self.steps += 1;
+            check_steps(self.arena, self.steps, 1);
+        }
+    }
+
+    /// Returns the node after `node` in the pre-order of the subtree.
+    fn next_of(&self, node: NodeId) -> Option<NodeId> {
+        if let Some(first_child) = self.arena[node].first_child {
+            return Some(first_child);
+        }
+        let mut node = node;
+        while node != self.root {
+            let current = &self.arena[node];
+            match current.next_sibling {
+                Some(next_sibling) => return Some(next_sibling),
+                // `parent` here can only be `None` if the tree has been
+                // modified, in which case the walk silently stops.
+                None => node = current.parent?,
+            }
+        }
+        None
+    }
+
+    /// Returns the node before `node` in the pre-order of the subtree.
+    fn previous_of(&self, node: NodeId) -> Option<NodeId> {
+        if node == self.root {
+            return None;
+        }
+        let current = &self.arena[node];
+        match current.previous_sibling {
+            Some(previous_sibling) => Some(self.last_of(previous_sibling)),
+            None => current.parent,
+        }
+    }
+
+    /// Returns the last node in the pre-order of the subtree of `node`.
+    fn last_of(&self, mut node: NodeId) -> NodeId {
+        while let Some(last_child) = self.arena[node].last_child {
+            node = last_child;
+        }
+        node
     }
 }
 
@@ -117,10 +258,50 @@ impl<'a, T> Iterator for Descendants<'a, T> {
     type Item = NodeId;
 
     fn next(&mut self) -> Option<NodeId> {
-        self.0.find_map(|edge| match edge {
-            NodeEdge::Start(node) => Some(node),
-            NodeEdge::End(_) => None,
-        })
+        if self.done {
+            return None;
+        }
+        let node = self.front;
+        if self.back == Some(node) {
+            self.done = true;
+        } else {
+            match self.next_of(node) {
+                Some(next) => self.front = next,
+                None => self.done = true,
+            }
+        }
+        self.yielded();
+        Some(node)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        if self.done {
+            (0, Some(0))
+        } else {
+            (1, None)
+        }
+    }
+}
+
+impl<'a, T> DoubleEndedIterator for Descendants<'a, T> {
+    fn next_back(&mut self) -> Option<NodeId> {
+        if self.done {
+            return None;
+        }
+        let node = match self.back {
+            Some(back) => back,
+            None => self.last_of(self.root),
+        };
+        if self.front == node {
+            self.done = true;
+        } else {
+            match self.previous_of(node) {
+                Some(previous) => self.back = Some(previous),
+                None => self.done = true,
+            }
+        }
+        self.yielded();
+        Some(node)
     }
 }
 
@@ -140,15 +321,34 @@ pub enum NodeEdge {
     End(NodeId),
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+/// What to do with a node visited by [`NodeId::edit_preorder`].
+///
+/// [`NodeId::edit_preorder`]: struct.NodeId.html#method.edit_preorder
+pub enum EditAction {
+    /// Keeps the node and goes on with its children.
+    Keep,
+    /// Removes the node and its descendants from the arena.
+    RemoveSubtree,
+    /// Keeps the node but does not visit its descendants.
+    SkipChildren,
+}
+
 #[derive(Clone)]
 /// An iterator of the "sides" of a node visited during a depth-first pre-order traversal,
 /// where node sides are visited start to end and children are visited in insertion order.
 ///
 /// i.e. node.start -> first child -> second child -> node.end
+///
+/// In debug builds, the iterator panics if it yields more than two edges per
+/// node of the arena, which is only possible if the links form a cycle.
 pub struct Traverse<'a, T> {
     arena: &'a Arena<T>,
     root: NodeId,
     next: Option<NodeEdge>,
+    /// Number of edges yielded so far.
+    #[cfg(debug_assertions)]
+    steps: usize,
 }
 
 impl<'a, T> Traverse<'a, T> {
@@ -157,6 +357,8 @@ impl<'a, T> Traverse<'a, T> {
             arena,
             root: current,
             next: Some(NodeEdge::Start(current)),
+            #[cfg(debug_assertions)]
+            steps: 0,
         }
     }
 
@@ -190,6 +392,12 @@ impl<'a, T> Iterator for Traverse<'a, T> {
     fn next(&mut self) -> Option<NodeEdge> {
         let next = self.next.take()?;
         self.next = self.next_of_next(next);
+        // Every node is entered and left once.
+        #[cfg(debug_assertions)]
+        {
+            self.steps += 1;
+            check_steps(self.arena, self.steps, 2);
+        }
         Some(next)
     }
 }
@@ -199,10 +407,17 @@ impl<'a, T> Iterator for Traverse<'a, T> {
 /// where nodes are visited end to start and children are visited in reverse insertion order.
 ///
 /// i.e. node.end -> second child -> first child -> node.start
+///
+/// Like [`Traverse`], this panics on cycles in debug builds.
+///
+/// [`Traverse`]: struct.Traverse.html
 pub struct ReverseTraverse<'a, T> {
     arena: &'a Arena<T>,
     root: NodeId,
     next: Option<NodeEdge>,
+    /// Number of edges yielded so far.
+    #[cfg(debug_assertions)]
+    steps: usize,
 }
 
 impl<'a, T> ReverseTraverse<'a, T> {
@@ -211,6 +426,8 @@ impl<'a, T> ReverseTraverse<'a, T> {
             arena,
             root: current,
             next: Some(NodeEdge::End(current)),
+            #[cfg(debug_assertions)]
+            steps: 0,
         }
     }
 
@@ -244,6 +461,124 @@ impl<'a, T> Iterator for ReverseTraverse<'a, T> {
     fn next(&mut self) -> Option<NodeEdge> {
         let next = self.next.take()?;
         self.next = self.next_of_next(next);
+        // Every node is entered and left once.
+        #[cfg(debug_assertions)]
+        {
+            self.steps += 1;
+            check_steps(self.arena, self.steps, 2);
+        }
         Some(next)
     }
 }
+
+#[derive(Clone)]
+/// An iterator walking two subtrees in lockstep, as a pre-order depth-first
+/// search where children are visited in insertion order.
+///
+/// Children are matched by position. Where only one of the subtrees has a
+/// node at some position, the whole subtree below it is paired with `None`.
+pub struct ZipDescendants<'a, T, U> {
+    arena_a: &'a Arena<T>,
+    arena_b: &'a Arena<U>,
+    /// Pairs of nodes still to be visited, the next one last.
+    stack: Vec<(Option<NodeId>, Option<NodeId>)>,
+}
+
+impl<'a, T, U> Iterator for ZipDescendants<'a, T, U> {
+    type Item = (Option<&'a T>, Option<&'a U>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (a, b) = self.stack.pop()?;
+        let a_children = a.map_or_else(Vec::new, |a| a.children(self.arena_a).collect());
+        let b_children = b.map_or_else(Vec::new, |b| b.children(self.arena_b).collect());
+        for index in (0..a_children.len().max(b_children.len())).rev() {
+            self.stack
+                .push((a_children.get(index).copied(), b_children.get(index).copied()));
+        }
+        Some((
+            a.map(|a| &self.arena_a[a].data),
+            b.map(|b| &self.arena_b[b].data),
+        ))
+    }
+}
+
+/// Returns an iterator walking the subtrees of `a` and `b` in lockstep, and
+/// yielding the data of both nodes at each position.
+///
+/// Children are matched by position, so the iterator never panics on
+/// mismatching shapes: where only one subtree has a node, the other side is
+/// `None`, for that node and all its descendants.
+///
+/// # Examples
+///
+/// ```
+/// # use generational_indextree::{zip_descendants, Arena};
+/// let mut arena_a = Arena::new();
+/// let a = arena_a.new_node("a");
+/// let a_1 = arena_a.new_node("a_1");
+/// a.append(a_1, &mut arena_a);
+/// let a_1_1 = arena_a.new_node("a_1_1");
+/// a_1.append(a_1_1, &mut arena_a);
+///
+/// let mut arena_b = Arena::new();
+/// let b = arena_b.new_node(0);
+/// let b_1 = arena_b.new_node(1);
+/// b.append(b_1, &mut arena_b);
+/// let b_2 = arena_b.new_node(2);
+/// b.append(b_2, &mut arena_b);
+///
+/// let mut iter = zip_descendants(a, &arena_a, b, &arena_b);
+/// assert_eq!(iter.next(), Some((Some(&"a"), Some(&0))));
+/// assert_eq!(iter.next(), Some((Some(&"a_1"), Some(&1))));
+/// assert_eq!(iter.next(), Some((Some(&"a_1_1"), None)));
+/// assert_eq!(iter.next(), Some((None, Some(&2))));
+/// assert_eq!(iter.next(), None);
+/// ```
+pub fn zip_descendants<'a, T, U>(
+    a: NodeId,
+    arena_a: &'a Arena<T>,
+    b: NodeId,
+    arena_b: &'a Arena<U>,
+) -> ZipDescendants<'a, T, U> {
+    ZipDescendants {
+        arena_a,
+        arena_b,
+        stack: vec![(Some(a), Some(b))],
+    }
+}
+
+#[cfg(debug_assertions)]
+#[test]
+#[should_panic(expected = "form a cycle")]
+fn descendants_panics_on_cycle() {
+    let mut arena = Arena::new();
+    let n1 = arena.new_node("1");
+    let n1_1 = arena.new_node("1_1");
+    n1.append(n1_1, &mut arena);
+    arena[n1_1].next_sibling = Some(n1_1);
+    n1.descendants(&arena).take(10).for_each(drop);
+}
+
+#[cfg(debug_assertions)]
+#[test]
+#[should_panic(expected = "form a cycle")]
+fn traverse_panics_on_cycle() {
+    let mut arena = Arena::new();
+    let n1 = arena.new_node("1");
+    let n1_1 = arena.new_node("1_1");
+    n1.append(n1_1, &mut arena);
+    arena[n1_1].next_sibling = Some(n1_1);
+    n1.traverse(&arena).take(10).for_each(drop);
+}
+
+#[cfg(debug_assertions)]
+#[test]
+#[should_panic(expected = "form a cycle")]
+fn reverse_traverse_panics_on_cycle() {
+    let mut arena = Arena::new();
+    let n1 = arena.new_node("1");
+    let n1_1 = arena.new_node("1_1");
+    n1.append(n1_1, &mut arena);
+    arena[n1_1].previous_sibling = Some(n1_1);
+    n1.reverse_traverse(&arena).take(10).for_each(drop);
+}
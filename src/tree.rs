@@ -0,0 +1,28 @@
+//! Nested tree literals.
+
+#[cfg(not(feature = "std"))]
+use alloc::vec::Vec;
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+/// An owned tree, given as a node with the trees of its children, as read by
+/// [`Arena::from_nested`].
+///
+/// [`Arena::from_nested`]: struct.Arena.html#method.from_nested
+pub struct Tree<T> {
+    /// Data of the root.
+    pub data: T,
+    /// Subtrees of the children of the root, in order.
+    pub children: Vec<Tree<T>>,
+}
+
+impl<T> Tree<T> {
+    /// Creates a tree from the data of its root and its subtrees.
+    pub fn new(data: T, children: Vec<Tree<T>>) -> Tree<T> {
+        Tree { data, children }
+    }
+
+    /// Creates a tree with a single node.
+    pub fn leaf(data: T) -> Tree<T> {
+        Tree::new(data, Vec::new())
+    }
+}
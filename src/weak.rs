@@ -0,0 +1,54 @@
+//! Weak node ID.
+
+#[cfg(feature = "deser")]
+use serde::{Deserialize, Serialize};
+
+use crate::{Arena, NodeId};
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "deser", derive(Deserialize, Serialize))]
+/// A node identifier which may no longer refer to a node.
+///
+/// Unlike a [`NodeId`], a `WeakNodeId` can not be used to access the arena
+/// directly: it has to be [upgraded] first, which checks that the node still
+/// exists. This makes it suitable for storing long-lived references to nodes
+/// which may be removed in the meantime.
+///
+/// [`NodeId`]: struct.NodeId.html
+/// [upgraded]: struct.WeakNodeId.html#method.upgrade
+pub struct WeakNodeId(NodeId);
+
+impl WeakNodeId {
+    /// Returns the `NodeId` if the node still exists in the given arena.
+    ///
+    /// This never panics. In debug builds, where IDs know the arena which
+    /// created them, the ID of a node of another arena gives `None` as well.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use generational_indextree::Arena;
+    /// let mut arena = Arena::new();
+    /// let foo = arena.new_node("foo");
+    /// let weak = foo.downgrade();
+    /// assert_eq!(weak.upgrade(&arena), Some(foo));
+    ///
+    /// foo.remove(&mut arena);
+    /// let _bar = arena.new_node("bar");
+    /// assert_eq!(weak.upgrade(&arena), None);
+    /// ```
+    pub fn upgrade<T>(&self, arena: &Arena<T>) -> Option<NodeId> {
+        // Unlike `Arena::get`, do not assert that the ID belongs to the arena.
+        if self.0.belongs_to(arena) && arena.nodes.contains(self.0.get_index()) {
+            Some(self.0)
+        } else {
+            None
+        }
+    }
+}
+
+impl From<NodeId> for WeakNodeId {
+    fn from(id: NodeId) -> WeakNodeId {
+        WeakNodeId(id)
+    }
+}
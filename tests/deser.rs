@@ -0,0 +1,320 @@
+//! Serialization round trips.
+
+#![cfg(feature = "deser")]
+
+use generational_indextree::{Arena, NodeId};
+
+/// Returns the data of the subtree of `root` with the depth of every node.
+fn shape<T: Clone>(root: NodeId, arena: &Arena<T>) -> Vec<(usize, T)> {
+    root.descendants(arena)
+        .map(|id| (id.ancestors(arena).count(), arena[id].get().clone()))
+        .collect()
+}
+
+/// Builds a forest of two trees, the first one with some removed nodes.
+fn sample() -> (Arena<String>, Vec<NodeId>) {
+    let mut arena = Arena::new();
+    let n1 = arena.new_node("1".to_string());
+    for data in &["1_1", "1_2", "1_3"] {
+        let child = arena.new_node(data.to_string());
+        n1.append(child, &mut arena);
+    }
+    let n1_2 = arena[n1].first_child().unwrap().following_siblings(&arena).nth(1).unwrap();
+    let n1_2_1 = arena.new_node("1_2_1".to_string());
+    n1_2.append(n1_2_1, &mut arena);
+    let removed = arena.new_node("removed".to_string());
+    n1.prepend(removed, &mut arena);
+    removed.remove(&mut arena);
+    let n2 = arena.new_node("2".to_string());
+    let n2_1 = arena.new_node("2_1".to_string());
+    n2.append(n2_1, &mut arena);
+    (arena, vec![n1, n2])
+}
+
+#[test]
+fn forest_round_trip() {
+    let (arena, roots) = sample();
+    let json = serde_json::to_string(&arena.serialize_forest()).unwrap();
+    assert!(!json.contains("parent"));
+
+    let mut restored = Arena::new();
+    let restored_roots = restored
+        .deserialize_forest(&mut serde_json::Deserializer::from_str(&json))
+        .unwrap();
+    assert_eq!(restored_roots.len(), 2);
+    for (&root, &restored_root) in roots.iter().zip(&restored_roots) {
+        assert_eq!(shape(restored_root, &restored), shape(root, &arena));
+    }
+    assert_eq!(restored.count(), arena.count());
+    assert!(restored.forest_eq(&arena));
+    assert_eq!(restored.validate(), Ok(()));
+}
+
+#[test]
+fn arena_round_trip() {
+    let (arena, roots) = sample();
+    let json = serde_json::to_string(&arena).unwrap();
+    let mut restored: Arena<String> = serde_json::from_str(&json).unwrap();
+
+    // The IDs of the original arena are valid in the restored one.
+    for &root in &roots {
+        assert_eq!(shape(root, &restored), shape(root, &arena));
+    }
+    assert_eq!(restored.validate(), Ok(()));
+    let n3 = restored.new_node("3".to_string());
+    roots[1].append(n3, &mut restored);
+    assert_eq!(restored.validate(), Ok(()));
+}
+
+#[cfg(feature = "json")]
+#[test]
+fn append_json_tree_partial_update() {
+    let mut arena = Arena::new();
+    let root = arena.new_node(0);
+    let existing = arena.new_node(1);
+    root.append(existing, &mut arena);
+
+    let update = serde_json::json!({
+        "data": 2,
+        "children": [{"data": 3, "children": [{"data": 4, "children": []}]}, {"data": 5}],
+    });
+    let added = root.append_json_tree(&update, &mut arena).unwrap();
+    assert_eq!(shape(added, &arena), vec![(2, 2), (3, 3), (4, 4), (3, 5)]);
+    assert_eq!(root.children(&arena).collect::<Vec<_>>(), vec![existing, added]);
+    assert_eq!(arena.validate(), Ok(()));
+}
+
+#[cfg(feature = "json")]
+#[test]
+fn append_json_tree_failures() {
+    let mut arena = Arena::new();
+    let root = arena.new_node(0);
+    let existing = arena.new_node(1);
+    root.append(existing, &mut arena);
+    let before = shape(root, &arena);
+
+    let invalid = vec![
+        // Invalid data of the root.
+        serde_json::json!({"data": "not a number", "children": [{"data": 2, "children": []}]}),
+        // Invalid data in a nested node.
+        serde_json::json!({"data": 2, "children": [{"data": 3, "children": [{"data": "x"}]}]}),
+        // Missing data.
+        serde_json::json!({"children": [{"data": 2, "children": []}]}),
+        // Invalid children after some valid ones.
+        serde_json::json!({"data": 2, "children": [{"data": 3, "children": []}, 4]}),
+        // Children which are not a list.
+        serde_json::json!({"data": 2, "children": {"data": 3}}),
+        // An unknown field.
+        serde_json::json!({"data": 2, "parent": 0}),
+    ];
+    for update in &invalid {
+        assert!(root.append_json_tree(update, &mut arena).is_err(), "{}", update);
+        assert_eq!(arena.count(), 2, "{}", update);
+        assert_eq!(shape(root, &arena), before);
+        assert_eq!(arena.validate(), Ok(()));
+    }
+}
+
+#[cfg(feature = "json")]
+#[test]
+fn append_json_tree_deep() {
+    // Deeper values overflow the stack when dropped.
+    let depth = 500;
+    let mut value = serde_json::json!({"data": 0});
+    for _ in 1..depth {
+        value = serde_json::json!({"data": 0, "children": [value]});
+    }
+
+    let mut arena = Arena::new();
+    let root = arena.new_node(0);
+    let added = root.append_json_tree(&value, &mut arena).unwrap();
+    assert_eq!(arena.count(), depth + 1);
+    let leaf = added.descendants(&arena).next_back().unwrap();
+    assert_eq!(leaf.ancestors(&arena).count(), depth + 1);
+
+    // A failure at the bottom removes the whole path again.
+    let mut invalid = serde_json::json!({"data": "x"});
+    for _ in 1..depth {
+        invalid = serde_json::json!({"data": 0, "children": [invalid]});
+    }
+    assert!(root.append_json_tree(&invalid, &mut arena).is_err());
+    assert_eq!(arena.count(), depth + 1);
+}
+
+#[test]
+fn tree_round_trip() {
+    let (arena, roots) = sample();
+    let json = serde_json::to_string(&roots[0].serialize_tree(&arena)).unwrap();
+
+    let mut restored = Arena::new();
+    let other = restored.new_node("other".to_string());
+    let root = restored
+        .deserialize_tree(&mut serde_json::Deserializer::from_str(&json))
+        .unwrap();
+    assert_eq!(shape(root, &restored), shape(roots[0], &arena));
+    assert!(restored[root].parent().is_none());
+    assert_eq!(restored.count(), 6);
+
+    // A failed read leaves the arena as it was.
+    let truncated = &json[..json.len() - 10];
+    assert!(restored
+        .deserialize_tree(&mut serde_json::Deserializer::from_str(truncated))
+        .is_err());
+    assert_eq!(restored.count(), 6);
+    assert!(restored.get(other).is_some());
+    assert_eq!(restored.validate(), Ok(()));
+}
+
+#[test]
+fn tree_capacity_limit() {
+    let json = r#"{"data": 1, "children": [{"data": 2, "children": []}]}"#;
+    let mut arena = Arena::with_capacity_limit(2);
+    let other = arena.new_node(0);
+
+    // Reading fails instead of panicking, and leaves the arena as it was.
+    let error = arena
+        .deserialize_tree(&mut serde_json::Deserializer::from_str(json))
+        .unwrap_err();
+    assert!(error.to_string().contains("capacity limit"), "{}", error);
+    assert_eq!(arena.count(), 1);
+
+    other.remove(&mut arena);
+    assert!(arena
+        .deserialize_tree(&mut serde_json::Deserializer::from_str(json))
+        .is_ok());
+    assert_eq!(arena.count(), 2);
+}
+
+#[cfg(feature = "json")]
+#[test]
+fn json_writer_round_trip() {
+    let (arena, roots) = sample();
+    let mut json = Vec::new();
+    roots[0].to_json_writer(&arena, &mut json).unwrap();
+    let expected = serde_json::to_vec(&roots[0].serialize_tree(&arena)).unwrap();
+    assert_eq!(json, expected);
+
+    let mut restored = Arena::new();
+    let root = restored.from_json_reader(&json[..]).unwrap();
+    assert_eq!(shape(root, &restored), shape(roots[0], &arena));
+
+    assert!(restored.from_json_reader(&json[..json.len() - 1]).is_err());
+    assert_eq!(restored.count(), 5);
+}
+
+#[cfg(feature = "json")]
+#[test]
+// Validating the whole chain after every append is quadratic.
+#[cfg_attr(feature = "paranoid", ignore)]
+fn json_writer_deep_tree() {
+    let mut arena = Arena::new();
+    let root = arena.new_node(0);
+    let mut last = root;
+    for depth in 1..100_000 {
+        let node = arena.new_node(depth);
+        last.append(node, &mut arena);
+        last = node;
+    }
+    let mut json = Vec::new();
+    root.to_json_writer(&arena, &mut json).unwrap();
+    assert!(json.ends_with(&b"]}".repeat(100_000)));
+}
+
+#[test]
+fn stable_ids_round_trip() {
+    use std::collections::HashMap;
+
+    fn stable_id<T>(arena: &Arena<T>, id: Option<NodeId>) -> Option<u64> {
+        id.map(|id| arena[id].stable_id())
+    }
+
+    // Every node refers to another node of the arena.
+    let mut arena = Arena::new();
+    let removed = arena.new_node(("removed", None));
+    let n1 = arena.new_node(("1", None));
+    removed.remove(&mut arena);
+    let n1_1 = arena.new_node(("1_1", Some(n1)));
+    n1.append(n1_1, &mut arena);
+    let n1_2 = arena.new_node(("1_2", Some(n1_1)));
+    n1.append(n1_2, &mut arena);
+    arena[n1].get_mut().1 = Some(n1_2);
+    let n2 = arena.new_node(("2", Some(n1_1)));
+
+    let mut json = Vec::new();
+    arena
+        .serialize_with_stable_ids(&mut serde_json::Serializer::new(&mut json), |data, ids| {
+            (data.0, data.1.map(|id| ids[&id]))
+        })
+        .unwrap();
+    let mut restored = Arena::deserialize_with_stable_ids(
+        &mut serde_json::Deserializer::from_slice(&json),
+        |(name, target): (&str, Option<u64>), ids: &HashMap<u64, NodeId>| {
+            (name.to_string(), target.map(|target| ids[&target]))
+        },
+    )
+    .unwrap();
+    assert_eq!(restored.validate(), Ok(()));
+    assert_eq!(restored.count(), 4);
+
+    // Nodes keep their stable IDs, and references point to the same nodes.
+    let by_stable_id = restored.nodes_by_stable_id();
+    for (id, node) in arena.iter_pairs() {
+        let restored_node = &restored[by_stable_id[&node.stable_id()]];
+        assert_eq!(restored_node.get().0, node.get().0);
+        assert_eq!(
+            stable_id(&restored, restored_node.get().1),
+            stable_id(&arena, node.get().1)
+        );
+        assert_eq!(
+            stable_id(&restored, restored_node.parent()),
+            stable_id(&arena, arena[id].parent())
+        );
+    }
+    let restored_n2 = by_stable_id[&arena[n2].stable_id()];
+    assert!(restored[restored_n2].parent().is_none());
+
+    let new = restored.new_node(("new".to_string(), None));
+    assert!(arena.iter().all(|node| node.stable_id() < restored[new].stable_id()));
+}
+
+#[test]
+fn arena_without_stable_ids() {
+    /// Removes the given key from all objects in `value`.
+    fn strip(value: &mut serde_json::Value, key: &str) {
+        match value {
+            serde_json::Value::Object(map) => {
+                map.remove(key);
+                map.values_mut().for_each(|value| strip(value, key));
+            }
+            serde_json::Value::Array(values) => {
+                values.iter_mut().for_each(|value| strip(value, key))
+            }
+            _ => {}
+        }
+    }
+
+    let (arena, roots) = sample();
+    let mut json = serde_json::to_value(&arena).unwrap();
+
+    // Without the counter, it is derived from the stable IDs of the nodes.
+    strip(&mut json, "next_stable_id");
+    let mut restored: Arena<String> = serde_json::from_value(json.clone()).unwrap();
+    let new = restored.new_node("new".to_string());
+    assert!(arena.iter().all(|node| node.stable_id() < restored[new].stable_id()));
+    assert_eq!(shape(roots[0], &restored), shape(roots[0], &arena));
+
+    // Without stable IDs, e.g. from before they existed, the nodes get new
+    // unique ones.
+    strip(&mut json, "stable_id");
+    let mut restored: Arena<String> = serde_json::from_value(json.clone()).unwrap();
+    restored.new_node("new".to_string());
+    let stable_ids = restored.iter().map(|node| node.stable_id()).collect::<Vec<_>>();
+    assert_eq!(stable_ids, (0..stable_ids.len() as u64).collect::<Vec<_>>());
+    assert_eq!(shape(roots[0], &restored), shape(roots[0], &arena));
+
+    // Without the IDs of the nodes, they are taken from where the nodes are.
+    strip(&mut json, "id");
+    let restored: Arena<String> = serde_json::from_value(json).unwrap();
+    assert!(restored.iter_pairs().all(|(id, node)| node.id() == id));
+    assert_eq!(restored, arena);
+}
@@ -0,0 +1,115 @@
+//! Tree diffs.
+
+use generational_indextree::{Arena, NodeEdge, NodeError, NodeId, TreeEdit};
+
+/// Minimal deterministic pseudo random number generator.
+struct Lcg(u64);
+
+impl Lcg {
+    fn next(&mut self, bound: usize) -> usize {
+        self.0 = self
+            .0
+            .wrapping_mul(6_364_136_223_846_793_005)
+            .wrapping_add(1_442_695_040_888_963_407);
+        ((self.0 >> 33) as usize) % bound
+    }
+}
+
+fn random_tree(rng: &mut Lcg, arena: &mut Arena<usize>, size: usize) -> NodeId {
+    let root = arena.new_node(rng.next(4));
+    let mut nodes = vec![root];
+    for _ in 1..size {
+        let parent = nodes[rng.next(nodes.len())];
+        let child = arena.new_node(rng.next(4));
+        parent.append(child, arena);
+        nodes.push(child);
+    }
+    root
+}
+
+fn shape(node: NodeId, arena: &Arena<usize>) -> Vec<Option<usize>> {
+    node.traverse(arena)
+        .map(|edge| match edge {
+            NodeEdge::Start(id) => Some(*arena[id].get()),
+            NodeEdge::End(_) => None,
+        })
+        .collect()
+}
+
+#[test]
+fn apply_diff_roundtrip() {
+    let mut rng = Lcg(42);
+    for _ in 0..200 {
+        let arena = &mut Arena::new();
+        let a_size = 1 + rng.next(12);
+        let b_size = 1 + rng.next(12);
+        let a = random_tree(&mut rng, arena, a_size);
+        let b = random_tree(&mut rng, arena, b_size);
+
+        let edits = a.diff(arena, b, arena);
+        a.apply_edits(arena, &edits).unwrap();
+        assert_eq!(shape(a, arena), shape(b, arena));
+        assert_eq!(arena.validate(), Ok(()));
+        assert!(a.diff(arena, b, arena).is_empty());
+    }
+}
+
+#[test]
+fn apply_move() {
+    let arena = &mut Arena::new();
+    let root = arena.new_node(0);
+    let n1 = arena.new_node(1);
+    root.append(n1, arena);
+    let n2 = arena.new_node(2);
+    root.append(n2, arena);
+    let n1_1 = arena.new_node(11);
+    n1.append(n1_1, arena);
+
+    let edits = [TreeEdit::Move {
+        from: vec![0, 0],
+        to: vec![1, 0],
+    }];
+    assert!(root.apply_edits(arena, &edits).is_ok());
+    assert_eq!(arena[n1_1].parent(), Some(n2));
+
+    // Moving a node into its own subtree can not be resolved.
+    let edits = [TreeEdit::Move {
+        from: vec![1],
+        to: vec![1, 0, 0],
+    }];
+    assert!(matches!(
+        root.apply_edits(arena, &edits),
+        Err(NodeError::InvalidPath)
+    ));
+    assert_eq!(root.children(arena).collect::<Vec<_>>(), vec![n1, n2]);
+}
+
+#[test]
+fn apply_invalid_path() {
+    let arena = &mut Arena::new();
+    let root = arena.new_node(0);
+    for edit in [
+        TreeEdit::Delete { path: vec![] },
+        TreeEdit::Delete { path: vec![0] },
+        TreeEdit::Update {
+            path: vec![0],
+            data: 1,
+        },
+        TreeEdit::Insert {
+            path: vec![1],
+            data: 1,
+        },
+        TreeEdit::Insert {
+            path: vec![],
+            data: 1,
+        },
+    ]
+    .iter()
+    {
+        assert!(matches!(
+            root.apply_edits(arena, std::slice::from_ref(edit)),
+            Err(NodeError::InvalidPath)
+        ));
+    }
+    assert_eq!(arena.count(), 1);
+}
@@ -1,4 +1,4 @@
-use generational_indextree::Arena;
+use generational_indextree::{Arena, Forest, LcaIndex, NodeError, NodeId};
 #[cfg(feature = "par_iter")]
 use rayon::prelude::*;
 
@@ -11,7 +11,7 @@ fn success_create() {
             new_counter += 1;
             arena.new_node(new_counter)
         }};
-    };
+    }
 
     let a = new!(); // 1
     assert!(a.checked_append(new!(), arena).is_ok()); // 2
@@ -51,9 +51,9 @@ fn success_detach() {
     let a = arena.new_node(1);
     let b = arena.new_node(1);
     assert!(a.checked_append(b, arena).is_ok());
-    assert_eq!(b.ancestors(arena).into_iter().count(), 2);
+    assert_eq!(b.ancestors(arena).count(), 2);
     b.detach(arena);
-    assert_eq!(b.ancestors(arena).into_iter().count(), 1);
+    assert_eq!(b.ancestors(arena).count(), 1);
 }
 
 #[test]
@@ -167,3 +167,513 @@ fn insert_removed_node() {
     assert!(n1.checked_insert_before(n2, &mut arena).is_err());
     assert!(n2.checked_insert_before(n1, &mut arena).is_err());
 }
+
+#[test]
+fn snapshot_restore() {
+    let arena = &mut Arena::new();
+    let a = arena.new_node(1);
+    let b = arena.new_node(2);
+    a.append(b, arena);
+    let snapshot = arena.snapshot();
+
+    b.remove(arena);
+    let c = arena.new_node(3);
+    a.append(c, arena);
+    assert!(arena.get(b).is_none());
+
+    arena.restore_from(snapshot);
+    assert_eq!(*arena[a].get(), 1);
+    assert_eq!(*arena[b].get(), 2);
+    assert_eq!(arena[b].parent(), Some(a));
+    assert_eq!(a.children(arena).collect::<Vec<_>>(), vec![b]);
+    // `c` reused the slot freed by `b`, but the generation tells them apart.
+    assert!(arena.get(c).is_none());
+    assert_eq!(arena.count(), 2);
+}
+
+#[test]
+// Validating the whole chain after every append is quadratic.
+#[cfg_attr(feature = "paranoid", ignore)]
+fn drop_deep_chain() {
+    let mut arena = Arena::with_capacity(1_000_000);
+    let mut parent = arena.new_node(0);
+    for i in 1..1_000_000 {
+        let child = arena.new_node(i);
+        parent.append(child, &mut arena);
+        parent = child;
+    }
+    assert_eq!(arena.count(), 1_000_000);
+    drop(arena);
+}
+
+#[test]
+fn forest_roots() {
+    let forest = &mut Forest::new();
+    let a = forest.add_tree(1);
+    let b = forest.add_tree(2);
+    let c = forest.add_tree(3);
+    let d = forest.add_tree(4);
+    forest.append(a, b);
+    forest.append(b, c);
+    forest.insert_after(a, d);
+    assert_eq!(forest.roots().collect::<Vec<_>>(), vec![a, d]);
+
+    forest.insert_after(c, d);
+    assert_eq!(forest.roots().collect::<Vec<_>>(), vec![a]);
+
+    forest.remove(b);
+    forest.detach(d);
+    assert_eq!(forest.roots().collect::<Vec<_>>(), vec![a, d]);
+
+    forest.remove_subtree(a);
+    assert_eq!(forest.roots().collect::<Vec<_>>(), vec![d]);
+    assert_eq!(forest.arena().count(), 1);
+
+    let scanned = Forest::from(forest.clone().into_arena());
+    assert_eq!(scanned.roots().collect::<Vec<_>>(), vec![d]);
+}
+
+#[cfg(debug_assertions)]
+#[test]
+#[should_panic(expected = "was not created by this arena")]
+fn foreign_id() {
+    let mut arena = Arena::new();
+    let mut another_arena = Arena::new();
+    let _ = another_arena.new_node(1);
+    let foo = arena.new_node(2);
+    let _ = &another_arena[foo];
+}
+
+#[cfg(debug_assertions)]
+#[test]
+fn upgrade_foreign_id() {
+    let mut arena = Arena::new();
+    let mut another_arena = Arena::new();
+    let _ = another_arena.new_node(1);
+    let foo = arena.new_node(2);
+    assert_eq!(foo.downgrade().upgrade(&another_arena), None);
+    assert_eq!(foo.downgrade().upgrade(&arena), Some(foo));
+}
+
+#[test]
+fn snapshot_keeps_arena_stamp() {
+    let mut arena = Arena::new();
+    let foo = arena.new_node(1);
+    let snapshot = arena.snapshot();
+    assert_eq!(*snapshot[foo].get(), 1);
+}
+
+#[test]
+fn wrap_children() {
+    let arena = &mut Arena::new();
+    let root = arena.new_node(0);
+    let children = (1..=5)
+        .map(|data| {
+            let child = arena.new_node(data);
+            root.append(child, arena);
+            child
+        })
+        .collect::<Vec<_>>();
+    let data = |arena: &Arena<i32>, node: NodeId| {
+        node.children(arena).map(|child| *arena[child].get()).collect::<Vec<_>>()
+    };
+
+    let all = root.wrap_children(0..5, 10, arena);
+    assert_eq!(data(arena, root), [10]);
+    assert_eq!(data(arena, all), [1, 2, 3, 4, 5]);
+    assert_eq!(arena[all].parent(), Some(root));
+    assert_eq!(arena[children[0]].previous_sibling(), None);
+    assert_eq!(arena[children[4]].next_sibling(), None);
+
+    let head = all.wrap_children(0..2, 11, arena);
+    let tail = all.wrap_children(2..4, 12, arena);
+    assert_eq!(data(arena, all), [11, 3, 12]);
+    assert_eq!(data(arena, head), [1, 2]);
+    assert_eq!(data(arena, tail), [4, 5]);
+    assert_eq!(arena[all].first_child(), Some(head));
+    assert_eq!(arena[all].last_child(), Some(tail));
+    assert_eq!(arena[children[2]].previous_sibling(), Some(head));
+    assert_eq!(arena[children[2]].next_sibling(), Some(tail));
+
+    let empty = all.wrap_children(3..3, 13, arena);
+    assert_eq!(data(arena, all), [11, 3, 12, 13]);
+    assert_eq!(arena[empty].first_child(), None);
+    assert_eq!(arena[all].last_child(), Some(empty));
+    assert_eq!(arena.validate(), Ok(()));
+}
+
+#[test]
+#[should_panic(expected = "invalid range")]
+fn wrap_children_out_of_range() {
+    let arena = &mut Arena::new();
+    let root = arena.new_node(0);
+    let child = arena.new_node(1);
+    root.append(child, arena);
+    root.wrap_children(0..2, 2, arena);
+}
+
+#[test]
+#[should_panic(expected = "capacity limit")]
+fn new_node_at_capacity_limit() {
+    let mut arena = Arena::with_capacity_limit(1);
+    arena.new_node(1);
+    arena.new_node(2);
+}
+
+#[test]
+fn evict_only_at_capacity_limit() {
+    let mut arena = Arena::with_capacity_limit(3);
+    arena.new_node("foo");
+    // Under its limit, but without a free slot.
+    arena.shrink_to(1);
+    assert_eq!(arena.slot_count(), 1);
+
+    let bar = arena.new_node_or_evict("bar", |_| panic!("nothing to evict"));
+    assert!(bar.is_ok());
+    assert!(arena.try_new_node("baz").is_ok());
+    assert_eq!(arena.count(), 3);
+
+    let mut evicted = false;
+    let qux = arena.new_node_or_evict("qux", |arena| {
+        evicted = true;
+        let oldest = arena.node_ids().next().unwrap();
+        oldest.remove(arena);
+    });
+    assert!(evicted && qux.is_ok());
+    assert_eq!(arena.count(), 3);
+    assert_eq!(arena.try_new_node("quux"), Err("quux"));
+}
+
+#[test]
+fn descendants_postorder() {
+    let arena = &mut Arena::new();
+    let root = arena.new_node(0);
+    let mut parent = root;
+    for data in 1..=3 {
+        let child = arena.new_node(data);
+        parent.append(child, arena);
+        parent.append(arena.new_node(data * 10), arena);
+        parent = child;
+    }
+
+    let order = root
+        .descendants_postorder(arena)
+        .map(|node| *arena[node].get())
+        .collect::<Vec<_>>();
+    assert_eq!(order, [3, 30, 2, 20, 1, 10, 0]);
+    assert_eq!(order.last(), Some(&0));
+    for node in root.descendants(arena) {
+        let position = |id| root.descendants_postorder(arena).position(|other| other == id);
+        if let Some(parent) = arena[node].parent() {
+            assert!(position(node) < position(parent));
+        }
+    }
+}
+
+#[test]
+fn graft_parent() {
+    let arena = &mut Arena::new();
+    let root = arena.new_node(0);
+    let children = (1..=3)
+        .map(|data| {
+            let child = arena.new_node(data);
+            root.append(child, arena);
+            child
+        })
+        .collect::<Vec<_>>();
+
+    let first = children[0].graft_parent(10, arena);
+    let last = children[2].graft_parent(30, arena);
+    assert_eq!(root.children(arena).collect::<Vec<_>>(), [first, children[1], last]);
+    assert_eq!(arena[root].first_child(), Some(first));
+    assert_eq!(arena[root].last_child(), Some(last));
+    assert_eq!(arena[first].previous_sibling(), None);
+    assert_eq!(arena[last].next_sibling(), None);
+    assert_eq!(arena[children[0]].parent(), Some(first));
+    assert_eq!(arena[children[0]].next_sibling(), None);
+    assert_eq!(arena[children[2]].previous_sibling(), None);
+
+    let top = root.graft_parent(-1, arena);
+    assert_eq!(arena[top].parent(), None);
+    assert_eq!(top.children(arena).collect::<Vec<_>>(), [root]);
+    assert_eq!(arena.validate(), Ok(()));
+}
+
+#[test]
+fn descendants_double_ended() {
+    let arena = &mut Arena::new();
+    let root = arena.new_node(0);
+    let mut parent = root;
+    for data in 1..=3 {
+        let child = arena.new_node(data);
+        parent.append(child, arena);
+        parent.append(arena.new_node(data * 10), arena);
+        child.append(arena.new_node(data * 100), arena);
+        parent = child;
+    }
+
+    for node in root.descendants(arena).collect::<Vec<_>>() {
+        let forward = node.descendants(arena).collect::<Vec<_>>();
+        let mut backward = node.descendants(arena).rev().collect::<Vec<_>>();
+        backward.reverse();
+        assert_eq!(forward, backward);
+
+        // Both ends meet exactly once, whichever end moves first.
+        for front_first in &[true, false] {
+            let mut iter = node.descendants(arena);
+            let (mut front, mut back) = (Vec::new(), Vec::new());
+            let mut use_front = *front_first;
+            loop {
+                let next = if use_front { iter.next() } else { iter.next_back() };
+                match next {
+                    Some(id) if use_front => front.push(id),
+                    Some(id) => back.push(id),
+                    None => break,
+                }
+                use_front = !use_front;
+            }
+            assert_eq!(iter.next(), None);
+            back.reverse();
+            front.extend(back);
+            assert_eq!(front, forward);
+        }
+    }
+}
+
+#[test]
+fn descendants_count_exact() {
+    let arena = &mut Arena::new();
+    let root = arena.new_node(0);
+    for data in 1..=4 {
+        let child = arena.new_node(data);
+        root.append(child, arena);
+        child.append(arena.new_node(data * 10), arena);
+    }
+
+    let mut iter = root.descendants(arena);
+    assert_eq!(iter.size_hint(), (1, None));
+    iter.next();
+    iter.next_back();
+    assert_eq!(iter.count_exact(), 7);
+    iter.next();
+    iter.next_back();
+    assert_eq!(iter.count_exact(), 5);
+    assert_eq!(iter.by_ref().count(), 5);
+    assert_eq!(iter.count_exact(), 0);
+    assert_eq!(iter.size_hint(), (0, Some(0)));
+    assert_eq!(root.descendants(arena).count_exact(), 9);
+}
+
+#[test]
+fn lca_index() {
+    let arena = &mut Arena::new();
+    let root = arena.new_node(0);
+    let mut nodes = vec![root];
+    // A tree mixing a long chain with bushy parts, to exercise every lifting
+    // level.
+    for data in 1..40 {
+        let parent = nodes[if data % 3 == 0 { data / 2 } else { data - 1 }];
+        let child = arena.new_node(data);
+        parent.append(child, arena);
+        nodes.push(child);
+    }
+    let other = arena.new_node(100);
+
+    let index = LcaIndex::build(root, arena);
+    for &a in &nodes {
+        for &b in &nodes {
+            assert_eq!(index.query(a, b), arena.lowest_common_ancestor_many(vec![a, b]));
+        }
+        assert_eq!(index.query(a, other), None);
+    }
+
+    // Node 1 has nodes 2 and 3 as children.
+    let subtree = LcaIndex::build(nodes[1], arena);
+    assert_eq!(subtree.query(nodes[2], nodes[3]), Some(nodes[1]));
+    assert_eq!(subtree.query(nodes[0], nodes[3]), None);
+}
+
+#[test]
+fn option_node_id_size() {
+    use std::mem::size_of;
+    assert_eq!(size_of::<Option<NodeId>>(), size_of::<NodeId>());
+}
+
+#[test]
+fn find_duplicate_subtrees_deterministic() {
+    let arena = &mut Arena::new();
+    let root = arena.new_node(0);
+    for data in 0..20 {
+        let child = arena.new_node(data % 4);
+        child.append(arena.new_node(data % 2), arena);
+        root.append(child, arena);
+    }
+
+    // Every call hashes with a freshly seeded `HashMap`.
+    let expected = arena.find_duplicate_subtrees();
+    assert_eq!(expected.len(), 6);
+    for _ in 0..10 {
+        assert_eq!(arena.find_duplicate_subtrees(), expected);
+    }
+}
+
+#[test]
+fn node_id_after_compact() {
+    let arena = &mut Arena::new();
+    let a = arena.new_node(1);
+    let b = arena.new_node(2);
+    a.append(b, arena);
+    a.remove(arena);
+    assert_eq!(arena[b].id(), b);
+
+    let remap = arena.compact_with(|_, _| {});
+    assert_eq!(arena[remap[&b]].id(), remap[&b]);
+    assert!(arena.iter_pairs().all(|(id, node)| node.id() == id));
+}
+
+#[test]
+fn shrink_to_keeps_nodes() {
+    let arena = &mut Arena::with_capacity(16);
+    let ids = (0..6).map(|i| arena.new_node(i)).collect::<Vec<_>>();
+    ids[0].append(ids[1], arena);
+    ids[0].append(ids[2], arena);
+    ids[3].remove(arena);
+
+    // Already small enough: nothing moves.
+    let remap = arena.shrink_to(32);
+    assert_eq!(arena.slot_count(), 16);
+    assert!(remap.iter().all(|(old, new)| old == new));
+
+    let remap = arena.shrink_to(3);
+    assert_eq!((arena.count(), arena.slot_count()), (5, 5));
+    assert_eq!(remap.len(), 5);
+    let children = remap[&ids[0]].children(arena).collect::<Vec<_>>();
+    assert_eq!(children, vec![remap[&ids[1]], remap[&ids[2]]]);
+    assert_eq!(arena.node_ids().map(|id| *arena[id].get()).collect::<Vec<_>>(), [0, 1, 2, 4, 5]);
+    assert_eq!(arena.validate(), Ok(()));
+}
+
+#[test]
+fn link_by_keys_errors() {
+    fn link(rows: &[(u32, Option<u32>)]) -> Result<Vec<NodeId>, NodeError> {
+        let mut arena = rows.iter().copied().collect::<Arena<_>>();
+        let result = arena.link_by_keys(|&(key, _)| key, |&(_, parent)| parent);
+        if result.is_err() {
+            // Nothing is linked on failure.
+            assert!(arena.iter().all(|node| node.parent().is_none()));
+        }
+        result
+    }
+
+    assert!(matches!(link(&[(1, None), (1, None)]), Err(NodeError::DuplicateKey)));
+    assert!(matches!(link(&[(1, None), (2, Some(3))]), Err(NodeError::DanglingParentKey)));
+    assert!(matches!(link(&[(1, Some(1))]), Err(NodeError::ParentKeyCycle)));
+    assert!(matches!(
+        link(&[(0, None), (1, Some(3)), (2, Some(1)), (3, Some(2))]),
+        Err(NodeError::ParentKeyCycle)
+    ));
+    assert_eq!(link(&[(1, None), (2, Some(1)), (3, Some(2))]).unwrap().len(), 1);
+}
+
+#[test]
+fn unwrap_keeps_sibling_positions() {
+    let arena = &mut Arena::new();
+    let root = arena.new_node(0);
+    let siblings = (1..=6).map(|data| arena.new_node(data)).collect::<Vec<_>>();
+    for &sibling in &siblings {
+        root.append(sibling, arena);
+    }
+    let middle = siblings[3];
+    let children = (10..13).map(|data| arena.new_node(data)).collect::<Vec<_>>();
+    for &child in &children {
+        middle.append(child, arena);
+    }
+
+    assert_eq!(middle.unwrap(arena), children);
+    let data = root.children(arena).map(|id| *arena[id].get()).collect::<Vec<_>>();
+    assert_eq!(data, vec![1, 2, 3, 10, 11, 12, 5, 6]);
+    assert_eq!(arena[children[0]].previous_sibling(), Some(siblings[2]));
+    assert_eq!(arena[children[2]].next_sibling(), Some(siblings[4]));
+    assert!(children.iter().all(|&child| arena[child].parent() == Some(root)));
+    assert_eq!(arena.validate(), Ok(()));
+
+    // The children of a root become roots.
+    assert_eq!(root.unwrap(arena).len(), 8);
+    assert!(siblings[0].following_siblings(arena).nth(1).is_none());
+    assert_eq!(arena.roots().count(), 8);
+}
+
+#[test]
+fn retain_reachable_from_unlinks_kept_roots() {
+    let arena = &mut Arena::new();
+    let roots = (0..3).map(|data| arena.new_node(data)).collect::<Vec<_>>();
+    roots[0].insert_after(roots[1], arena);
+    roots[1].insert_after(roots[2], arena);
+    let child = arena.new_node(10);
+    roots[1].append(child, arena);
+    let orphan = arena.new_node(20);
+    let stale = arena.new_node(30);
+    stale.remove(arena);
+
+    let keep = vec![roots[0], roots[2], stale, roots[0]];
+    assert_eq!(arena.retain_reachable_from(keep), 3);
+    assert!(arena.get(roots[1]).is_none());
+    assert!(arena.get(child).is_none());
+    assert!(arena.get(orphan).is_none());
+    assert!(arena[roots[0]].next_sibling().is_none());
+    assert!(arena[roots[2]].previous_sibling().is_none());
+    assert_eq!(arena.count(), 2);
+    assert_eq!(arena.validate(), Ok(()));
+}
+
+#[test]
+fn node_eq_ignores_provenance() {
+    let mut arena = Arena::new();
+    let foo = arena.new_node("foo");
+    let mut other = Arena::new();
+    other.new_node("removed").remove(&mut other);
+    let other_foo = other.new_node("foo");
+
+    assert_ne!(arena[foo].id(), other[other_foo].id());
+    assert_ne!(arena[foo].stable_id(), other[other_foo].stable_id());
+    assert_eq!(arena[foo], other[other_foo]);
+    let bar = arena.new_node("bar");
+    assert_ne!(arena[foo], arena[bar]);
+}
+
+#[test]
+fn get_disjoint_mut_orders() {
+    let mut arena = Arena::new();
+    let ids = (0..6).map(|data| arena.new_node(data)).collect::<Vec<_>>();
+    for len in 0..=4 {
+        let wanted = ids.iter().rev().step_by(2).take(len).copied().collect::<Vec<_>>();
+        let nodes = arena.get_disjoint_mut(&wanted).unwrap();
+        let data = nodes.iter().map(|node| *node.get()).collect::<Vec<_>>();
+        assert_eq!(data, vec![5, 3, 1].into_iter().take(len).collect::<Vec<_>>());
+    }
+    let duplicate = arena.get_disjoint_mut(&[ids[1], ids[2], ids[1]]);
+    assert!(matches!(duplicate, Err(NodeError::DuplicateNode)));
+}
+
+#[test]
+fn insert_children_at_moves_own_children() {
+    let arena = &mut Arena::new();
+    let root = arena.new_node(0);
+    let ids = (1..=4).map(|data| arena.new_node(data)).collect::<Vec<_>>();
+    for &id in &ids {
+        root.append(id, arena);
+    }
+    let data = |arena: &Arena<i32>| {
+        root.children(arena).map(|id| *arena[id].get()).collect::<Vec<_>>()
+    };
+
+    // The anchor itself is among the inserted nodes.
+    root.insert_children_at(2, vec![ids[2], ids[0]], arena);
+    assert_eq!(data(arena), [2, 4, 3, 1]);
+    assert_eq!(arena.validate(), Ok(()));
+
+    // A child before the position is moved, and counted once it is out.
+    root.insert_children_at(2, vec![ids[1]], arena);
+    assert_eq!(data(arena), [4, 3, 2, 1]);
+    assert_eq!(arena.validate(), Ok(()));
+}
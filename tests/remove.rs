@@ -386,3 +386,80 @@ fn middle_child_with_multiple_children() {
         ]
     );
 }
+
+#[test]
+fn remove_subtree_frees_nodes() {
+    let mut arena = Arena::new();
+    let n1 = arena.new_node("1");
+    let n1_1 = arena.new_node("1_1");
+    n1.append(n1_1, &mut arena);
+    let n1_1_1 = arena.new_node("1_1_1");
+    n1_1.append(n1_1_1, &mut arena);
+    let n1_1_2 = arena.new_node("1_1_2");
+    n1_1.append(n1_1_2, &mut arena);
+    let n1_2 = arena.new_node("1_2");
+    n1.append(n1_2, &mut arena);
+    // arena
+    // `-- 1
+    //     |-- 1_1 *
+    //     |   |-- 1_1_1
+    //     |   `-- 1_1_2
+    //     `-- 1_2
+    n1_1.remove_subtree(&mut arena);
+    assert_eq!(arena.count(), 2);
+    for &id in &[n1_1, n1_1_1, n1_1_2] {
+        assert!(arena.get(id).is_none());
+    }
+    assert_eq!(
+        n1.traverse(&arena).collect::<Vec<_>>(),
+        &[Start(n1), Start(n1_2), End(n1_2), End(n1)]
+    );
+}
+
+#[test]
+fn swap_remove_each_position() {
+    // Removes the child at `position` of a node with four children, the
+    // removed one having two children of its own.
+    fn swap_remove_at(position: usize) -> Vec<&'static str> {
+        let mut arena = Arena::new();
+        let n1 = arena.new_node("1");
+        for data in &["1_1", "1_2", "1_3", "1_4"] {
+            let child = arena.new_node(*data);
+            n1.append(child, &mut arena);
+        }
+        let removed = n1.children(&arena).nth(position).unwrap();
+        for data in &["x_1", "x_2"] {
+            let child = arena.new_node(*data);
+            removed.append(child, &mut arena);
+        }
+        assert!(removed.swap_remove(&mut arena).is_some());
+        assert_eq!(arena.validate(), Ok(()));
+        assert_eq!(arena.count(), 6);
+        n1.children(&arena).map(|id| *arena[id].get()).collect()
+    }
+
+    assert_eq!(swap_remove_at(0), ["1_4", "1_2", "1_3", "x_1", "x_2"]);
+    assert_eq!(swap_remove_at(1), ["1_1", "1_4", "1_3", "x_1", "x_2"]);
+    // Right before the last sibling, or the last sibling itself, nothing
+    // moves.
+    assert_eq!(swap_remove_at(2), ["1_1", "1_2", "1_4", "x_1", "x_2"]);
+    assert_eq!(swap_remove_at(3), ["1_1", "1_2", "1_3", "x_1", "x_2"]);
+}
+
+#[test]
+fn swap_remove_only_child_and_root() {
+    let mut arena = Arena::new();
+    let n1 = arena.new_node("1");
+    let n1_1 = arena.new_node("1_1");
+    n1.append(n1_1, &mut arena);
+    assert_eq!(n1_1.swap_remove(&mut arena), Some("1_1"));
+    assert!(arena[n1].first_child().is_none() && arena[n1].last_child().is_none());
+
+    // A root is removed in place, its children becoming roots.
+    let n1_1 = arena.new_node("1_1");
+    n1.append(n1_1, &mut arena);
+    assert_eq!(n1.swap_remove(&mut arena), Some("1"));
+    assert!(arena[n1_1].parent().is_none());
+    assert_eq!(n1.swap_remove(&mut arena), None);
+    assert_eq!(arena.validate(), Ok(()));
+}